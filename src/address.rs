@@ -0,0 +1,223 @@
+use std::fmt;
+
+use rosc::{OscMessage, OscType};
+
+/*
+    OSC address syntax validation.
+
+    An address is a '/'-separated path where each part is a non-empty run of printable ASCII,
+        excluding space and the characters reserved for OSC pattern matching: # * , / ? [ ] { }
+
+    Use the osc_addr! macro for address constants in source code, so that typos such as
+        "note_on" or "/note on" fail the build rather than silently never matching.
+ */
+
+const fn is_reserved(byte: u8) -> bool {
+    matches!(byte, b' ' | b'#' | b'*' | b',' | b'/' | b'?' | b'[' | b']' | b'{' | b'}')
+}
+
+// Byte index of the first syntax violation in the address, if any
+const fn first_violation(addr: &str) -> Option<usize> {
+    let bytes = addr.as_bytes();
+
+    if bytes.is_empty() || bytes[0] != b'/' {
+        return Some(0);
+    }
+
+    let mut i = 1;
+    let mut part_len = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte == b'/' {
+            if part_len == 0 {
+                return Some(i);
+            }
+            part_len = 0;
+        } else if byte < 0x21 || byte > 0x7E || is_reserved(byte) {
+            return Some(i);
+        } else {
+            part_len += 1;
+        }
+        i += 1;
+    }
+
+    if part_len == 0 {
+        return Some(bytes.len() - 1);
+    }
+
+    None
+}
+
+// Usable in const contexts, see osc_addr!
+pub const fn is_valid_address(addr: &str) -> bool {
+    first_violation(addr).is_none()
+}
+
+pub fn validate_address(addr: &str) -> Result<(), String> {
+    match first_violation(addr) {
+        None => Ok(()),
+        Some(0) if !addr.starts_with('/') => Err(format!("Invalid OSC address {:?}: must start with '/'", addr)),
+        Some(index) => Err(format!("Invalid OSC address {:?}: unexpected character or empty part at position {}", addr, index))
+    }
+}
+
+/*
+    Compile-time validated OSC address literal.
+    let addr: &'static str = osc_addr!("/note_on");
+ */
+#[macro_export]
+macro_rules! osc_addr {
+    ($addr:literal) => {{
+        const ADDR: &str = $addr;
+        const _: () = assert!($crate::address::is_valid_address(ADDR), "Invalid OSC address literal");
+        ADDR
+    }};
+}
+
+// How OscAddress treats letter case when normalizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CasePolicy {
+    #[default]
+    Preserve,
+    Lowercase
+}
+
+/*
+    Normalized OSC address, so that "/note_on/" and "/note_on" are considered the same address.
+    Accepted anywhere an address is registered or built (via From<&str>/From<String>).
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OscAddress(String);
+
+impl OscAddress {
+    pub fn new(addr: &str) -> OscAddress {
+        OscAddress::with_case_policy(addr, CasePolicy::Preserve)
+    }
+
+    pub fn with_case_policy(addr: &str, policy: CasePolicy) -> OscAddress {
+        let trimmed = addr.trim().trim_end_matches('/');
+        let mut normalized = if trimmed.starts_with('/') { trimmed.to_string() } else { format!("/{}", trimmed) };
+
+        if policy == CasePolicy::Lowercase {
+            normalized = normalized.to_lowercase();
+        }
+
+        OscAddress(normalized)
+    }
+
+    // Same as new(), but rejecting syntactically invalid addresses
+    pub fn parse(addr: &str) -> Result<OscAddress, String> {
+        let address = OscAddress::new(addr);
+        validate_address(address.as_str())?;
+        Ok(address)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn message(&self, args: Vec<OscType>) -> OscMessage {
+        OscMessage {
+            addr: self.0.clone(),
+            args
+        }
+    }
+
+    // True if this address contains OSC pattern characters, i.e. should be matched rather than compared
+    pub fn is_pattern(&self) -> bool {
+        self.0.contains(['?', '*', '[', '{'])
+    }
+
+    // OSC 1.0 pattern matching, with self as the pattern: ? * [abc] [!a-z] {foo,bar}
+    // E.g. OscAddress::new("/synth/{kick,snare}").matches("/synth/kick") == true
+    pub fn matches(&self, addr: &str) -> bool {
+        match_pattern(self.0.as_bytes(), OscAddress::new(addr).0.as_bytes())
+    }
+}
+
+fn match_pattern(pattern: &[u8], addr: &[u8]) -> bool {
+    match pattern.first() {
+        None => addr.is_empty(),
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            (0..=addr.len())
+                .take_while(|&len| len == 0 || addr[len - 1] != b'/')
+                .any(|len| match_pattern(rest, &addr[len..]))
+        },
+        Some(b'?') => {
+            matches!(addr.first(), Some(&c) if c != b'/') && match_pattern(&pattern[1..], &addr[1..])
+        },
+        Some(b'[') => {
+            let end = match pattern.iter().position(|&c| c == b']') {
+                Some(end) => end,
+                None => return false
+            };
+            match addr.first() {
+                Some(&c) if c != b'/' && match_set(&pattern[1..end], c) => match_pattern(&pattern[end + 1..], &addr[1..]),
+                _ => false
+            }
+        },
+        Some(b'{') => {
+            let end = match pattern.iter().position(|&c| c == b'}') {
+                Some(end) => end,
+                None => return false
+            };
+            pattern[1..end].split(|&c| c == b',').any(|alternative| {
+                addr.starts_with(alternative) && match_pattern(&pattern[end + 1..], &addr[alternative.len()..])
+            })
+        },
+        Some(&c) => addr.first() == Some(&c) && match_pattern(&pattern[1..], &addr[1..])
+    }
+}
+
+fn match_set(set: &[u8], c: u8) -> bool {
+    let (negated, set) = match set.first() {
+        Some(b'!') => (true, &set[1..]),
+        _ => (false, set)
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < set.len() {
+        if i + 2 < set.len() && set[i + 1] == b'-' {
+            found |= set[i] <= c && c <= set[i + 2];
+            i += 3;
+        } else {
+            found |= set[i] == c;
+            i += 1;
+        }
+    }
+
+    found != negated
+}
+
+impl fmt::Display for OscAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for OscAddress {
+    fn from(addr: &str) -> Self {
+        OscAddress::new(addr)
+    }
+}
+
+impl From<String> for OscAddress {
+    fn from(addr: String) -> Self {
+        OscAddress::new(&addr)
+    }
+}
+
+impl From<&String> for OscAddress {
+    fn from(addr: &String) -> Self {
+        OscAddress::new(addr)
+    }
+}
+
+// Allows OscMessage { addr: address.into(), ... }
+impl From<OscAddress> for String {
+    fn from(addr: OscAddress) -> Self {
+        addr.0
+    }
+}
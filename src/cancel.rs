@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rosc::{OscMessage, OscType};
+
+/*
+    Cancellation convention for long running operations such as renders and imports:
+    ["/jdw/cancel", "render-42"]
+    aborts the operation with the given id, agreed on with the requester as for progress
+        updates (see progress.rs).
+
+    Operations poll a CancellationToken, tripped through a CancellationRegistry when a cancel
+        message for their id arrives. OSCStack does both for handlers registered with
+        on_message_cancellable.
+ */
+
+pub const CANCEL_ADDR: &str = "/jdw/cancel";
+
+pub fn cancel_message(id: &str) -> OscMessage {
+    OscMessage { addr: CANCEL_ADDR.to_string(), args: vec![OscType::String(id.to_string())] }
+}
+
+// Id to cancel, None if the message is not a well-formed cancel message
+pub fn cancelled_id(msg: &OscMessage) -> Option<&str> {
+    match (msg.addr.as_str(), msg.args.first()) {
+        (CANCEL_ADDR, Some(OscType::String(id))) => Some(id),
+        _ => None
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    // For bailing out with ? between steps of an operation
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() { Err("Operation was cancelled".to_string()) } else { Ok(()) }
+    }
+}
+
+/*
+    Tokens of the operations currently running, by id. Cloned handles share the registry.
+    Cancelling an id that is not registered does nothing, so operations should register
+        before they can be cancelled, i.e. before replying to or acknowledging the request.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CancellationRegistry {
+    tokens: Arc<Mutex<HashMap<String, CancellationToken>>>
+}
+
+impl CancellationRegistry {
+    pub fn new() -> CancellationRegistry {
+        CancellationRegistry::default()
+    }
+
+    fn tokens(&self) -> MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.tokens.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Fresh token for the id, replacing any previous one
+    pub fn register(&self, id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.tokens().insert(id.to_string(), token.clone());
+        token
+    }
+
+    // Trips the token of the id, returning false if no operation is registered under it
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.tokens().get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            },
+            None => false
+        }
+    }
+
+    // Trips the tokens of all registered operations, returning how many there were
+    pub fn cancel_all(&self) -> usize {
+        let tokens = self.tokens();
+        for token in tokens.values() {
+            token.cancel();
+        }
+        tokens.len()
+    }
+
+    // Like register, but finishes the operation when the guard is dropped, also on panic
+    pub fn register_scoped(&self, id: &str) -> Registration {
+        Registration { registry: self.clone(), id: id.to_string(), token: self.register(id) }
+    }
+
+    // Called when the operation ends; keeps a newer registration under the same id
+    pub fn finish(&self, id: &str, token: &CancellationToken) {
+        let mut tokens = self.tokens();
+        if tokens.get(id).is_some_and(|registered| Arc::ptr_eq(&registered.cancelled, &token.cancelled)) {
+            tokens.remove(id);
+        }
+    }
+
+    pub fn is_registered(&self, id: &str) -> bool {
+        self.tokens().contains_key(id)
+    }
+
+    // Sorted
+    pub fn registered_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.tokens().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+// Operation registered with CancellationRegistry::register_scoped, finished when dropped
+#[derive(Debug)]
+pub struct Registration {
+    registry: CancellationRegistry,
+    id: String,
+    token: CancellationToken
+}
+
+impl Registration {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.registry.finish(&self.id, &self.token);
+    }
+}
@@ -0,0 +1,323 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rosc::{OscMessage, OscPacket};
+
+use crate::codec::{default_codec, SharedCodec};
+use crate::congestion::AdaptiveRate;
+use crate::local::{self, LOCAL_SCHEME, LOCAL_SENDER};
+use crate::hello::Capabilities;
+use crate::immediate;
+use crate::model;
+use crate::progress::Progress;
+use crate::queue_update::QueueUpdate;
+use crate::text;
+use crate::time_value::TimeEncoding;
+
+// Largest payload a single IPv4 UDP datagram can carry
+pub const MAX_UDP_PAYLOAD: usize = 65507;
+
+// What to do when an encoded packet exceeds the configured datagram limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizePolicy {
+    // Log a warning but send anyway, leaving fragmentation to the OS
+    #[default]
+    Warn,
+    // Refuse to send and return an error
+    Reject
+}
+
+/*
+    How OscClient spaces out consecutive sends, so that bursts (e.g. a large queue sent in a
+        tight loop) don't overflow the receive buffer on the other end. Order is preserved,
+        also between threads sharing a client.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pacing {
+    // Send right away
+    #[default]
+    Unpaced,
+    // At least this long between consecutive sends
+    Gap(Duration),
+    // Max bytes per second, each send waiting until the previous one has passed at this rate
+    Bandwidth(u64)
+}
+
+impl Pacing {
+    // How long the next send has to wait after sending the given amount of bytes
+    pub fn delay_after(&self, bytes: usize) -> Duration {
+        match self {
+            Pacing::Unpaced | Pacing::Bandwidth(0) => Duration::ZERO,
+            Pacing::Gap(gap) => *gap,
+            Pacing::Bandwidth(bytes_per_second) => {
+                let nanos = bytes as u128 * 1_000_000_000 / *bytes_per_second as u128;
+                Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+            }
+        }
+    }
+}
+
+/*
+    Sending counterpart to OSCStack: encodes packets and sends them over UDP to a fixed target.
+
+    let client = OscClient::new("127.0.0.1:13339")?;
+    client.send_message(OscMessage { addr: "/note_on".to_string(), args: vec![...] })?;
+
+    A "local:<name>" target sends straight to the receiver or OSCStack bound to the same url
+        in this process instead, see local.rs.
+ */
+pub struct OscClient {
+    destination: Destination,
+    datagram_limit: usize,
+    oversize_policy: OversizePolicy,
+    codec: SharedCodec,
+    dry_run: bool,
+    // Packets that would have been sent while in dry run mode
+    recorded: Mutex<Vec<OscPacket>>,
+    pacing: Pacing,
+    // Overrides pacing when set, see with_adaptive_rate
+    adaptive_rate: Option<AdaptiveRate>,
+    // Earliest time the next paced send may go out; held while sending to keep order
+    next_send: Mutex<Option<Instant>>,
+    time_encoding: TimeEncoding
+}
+
+enum Destination {
+    Udp { socket: UdpSocket, target: SocketAddr },
+    Local(String)
+}
+
+impl OscClient {
+    // Sends from an OS-assigned local port
+    pub fn new(target_url: &str) -> Result<OscClient, String> {
+        OscClient::with_local_socket(target_url, |target| {
+            let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+            UdpSocket::bind(local).map_err(|e| format!("Failed to bind client socket: {}", e))
+        })
+    }
+
+    /*
+        Sends from the given local port, for peers and firewalls that only accept packets
+            (or send replies) from known source ports. Port 0 behaves like new().
+     */
+    pub fn from_source_port(target_url: &str, port: u16) -> Result<OscClient, String> {
+        OscClient::with_local_socket(target_url, |target| {
+            let local = if target.is_ipv4() { format!("0.0.0.0:{}", port) } else { format!("[::]:{}", port) };
+            UdpSocket::bind(&local).map_err(|e| format!("Failed to bind client socket to {}: {}", local, e))
+        })
+    }
+
+    /*
+        Sends from a socket set up by the caller. The standard library cannot enable address
+            reuse before binding, so this is the way to share a source port with other sockets
+            (e.g. an OscReceiver) by creating it with SO_REUSEADDR/SO_REUSEPORT via socket2.
+     */
+    pub fn from_socket(target_url: &str, socket: UdpSocket) -> Result<OscClient, String> {
+        OscClient::with_local_socket(target_url, |_| Ok(socket))
+    }
+
+    // Local targets need no socket, so create_socket is only called for network targets
+    fn with_local_socket(target_url: &str, create_socket: impl FnOnce(SocketAddr) -> Result<UdpSocket, String>) -> Result<OscClient, String> {
+        let destination = match local::local_name(target_url) {
+            Some(name) => Destination::Local(name.to_string()),
+            None => {
+                let target = target_url.to_socket_addrs()
+                    .map_err(|e| format!("Invalid target address {}: {}", target_url, e))?
+                    .next()
+                    .ok_or(format!("Target address {} did not resolve", target_url))?;
+                Destination::Udp { socket: create_socket(target)?, target }
+            }
+        };
+
+        Ok(OscClient {
+            destination,
+            datagram_limit: MAX_UDP_PAYLOAD,
+            oversize_policy: OversizePolicy::Warn,
+            codec: default_codec(),
+            dry_run: false,
+            recorded: Mutex::new(Vec::new()),
+            pacing: Pacing::Unpaced,
+            adaptive_rate: None,
+            next_send: Mutex::new(None),
+            time_encoding: TimeEncoding::DecimalString
+        })
+    }
+
+    /*
+        Packets above the limit are handled according to the policy instead of being
+            silently fragmented (or dropped) by the OS. For links crossing a real network
+            1472 (ethernet MTU minus headers) avoids IP fragmentation entirely.
+     */
+    pub fn with_datagram_limit(mut self, limit: usize, policy: OversizePolicy) -> OscClient {
+        self.datagram_limit = limit;
+        self.oversize_policy = policy;
+        self
+    }
+
+    /*
+        Space out sends according to the pacing, blocking the sending thread as needed.
+        Dry runs are never paced.
+     */
+    pub fn with_pacing(mut self, pacing: Pacing) -> OscClient {
+        self.pacing = pacing;
+        self
+    }
+
+    /*
+        Pace sends at the current rate of the handle instead, and split queue updates into
+            parts of its current batch size, see congestion.rs.
+     */
+    pub fn with_adaptive_rate(mut self, rate: AdaptiveRate) -> OscClient {
+        self.adaptive_rate = Some(rate);
+        self
+    }
+
+    // Pacing applied to the next send
+    pub fn pacing(&self) -> Pacing {
+        self.adaptive_rate.as_ref().map_or(self.pacing, AdaptiveRate::pacing)
+    }
+
+    // Encode packets with something other than plain OSC, see PacketCodec
+    pub fn with_codec(mut self, codec: SharedCodec) -> OscClient {
+        self.codec = codec;
+        self
+    }
+
+    /*
+        Write the times of timed_msg bundles sent with this client in the given encoding,
+            nested ones included, e.g. floats for a receiver outside JDW. Times the encoding
+            can't represent exactly are still sent as decimal strings, see TimeEncoding.
+     */
+    pub fn with_time_encoding(mut self, encoding: TimeEncoding) -> OscClient {
+        self.time_encoding = encoding;
+        self
+    }
+
+    /*
+        In dry run mode packets are encoded and checked as usual, but logged in text form and
+            recorded instead of sent. For rehearsing NRT renders or checking generated
+            sequences without a running server; see recorded_packets.
+     */
+    pub fn dry_run(mut self, enabled: bool) -> OscClient {
+        self.dry_run = enabled;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    // Takes the packets recorded in dry run mode so far, oldest first
+    pub fn recorded_packets(&self) -> Vec<OscPacket> {
+        std::mem::take(&mut *self.recorded.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    // LOCAL_SENDER for local targets, see target_url
+    pub fn target(&self) -> SocketAddr {
+        match &self.destination {
+            Destination::Udp { target, .. } => *target,
+            Destination::Local(_) => LOCAL_SENDER
+        }
+    }
+
+    // Address packets are sent from; None for local targets
+    pub fn source_addr(&self) -> Option<SocketAddr> {
+        match &self.destination {
+            Destination::Udp { socket, .. } => socket.local_addr().ok(),
+            Destination::Local(_) => None
+        }
+    }
+
+    pub fn target_url(&self) -> String {
+        match &self.destination {
+            Destination::Udp { target, .. } => target.to_string(),
+            Destination::Local(name) => format!("{}{}", LOCAL_SCHEME, name)
+        }
+    }
+
+    pub fn send(&self, packet: &OscPacket) -> Result<(), String> {
+        let reencoded;
+        let packet = match self.time_encoding {
+            TimeEncoding::DecimalString => packet,
+            encoding => {
+                reencoded = model::encode_times(packet, encoding);
+                &reencoded
+            }
+        };
+        let bytes = self.codec.encode(packet)?;
+
+        if bytes.len() > self.datagram_limit {
+            let problem = format!("Encoded packet of {} bytes exceeds datagram limit of {} bytes", bytes.len(), self.datagram_limit);
+            match self.oversize_policy {
+                OversizePolicy::Warn => warn!("{}, sending anyway", problem),
+                OversizePolicy::Reject => return Err(problem)
+            }
+        }
+
+        if self.dry_run {
+            info!("[dry run] {} -> {}", self.target_url(), text::to_text(packet));
+            self.recorded.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(packet.clone());
+            return Ok(());
+        }
+
+        let pacing = self.pacing();
+        if pacing == Pacing::Unpaced {
+            return self.transmit(bytes);
+        }
+
+        let mut next_send = self.next_send.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(wait) = next_send.map(|at| at.saturating_duration_since(Instant::now())) {
+            thread::sleep(wait);
+        }
+        let delay = pacing.delay_after(bytes.len());
+        let result = self.transmit(bytes);
+        *next_send = Some(Instant::now() + delay);
+        result
+    }
+
+    fn transmit(&self, bytes: Vec<u8>) -> Result<(), String> {
+        match &self.destination {
+            Destination::Udp { socket, target } => {
+                socket.send_to(&bytes, target).map_err(|e| format!("Failed to send to {}: {}", target, e))?;
+            },
+            Destination::Local(name) => local::send(name, bytes)?
+        }
+        Ok(())
+    }
+
+    // Sends the packets in order, stopping at the first failure; paced as any other sends
+    pub fn send_all<'p>(&self, packets: impl IntoIterator<Item = &'p OscPacket>) -> Result<(), String> {
+        packets.into_iter().try_for_each(|packet| self.send(packet))
+    }
+
+    pub fn send_message(&self, msg: OscMessage) -> Result<(), String> {
+        self.send(&OscPacket::Message(msg))
+    }
+
+    // Sends /jdw/panic as an immediate bundle, see immediate.rs
+    pub fn panic(&self) -> Result<(), String> {
+        self.send(&OscPacket::Bundle(immediate::panic_bundle()))
+    }
+
+    // Progress update for a long running operation, see progress.rs
+    pub fn send_progress(&self, id: &str, pct: f32, message: &str) -> Result<(), String> {
+        self.send(&OscPacket::Bundle(Progress::new(id, pct, message).to_bundle()))
+    }
+
+    // Announces what the sending service supports, see hello.rs
+    pub fn send_hello(&self, capabilities: &Capabilities) -> Result<(), String> {
+        self.send_message(capabilities.hello_message())
+    }
+
+    // Incremental change to a queue on the receiving end, see queue_update.rs; split up when adaptive
+    pub fn send_queue_update(&self, update: &QueueUpdate) -> Result<(), String> {
+        match &self.adaptive_rate {
+            Some(rate) => update.chunks(rate.batch_size()).iter()
+                .try_for_each(|part| self.send(&OscPacket::Bundle(part.to_bundle()))),
+            None => self.send(&OscPacket::Bundle(update.to_bundle()))
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use rosc::OscPacket;
+
+/*
+    Wire format used to turn packets into bytes and back.
+    OscReceiver, OSCStack and OscClient all default to plain OSC (OscCodec) but can be
+        given any other codec, e.g. for OSC-over-JSON or SLIP-framed links:
+
+    let client = OscClient::new("127.0.0.1:13339")?.with_codec(Arc::new(MyJsonCodec));
+ */
+pub trait PacketCodec {
+    fn encode(&self, packet: &OscPacket) -> Result<Vec<u8>, String>;
+
+    // Decode the first packet in bytes, returning it along with any unconsumed remainder
+    fn decode<'b>(&self, bytes: &'b [u8]) -> Result<(&'b [u8], OscPacket), String>;
+}
+
+// Shared between the stack, its receiver and any clients
+pub type SharedCodec = Arc<dyn PacketCodec + Send + Sync>;
+
+// Standard OSC 1.0 binary encoding, as implemented by rosc
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OscCodec;
+
+impl PacketCodec for OscCodec {
+    fn encode(&self, packet: &OscPacket) -> Result<Vec<u8>, String> {
+        rosc::encoder::encode(packet).map_err(|e| e.to_string())
+    }
+
+    fn decode<'b>(&self, bytes: &'b [u8]) -> Result<(&'b [u8], OscPacket), String> {
+        rosc::decoder::decode_udp(bytes).map_err(|e| e.to_string())
+    }
+}
+
+pub fn default_codec() -> SharedCodec {
+    Arc::new(OscCodec)
+}
@@ -0,0 +1,522 @@
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use bigdecimal::BigDecimal;
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::deadline;
+use crate::echo::{self, EchoReply};
+use crate::envelope::{CurveShape, Envelope};
+use crate::hello::Capabilities;
+use crate::model::{FromTaggedBundle, InfoParsing, TaggedBundle, TimedOSCPacket};
+use crate::nrt;
+use crate::progress::Progress;
+use crate::queue_update::{QueueItem, QueueUpdate};
+use crate::reply::{self, Reply};
+use crate::supercollider::{AddAction, NFree, NSet, SNew};
+use crate::time_value::TimeEncoding;
+use crate::{cancel, immediate, sequence, session};
+
+/*
+    Frozen wire fixtures of every JDW message and bundle format, so that changes to building
+        or parsing can't silently break older JDW services on the same network. Run from CI
+        (or a service's own tests) with:
+
+    jdw_osc_lib::compat::verify_wire_compat()?;
+
+    Each fixture is checked both ways:
+        - The current builders must produce exactly the frozen bytes
+        - The current parsers must read the frozen bytes back into the expected values
+    Fixtures are never edited in place. A deliberate wire change bumps WIRE_FORMAT_VERSION
+        (and the crate's major version) and adds new fixtures next to the old ones, which
+        then only need to keep parsing.
+ */
+
+pub const WIRE_FORMAT_VERSION: u32 = 1;
+
+struct Fixture {
+    name: &'static str,
+    // Frozen bytes, as lowercase hex
+    hex: &'static str,
+    build: fn() -> Result<Vec<u8>, String>,
+    check: fn(&[u8]) -> Result<(), String>
+}
+
+const FIXTURES: &[Fixture] = &[
+    Fixture { name: "s_new", hex: "2f735f6e657700002c7369696973667366000000626c697000000000000003e90000000100000002667265710000000043dc0000616d70003f000000", build: build_s_new, check: check_s_new },
+    Fixture { name: "n_set", hex: "2f6e5f73657400002c69736600000000000003e9676174650000000000000000", build: build_n_set, check: check_n_set },
+    Fixture { name: "n_free", hex: "2f6e5f66726565002c696900000003e9000003ea", build: build_n_free, check: check_n_free },
+    Fixture { name: "timed_msg", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d73670000000000001c2f74696d65645f6d73675f696e666f002c730000302e3235000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_timed_msg, check: check_timed_msg },
+    Fixture { name: "timed_msg_with_info", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d7367000000000000382f74696d65645f6d73675f696e666f002c73667366736600312e35003f400000766f696365000000400000006f72646572000000bf8000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_timed_msg_with_info, check: check_timed_msg_with_info },
+    Fixture { name: "timed_msg_float_time", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d7367000000000000182f74696d65645f6d73675f696e666f002c6600003e8000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_timed_msg_float_time, check: check_timed_msg_float_time },
+    Fixture { name: "timed_msg_double_time", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d73670000000000001c2f74696d65645f6d73675f696e666f002c6400003fd00000000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_timed_msg_double_time, check: check_timed_msg_double_time },
+    Fixture { name: "sequenced_bundle", hex: "2362756e646c65000000000000000001000000282f62756e646c655f696e666f000000002c737369000000006e6f74655f6f6e0073657100000000290000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_sequenced_bundle, check: check_sequenced_bundle },
+    Fixture { name: "deadline_bundle_info", hex: "2362756e646c65000000000000000001000000342f62756e646c655f696e666f000000002c7373006e6f74655f6f6e00313731383030303030302e323530303030303030000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_deadline_bundle_info, check: check_deadline_bundle_info },
+    Fixture { name: "deadline_header", hex: "2362756e646c650000000000000000010000002c2f6a64772f646561646c696e650000002c730000313731383030303030302e323530303030303030000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_deadline_header, check: check_deadline_header },
+    Fixture { name: "deadline_key", hex: "2362756e646c65000000000000000001000000442f62756e646c655f696e666f000000002c737373000000006e6f74655f6f6e00646561646c696e6500000000313731383030303030302e323530303030303030000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_deadline_key, check: check_deadline_key },
+    Fixture { name: "envelope", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c730000656e76656c6f706500000000000000282f656e76656c6f70655f706f696e74002c7366736600000030000000000000006c696e0000000000000000282f656e76656c6f70655f706f696e74002c73667366000000302e31003f8000006c696e00000000000000002c2f656e76656c6f70655f706f696e74002c7366736600000032000000000000006375727665000000c0800000", build: build_envelope, check: check_envelope },
+    Fixture { name: "nrt_score", hex: "000000502362756e646c650000000000000000000000003c2f735f6e657700002c7369696973667366000000626c697000000000000003e90000000100000002667265710000000043dc0000616d70003f000000000000342362756e646c65000000000080000000000000202f6e5f73657400002c69736600000000000003e9676174650000000000000000000000282362756e646c65000000000100000000000000142f6e5f66726565002c696900000003e9000003ea", build: build_nrt_score, check: check_nrt_score },
+    Fixture { name: "reply_ok", hex: "2f6a64772f7265706c792f6f6b0000002c7300002f6e72745f7265636f726400", build: build_reply_ok, check: check_reply_ok },
+    Fixture { name: "reply_error", hex: "2f6a64772f7265706c792f6572726f72000000002c7373002f6e72745f7265636f7264004e6f2073796e7468206e616d656420626c697000", build: build_reply_error, check: check_reply_error },
+    Fixture { name: "progress", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000070726f6772657373000000000000003c2f70726f67726573730000002c7366730000000072656e6465722d34320000004216000052656e646572696e6720626172203132206f662033320000", build: build_progress, check: check_progress },
+    Fixture { name: "cancel", hex: "2f6a64772f63616e63656c002c73000072656e6465722d3432000000", build: build_cancel, check: check_cancel },
+    Fixture { name: "immediate", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c730000696d6d656469617465000000000000142f6e5f66726565002c696900000003e9000003ea", build: build_immediate, check: check_immediate },
+    Fixture { name: "panic", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c730000696d6d656469617465000000000000102f6a64772f70616e696300002c000000", build: build_panic, check: check_panic },
+    Fixture { name: "session", hex: "2362756e646c65000000000000000001000000302f62756e646c655f696e666f000000002c737373000000006e6f74655f6f6e0073657373696f6e006c6976652d6100000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_session, check: check_session },
+    Fixture { name: "echo", hex: "2f6a64772f6563686f0000002c73690070696e670000000000000007", build: build_echo, check: check_echo },
+    Fixture { name: "echo_reply", hex: "2f6a64772f6563686f2f7265706c79002c7369740000000070696e670000000000000007ea11180040000000", build: build_echo_reply, check: check_echo_reply },
+    Fixture { name: "queue_add", hex: "2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000071756575655f616464000000000000182f71756575655f696e666f002c7300006c6f6f702d610000000000d02362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000071756575655f6974656d0000000000202f71756575655f6974656d5f696e666f000000002c7300006b69636b2d310000000000742362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d73670000000000001c2f74696d65645f6d73675f696e666f002c730000302e3235000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000000000ec2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000071756575655f6974656d0000000000202f71756575655f6974656d5f696e666f000000002c730000736e6172652d3200000000902362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d7367000000000000382f74696d65645f6d73675f696e666f002c73667366736600312e35003f400000766f696365000000400000006f72646572000000bf8000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_queue_add, check: check_queue_add },
+    Fixture { name: "queue_replace", hex: "2362756e646c65000000000000000001000000242f62756e646c655f696e666f000000002c73000071756575655f7265706c616365000000000000182f71756575655f696e666f002c7300006c6f6f702d610000000000d02362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000071756575655f6974656d0000000000202f71756575655f6974656d5f696e666f000000002c7300006b69636b2d310000000000742362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d73670000000000001c2f74696d65645f6d73675f696e666f002c730000302e3235000000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000000000ec2362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000071756575655f6974656d0000000000202f71756575655f6974656d5f696e666f000000002c730000736e6172652d3200000000902362756e646c65000000000000000001000000202f62756e646c655f696e666f000000002c73000074696d65645f6d7367000000000000382f74696d65645f6d73675f696e666f002c73667366736600312e35003f400000766f696365000000400000006f72646572000000bf8000000000001c2f6e6f74655f6f6e000000002c736600626c69700000000043dc0000", build: build_queue_replace, check: check_queue_replace },
+    Fixture { name: "queue_remove", hex: "2362756e646c65000000000000000001000000242f62756e646c655f696e666f000000002c73000071756575655f72656d6f766500000000000000182f71756575655f696e666f002c7300006c6f6f702d610000000000242f71756575655f6974656d5f696473002c7373006b69636b2d310000736e6172652d3200", build: build_queue_remove, check: check_queue_remove },
+    Fixture { name: "hello", hex: "2f6a64772f68656c6c6f00002c736969737373730000000073616d706c65720000000001000000026e6f74655f6f6e0074696d65645f6d73670000002f6e5f73657400002f735f6e65770000", build: build_hello, check: check_hello },
+    Fixture { name: "gap_report", hex: "2f6a64772f676170000000002c6969000000002a0000002d", build: build_gap_report, check: check_gap_report }
+];
+
+// Checks every fixture, reporting all failures rather than just the first
+pub fn verify_wire_compat() -> Result<(), String> {
+    let failures: Vec<String> = FIXTURES.iter()
+        .filter_map(|fixture| verify_fixture(fixture).err().map(|e| format!("{}: {}", fixture.name, e)))
+        .collect();
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Wire format {} compatibility broken:\n{}", WIRE_FORMAT_VERSION, failures.join("\n")))
+    }
+}
+
+// Names of all fixtures, for reporting
+pub fn fixture_names() -> Vec<&'static str> {
+    FIXTURES.iter().map(|fixture| fixture.name).collect()
+}
+
+fn verify_fixture(fixture: &Fixture) -> Result<(), String> {
+    let frozen = from_hex(fixture.hex)?;
+    let built = (fixture.build)()?;
+    if built != frozen {
+        return Err(format!("built bytes differ from fixture\n  expected: {}\n  built:    {}", fixture.hex, to_hex(&built)));
+    }
+    (fixture.check)(&frozen)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err("Fixture has an odd number of hex digits".to_string());
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+fn encode(packet: OscPacket) -> Result<Vec<u8>, String> {
+    rosc::encoder::encode(&packet).map_err(|e| e.to_string())
+}
+
+fn decode_message(bytes: &[u8]) -> Result<OscMessage, String> {
+    match rosc::decoder::decode_udp(bytes).map_err(|e| e.to_string())? {
+        (_, OscPacket::Message(msg)) => Ok(msg),
+        (_, OscPacket::Bundle(_)) => Err("expected a message, got a bundle".to_string())
+    }
+}
+
+fn decode_bundle(bytes: &[u8]) -> Result<OscBundle, String> {
+    match rosc::decoder::decode_udp(bytes).map_err(|e| e.to_string())? {
+        (_, OscPacket::Bundle(bundle)) => Ok(bundle),
+        (_, OscPacket::Message(msg)) => Err(format!("expected a bundle, got message {}", msg.addr))
+    }
+}
+
+fn expect<T: PartialEq + std::fmt::Debug>(what: &str, expected: T, parsed: T) -> Result<(), String> {
+    if expected == parsed {
+        Ok(())
+    } else {
+        Err(format!("parsed {} {:?}, expected {:?}", what, parsed, expected))
+    }
+}
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).expect("Fixture decimals are valid")
+}
+
+fn s_new() -> SNew {
+    SNew::new("blip", 1001)
+        .with_target(AddAction::Tail, 2)
+        .with_control("freq", 440.0)
+        .with_control("amp", 0.5)
+}
+
+fn build_s_new() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(s_new().to_message()))
+}
+
+fn check_s_new(bytes: &[u8]) -> Result<(), String> {
+    expect("s_new", s_new(), SNew::from_message(&decode_message(bytes)?)?)
+}
+
+fn n_set() -> NSet {
+    NSet::new(1001).with_control("gate", 0.0)
+}
+
+fn build_n_set() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(n_set().to_message()))
+}
+
+fn check_n_set(bytes: &[u8]) -> Result<(), String> {
+    expect("n_set", n_set(), NSet::from_message(&decode_message(bytes)?)?)
+}
+
+fn n_free() -> NFree {
+    NFree::new(vec![1001, 1002])
+}
+
+fn build_n_free() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(n_free().to_message()))
+}
+
+fn check_n_free(bytes: &[u8]) -> Result<(), String> {
+    expect("n_free", n_free(), NFree::from_message(&decode_message(bytes)?)?)
+}
+
+fn note_on() -> OscPacket {
+    OscPacket::Message(OscMessage {
+        addr: "/note_on".to_string(),
+        args: vec![OscType::String("blip".to_string()), OscType::Float(440.0)]
+    })
+}
+
+fn timed_msg() -> TimedOSCPacket {
+    TimedOSCPacket::new(decimal("0.25"), note_on())
+}
+
+fn timed_msg_with_info() -> TimedOSCPacket {
+    TimedOSCPacket::new(decimal("1.5"), note_on())
+        .with_probability(0.75)
+        .with_metadata("voice", 2.0)
+        .with_order(-1)
+}
+
+fn check_timed(expected: TimedOSCPacket, bytes: &[u8]) -> Result<(), String> {
+    let parsed = TimedOSCPacket::from_bundle(TaggedBundle::new(&decode_bundle(bytes)?)?)?;
+    expect("time", expected.time, parsed.time)?;
+    expect("packet", expected.packet, parsed.packet)?;
+    expect("probability", expected.probability, parsed.probability)?;
+    expect("metadata", expected.metadata, parsed.metadata)?;
+    expect("order", expected.order, parsed.order)
+}
+
+fn build_timed_msg() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(timed_msg().to_bundle()))
+}
+
+fn check_timed_msg(bytes: &[u8]) -> Result<(), String> {
+    check_timed(timed_msg(), bytes)
+}
+
+// The time must also have been sent in the encoding itself
+fn check_timed_encoding(encoding: TimeEncoding, bytes: &[u8]) -> Result<(), String> {
+    let tagged = TaggedBundle::new(&decode_bundle(bytes)?)?;
+    let info = tagged.get_message(0)?;
+    expect("time encoding", Some(encoding), info.args.first().and_then(TimeEncoding::of))?;
+    check_timed(timed_msg(), bytes)
+}
+
+fn build_timed_msg_float_time() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(timed_msg().to_bundle_with(TimeEncoding::FloatArg)?))
+}
+
+fn check_timed_msg_float_time(bytes: &[u8]) -> Result<(), String> {
+    check_timed_encoding(TimeEncoding::FloatArg, bytes)
+}
+
+fn build_timed_msg_double_time() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(timed_msg().to_bundle_with(TimeEncoding::DoubleArg)?))
+}
+
+fn check_timed_msg_double_time(bytes: &[u8]) -> Result<(), String> {
+    check_timed_encoding(TimeEncoding::DoubleArg, bytes)
+}
+
+fn build_timed_msg_with_info() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(timed_msg_with_info().to_bundle()))
+}
+
+fn check_timed_msg_with_info(bytes: &[u8]) -> Result<(), String> {
+    check_timed(timed_msg_with_info(), bytes)
+}
+
+fn tagged(info: OscMessage, content: Vec<OscPacket>) -> OscBundle {
+    OscBundle {
+        timetag: OscTime { seconds: 0, fractional: 1 },
+        content: std::iter::once(OscPacket::Message(info)).chain(content).collect()
+    }
+}
+
+fn note_on_info() -> OscMessage {
+    OscMessage { addr: "/bundle_info".to_string(), args: vec![OscType::String("note_on".to_string())] }
+}
+
+fn build_sequenced_bundle() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(tagged(sequence::with_sequence(note_on_info(), 41), vec![note_on()])))
+}
+
+fn check_sequenced_bundle(bytes: &[u8]) -> Result<(), String> {
+    let bundle = decode_bundle(bytes)?;
+    expect("seq", Some(41), sequence::sequence_number(&bundle))?;
+    let tagged = TaggedBundle::new_with_info(&bundle, InfoParsing::Strict)?;
+    expect("info seq", Some(41), tagged.info.seq)?;
+    expect("tag", "note_on".to_string(), tagged.bundle_tag)
+}
+
+fn deadline() -> std::time::SystemTime {
+    UNIX_EPOCH + Duration::from_millis(1_718_000_000_250)
+}
+
+/*
+    Deadline as the bare second arg of /bundle_info, no longer built: legacy args such as
+        ["/bundle_info", "note_on", "1"] were mistaken for deadlines. Such bundles must keep
+        parsing as plain tagged bundles, without a deadline.
+ */
+fn build_deadline_bundle_info() -> Result<Vec<u8>, String> {
+    let mut info = note_on_info();
+    info.args.push(OscType::String("1718000000.250000000".to_string()));
+    encode(OscPacket::Bundle(tagged(info, vec![note_on()])))
+}
+
+fn check_deadline_bundle_info(bytes: &[u8]) -> Result<(), String> {
+    let bundle = decode_bundle(bytes)?;
+    expect("deadline", None, deadline::valid_until(&bundle))?;
+    let tagged = TaggedBundle::new_with_info(&bundle, InfoParsing::Lenient)?;
+    expect("info deadline", None, tagged.info.deadline)?;
+    expect("tag", "note_on".to_string(), tagged.bundle_tag)
+}
+
+fn build_deadline_key() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(tagged(deadline::bundle_info_with_deadline("note_on", deadline()), vec![note_on()])))
+}
+
+fn check_deadline_key(bytes: &[u8]) -> Result<(), String> {
+    let bundle = decode_bundle(bytes)?;
+    expect("deadline", Some(deadline()), deadline::valid_until(&bundle))?;
+    let tagged = TaggedBundle::new_with_info(&bundle, InfoParsing::Strict)?;
+    expect("info deadline", true, tagged.info.deadline.is_some())?;
+    expect("tag", "note_on".to_string(), tagged.bundle_tag)
+}
+
+fn build_deadline_header() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(tagged(deadline::deadline_header(deadline()), vec![note_on()])))
+}
+
+fn check_deadline_header(bytes: &[u8]) -> Result<(), String> {
+    expect("deadline", Some(deadline()), deadline::valid_until(&decode_bundle(bytes)?))
+}
+
+fn envelope() -> Envelope {
+    Envelope::new(0.0)
+        .then(decimal("0.1"), 1.0, CurveShape::Linear)
+        .then(decimal("2"), 0.0, CurveShape::Curve(-4.0))
+}
+
+fn build_envelope() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(envelope().to_bundle()))
+}
+
+fn check_envelope(bytes: &[u8]) -> Result<(), String> {
+    expect("envelope", envelope(), Envelope::from_tagged_bundle(TaggedBundle::new(&decode_bundle(bytes)?)?)?)
+}
+
+fn score() -> Vec<TimedOSCPacket> {
+    vec![
+        TimedOSCPacket::new(decimal("0"), OscPacket::Message(s_new().to_message())),
+        TimedOSCPacket::new(decimal("0.5"), OscPacket::Message(n_set().to_message())),
+        TimedOSCPacket::new(decimal("1"), OscPacket::Message(n_free().to_message()))
+    ]
+}
+
+fn build_nrt_score() -> Result<Vec<u8>, String> {
+    nrt::write_score(&score())
+}
+
+fn check_nrt_score(bytes: &[u8]) -> Result<(), String> {
+    let parsed = nrt::read_score(bytes)?;
+    expect("score length", score().len(), parsed.len())?;
+    for (expected, parsed) in score().into_iter().zip(parsed) {
+        expect("time", expected.time, parsed.time)?;
+        expect("packet", expected.packet, parsed.packet)?;
+    }
+    Ok(())
+}
+
+fn request() -> OscMessage {
+    OscMessage { addr: "/nrt_record".to_string(), args: vec![OscType::String("render-42".to_string())] }
+}
+
+fn build_reply_ok() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(reply::ok_reply(&request())))
+}
+
+fn check_reply_ok(bytes: &[u8]) -> Result<(), String> {
+    expect("reply", Reply::Ok { request: "/nrt_record".to_string() }, Reply::from_message(&decode_message(bytes)?)?)
+}
+
+fn build_reply_error() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(reply::error_reply(&request(), "No synth named blip")))
+}
+
+fn check_reply_error(bytes: &[u8]) -> Result<(), String> {
+    let expected = Reply::Error { request: "/nrt_record".to_string(), reason: "No synth named blip".to_string() };
+    expect("reply", expected, Reply::from_message(&decode_message(bytes)?)?)
+}
+
+fn progress() -> Progress {
+    Progress::new("render-42", 37.5, "Rendering bar 12 of 32")
+}
+
+fn build_progress() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(progress().to_bundle()))
+}
+
+fn check_progress(bytes: &[u8]) -> Result<(), String> {
+    expect("progress", progress(), Progress::from_tagged_bundle(TaggedBundle::new(&decode_bundle(bytes)?)?)?)
+}
+
+fn build_cancel() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(cancel::cancel_message("render-42")))
+}
+
+fn check_cancel(bytes: &[u8]) -> Result<(), String> {
+    expect("cancelled id", Some("render-42"), cancel::cancelled_id(&decode_message(bytes)?))
+}
+
+fn n_free_packet() -> OscPacket {
+    OscPacket::Message(n_free().to_message())
+}
+
+fn build_immediate() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(immediate::immediate_bundle(vec![n_free_packet()])))
+}
+
+fn check_immediate_contents(expected: Vec<OscPacket>, bytes: &[u8]) -> Result<(), String> {
+    let bundle = decode_bundle(bytes)?;
+    expect("immediate", true, immediate::is_immediate(&OscPacket::Bundle(bundle.clone())))?;
+    expect("contents", expected, TaggedBundle::new(&bundle)?.contents)
+}
+
+fn check_immediate(bytes: &[u8]) -> Result<(), String> {
+    check_immediate_contents(vec![n_free_packet()], bytes)
+}
+
+fn build_panic() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(immediate::panic_bundle()))
+}
+
+fn check_panic(bytes: &[u8]) -> Result<(), String> {
+    check_immediate_contents(vec![OscPacket::Message(immediate::panic_message())], bytes)
+}
+
+fn build_session() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(tagged(session::with_session(note_on_info(), "live-a"), vec![note_on()])))
+}
+
+fn check_session(bytes: &[u8]) -> Result<(), String> {
+    let bundle = decode_bundle(bytes)?;
+    expect("session", Some("live-a".to_string()), session::session_id(&bundle))?;
+    let tagged = TaggedBundle::new_with_info(&bundle, InfoParsing::Strict)?;
+    expect("info session", Some("live-a".to_string()), tagged.info.session)?;
+    expect("tag", "note_on".to_string(), tagged.bundle_tag)
+}
+
+fn echo_args() -> Vec<OscType> {
+    vec![OscType::String("ping".to_string()), OscType::Int(7)]
+}
+
+fn build_echo() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(echo::echo_message(echo_args())))
+}
+
+fn check_echo(bytes: &[u8]) -> Result<(), String> {
+    let msg = decode_message(bytes)?;
+    expect("addr", echo::ECHO_ADDR, msg.addr.as_str())?;
+    expect("args", echo_args(), msg.args)
+}
+
+fn build_echo_reply() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(echo::echo_reply(&echo::echo_message(echo_args()), deadline())))
+}
+
+// OSC times are rounded to about a quarter of a nanosecond, well within the millisecond used
+fn check_echo_reply(bytes: &[u8]) -> Result<(), String> {
+    let reply = EchoReply::from_message(&decode_message(bytes)?)?;
+    expect("args", echo_args(), reply.args)?;
+    let received_at = reply.received_at.duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?;
+    expect("received at", 1_718_000_000_250, received_at.as_millis())
+}
+
+fn queue_items() -> Vec<QueueItem> {
+    vec![QueueItem::new("kick-1", timed_msg()), QueueItem::new("snare-2", timed_msg_with_info())]
+}
+
+fn check_queue_update(expected: QueueUpdate, bytes: &[u8]) -> Result<(), String> {
+    expect("queue update", expected, QueueUpdate::from_tagged_bundle(TaggedBundle::new(&decode_bundle(bytes)?)?)?)
+}
+
+fn queue_add() -> QueueUpdate {
+    QueueUpdate::Add { queue: "loop-a".to_string(), items: queue_items() }
+}
+
+fn build_queue_add() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(queue_add().to_bundle()))
+}
+
+fn check_queue_add(bytes: &[u8]) -> Result<(), String> {
+    check_queue_update(queue_add(), bytes)
+}
+
+fn queue_replace() -> QueueUpdate {
+    QueueUpdate::Replace { queue: "loop-a".to_string(), items: queue_items() }
+}
+
+fn build_queue_replace() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(queue_replace().to_bundle()))
+}
+
+fn check_queue_replace(bytes: &[u8]) -> Result<(), String> {
+    check_queue_update(queue_replace(), bytes)
+}
+
+fn queue_remove() -> QueueUpdate {
+    QueueUpdate::Remove { queue: "loop-a".to_string(), ids: vec!["kick-1".to_string(), "snare-2".to_string()] }
+}
+
+fn build_queue_remove() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Bundle(queue_remove().to_bundle()))
+}
+
+fn check_queue_remove(bytes: &[u8]) -> Result<(), String> {
+    check_queue_update(queue_remove(), bytes)
+}
+
+fn capabilities() -> Capabilities {
+    Capabilities::new("sampler")
+        .with_tags(["note_on", "timed_msg"])
+        .with_addresses(["/s_new", "/n_set"])
+}
+
+fn build_hello() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(capabilities().hello_message()))
+}
+
+fn check_hello(bytes: &[u8]) -> Result<(), String> {
+    expect("capabilities", capabilities(), Capabilities::from_message(&decode_message(bytes)?)?)
+}
+
+fn build_gap_report() -> Result<Vec<u8>, String> {
+    encode(OscPacket::Message(sequence::gap_report(42, 45)))
+}
+
+fn check_gap_report(bytes: &[u8]) -> Result<(), String> {
+    expect("missing", Some(3), sequence::reported_missing(&decode_message(bytes)?))
+}
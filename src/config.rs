@@ -0,0 +1,49 @@
+use rosc::OscPacket;
+
+use crate::address::OscAddress;
+use crate::model::TaggedBundle;
+use crate::receiver::DEFAULT_BUFFER_SIZE;
+
+/*
+    Runtime-tunable settings of a running OSCStack, swapped in as a whole via
+        OSCStack::reload or StackController::reload without rebinding the socket.
+
+    controller.reload(StackConfig {
+        ignored_addresses: vec!["/debug/{ping,trace}".to_string()],
+        max_packets_per_second: Some(2000),
+        ..StackConfig::default()
+    });
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackConfig {
+    // Applied before the next datagram is read
+    pub buffer_size: usize,
+    // Messages matching any of these addresses or patterns are dropped before dispatch
+    pub ignored_addresses: Vec<String>,
+    // Tagged bundles with any of these tags are dropped before dispatch
+    pub ignored_tags: Vec<String>,
+    // Packets beyond this amount per second are dropped, None for no limit
+    pub max_packets_per_second: Option<u32>
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        StackConfig {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            ignored_addresses: vec![],
+            ignored_tags: vec![],
+            max_packets_per_second: None
+        }
+    }
+}
+
+impl StackConfig {
+    pub fn ignores(&self, packet: &OscPacket) -> bool {
+        match packet {
+            OscPacket::Message(msg) => self.ignored_addresses.iter()
+                .any(|ignored| OscAddress::new(ignored).matches(&msg.addr)),
+            OscPacket::Bundle(bundle) => !self.ignored_tags.is_empty() && TaggedBundle::new(bundle)
+                .is_ok_and(|tagged| self.ignored_tags.contains(&tagged.bundle_tag))
+        }
+    }
+}
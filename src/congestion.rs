@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::client::Pacing;
+
+/*
+    Simple congestion control for the LAN setups JDW runs in. The send rate and batch size
+        of a client grow steadily while the receiver keeps up and are cut down sharply when
+        it doesn't (additive increase, multiplicative decrease). Feedback comes from:
+        - acks, i.e. ok replies (see reply.rs): the receiver keeps up
+        - round trips of pings (see OSCStack::ping): fine near the best seen, congestion
+            when several times slower
+        - gap reports (see sequence.rs): the receiver lost bundles
+    OSCStack::adapt_rate feeds a rate with what the stack receives, and OscClient::with_adaptive_rate
+        paces sends and splits queue updates accordingly. Cloned handles share the rate.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveConfig {
+    // Bounds and starting point of the rate, in bytes per second
+    pub min_rate: u64,
+    pub max_rate: u64,
+    pub initial_rate: u64,
+    // Bytes per second added for every healthy signal
+    pub increase: u64,
+    // Factor applied to rate and batch size on congestion
+    pub decrease: f64,
+    // Bounds and starting point of the batch size, in items per queue update
+    pub min_batch: usize,
+    pub max_batch: usize,
+    pub initial_batch: usize,
+    // Round trips this many times the best seen count as congestion
+    pub slow_factor: f64,
+    // Shortest time between two decreases, so that one burst of loss only counts once
+    pub hold: Duration
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> AdaptiveConfig {
+        AdaptiveConfig {
+            min_rate: 16_000,
+            max_rate: 12_500_000,
+            initial_rate: 1_000_000,
+            increase: 50_000,
+            decrease: 0.5,
+            min_batch: 1,
+            max_batch: 256,
+            initial_batch: 32,
+            slow_factor: 3.0,
+            hold: Duration::from_millis(100)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RateState {
+    rate: u64,
+    batch: usize,
+    best_round_trip: Option<Duration>,
+    last_decrease: Option<Instant>
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveRate {
+    config: AdaptiveConfig,
+    state: Arc<Mutex<RateState>>
+}
+
+impl Default for AdaptiveRate {
+    fn default() -> AdaptiveRate {
+        AdaptiveRate::new(AdaptiveConfig::default())
+    }
+}
+
+impl AdaptiveRate {
+    pub fn new(config: AdaptiveConfig) -> AdaptiveRate {
+        let state = RateState {
+            rate: config.initial_rate.clamp(config.min_rate, config.max_rate),
+            batch: config.initial_batch.clamp(config.min_batch, config.max_batch),
+            best_round_trip: None,
+            last_decrease: None
+        };
+        AdaptiveRate { config, state: Arc::new(Mutex::new(state)) }
+    }
+
+    fn state(&self) -> MutexGuard<'_, RateState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn config(&self) -> AdaptiveConfig {
+        self.config
+    }
+
+    // Current rate in bytes per second
+    pub fn rate(&self) -> u64 {
+        self.state().rate
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.state().batch
+    }
+
+    pub fn pacing(&self) -> Pacing {
+        Pacing::Bandwidth(self.rate())
+    }
+
+    pub fn on_ack(&self) {
+        self.increase();
+    }
+
+    pub fn on_round_trip(&self, round_trip: Duration) {
+        let best = {
+            let mut state = self.state();
+            let best = state.best_round_trip.map_or(round_trip, |best| best.min(round_trip));
+            state.best_round_trip = Some(best);
+            best
+        };
+
+        if round_trip.as_secs_f64() > best.as_secs_f64() * self.config.slow_factor {
+            self.decrease();
+        } else {
+            self.increase();
+        }
+    }
+
+    pub fn on_loss(&self, missing: u64) {
+        if missing > 0 {
+            self.decrease();
+        }
+    }
+
+    fn increase(&self) {
+        let mut state = self.state();
+        state.rate = state.rate.saturating_add(self.config.increase).min(self.config.max_rate);
+        state.batch = (state.batch + 1).min(self.config.max_batch);
+    }
+
+    fn decrease(&self) {
+        let mut state = self.state();
+        let now = Instant::now();
+        if state.last_decrease.is_some_and(|last| now.duration_since(last) < self.config.hold) {
+            return;
+        }
+        state.last_decrease = Some(now);
+        state.rate = ((state.rate as f64 * self.config.decrease) as u64).max(self.config.min_rate);
+        state.batch = ((state.batch as f64 * self.config.decrease) as usize).max(self.config.min_batch);
+    }
+}
@@ -0,0 +1,401 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeInclusive;
+use core::str::FromStr;
+
+#[cfg(feature = "bigdecimal")]
+use bigdecimal::BigDecimal;
+use rosc::{OscMessage, OscType};
+
+use crate::core::schema::MessageSchema;
+
+/*
+    Adding some convenience functions for OscMessage args
+ */
+
+// Verify that custom args follow the String,float,String,float... pattern
+// Note: This could possibly be a bit expensive time-wise!
+fn validate_args(args: &Vec<OscType>) -> Result<(), String> {
+
+    let mut next_is_string = true;
+
+    for arg in args {
+        match arg {
+            OscType::Float(_) => {
+                if next_is_string {
+                    return Err("Malformed message: Custom arg float where string expected".to_string());
+                }
+
+                next_is_string = true;
+            },
+            OscType::String(_) => {
+                if !next_is_string {
+                    return Err("Malformed message: Custom arg string where float expected".to_string());
+                }
+
+                next_is_string = false;
+            },
+            _ => {
+                return Err("Malformed message: Custom arg in message not of type string or float".to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub trait OscArgHandler {
+    fn expect_addr(&self, addr_name: &str) -> Result<(), String>;
+    fn expect_args(&self, amount: usize) -> Result<String, String>;
+    fn get_string_at(&self, index: usize, name: &str, ) -> Result<String, String>;
+    fn get_float_at(&self, index: usize, name: &str, ) -> Result<f32, String>;
+    fn get_int_at(&self, index: usize, name: &str, ) -> Result<i32, String>;
+    fn get_u64_at(&self, index: usize, name: &str) -> Result<u64, String>;
+    #[cfg(feature = "bigdecimal")]
+    fn get_bigdecimal_at(&self, index: usize, name: &str) -> Result<BigDecimal, String>;
+    fn get_varargs(&self, start_index: usize) -> Result<Vec<OscType>, String>;
+    fn get_float_at_in_range(&self, index: usize, name: &str, range: RangeInclusive<f32>) -> Result<f32, String>;
+    fn get_int_at_in_range(&self, index: usize, name: &str, range: RangeInclusive<i32>) -> Result<i32, String>;
+    fn get_positive_int_at(&self, index: usize, name: &str) -> Result<i32, String>;
+    fn get_enum_at<T: FromStr>(&self, index: usize, name: &str) -> Result<T, String> where T::Err: fmt::Display;
+    fn validate(&self, schema: &MessageSchema) -> Result<(), String>;
+}
+
+/*
+    Error for FromStr implementations of enums parsed with get_enum_at, listing the options:
+
+    impl FromStr for Waveform {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "sine" => Ok(Waveform::Sine),
+                "saw" => Ok(Waveform::Saw),
+                _ => Err(unknown_variant(s, &["sine", "saw"]))
+            }
+        }
+    }
+ */
+pub fn unknown_variant(value: &str, accepted: &[&str]) -> String {
+    format!("unknown value \"{}\", expected one of: {}", value, accepted.join(", "))
+}
+
+// Longest arg preview in getter errors, in chars
+const PREVIEW_CHARS: usize = 40;
+
+// Error for a missing arg or one of another type than expected, showing what was there instead
+fn arg_error(args: &[OscType], index: usize, name: &str, expected: &str) -> String {
+    match args.get(index) {
+        None => format!("{} {} not found as {}th arg, message has {} args", name, expected, index, args.len()),
+        Some(actual) => format!("{} {} not found as {}th arg, got {}", name, expected, index, preview(actual))
+    }
+}
+
+// Debug form of the arg (e.g. String("kick")), cut short for long strings and blobs
+pub(crate) fn preview(arg: &OscType) -> String {
+    let full = format!("{:?}", arg);
+    match full.char_indices().nth(PREVIEW_CHARS) {
+        Some((cut, _)) => format!("{}...", &full[..cut]),
+        None => full
+    }
+}
+
+impl OscArgHandler for OscMessage {
+
+    fn expect_addr(&self, addr_name: &str) -> Result<(), String> {
+        if self.addr != addr_name {
+            return Err(format!("Attempted to format {} as the wrong kind of message - this likely a human error in the source code", addr_name));
+        }
+
+        Ok(())
+    }
+
+    fn expect_args(&self, amount: usize) -> Result<String, String> {
+
+        if self.args.len() < amount {
+            return Err(format!("Message did not contain the {} first required args.", amount));
+        }
+
+        Ok("Ok".to_string())
+    }
+
+    fn get_string_at(&self, index: usize, name: &str, ) -> Result<String, String> {
+        self.args
+            .get(index)
+            .and_then(|some| some.clone().string())
+            .ok_or_else(|| arg_error(&self.args, index, name, "string"))
+    }
+
+    fn get_float_at(&self, index: usize, name: &str, ) -> Result<f32, String> {
+        self.args
+            .get(index)
+            .and_then(|some| some.clone().float())
+            .ok_or_else(|| arg_error(&self.args, index, name, "float"))
+    }
+
+    fn get_int_at(&self, index: usize, name: &str, ) -> Result<i32, String> {
+        self.args
+            .get(index)
+            .and_then(|some| some.clone().int())
+            .ok_or_else(|| arg_error(&self.args, index, name, "int"))
+    }
+
+    fn get_u64_at(&self, index: usize, name: &str) -> Result<u64, String> {
+        let value = self.get_int_at(index, name)?;
+        u64::try_from(value).map_err(|_| format!("{} int at {}th arg should not be negative, got {}", name, index, value))
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    fn get_bigdecimal_at(&self, index: usize, name: &str) -> Result<BigDecimal, String> {
+        let value = self.get_string_at(index, name)?;
+        BigDecimal::from_str(&value).map_err(|e| format!("{} string {:?} at {}th arg is not a decimal: {}", name, value, index, e))
+    }
+
+    fn get_varargs(&self, start_index: usize) -> Result<Vec<OscType>, String> {
+        let named_args = if self.args.len() > start_index {self.args[start_index..].to_vec()} else {vec![]};
+        validate_args(&named_args)?;
+        Ok(named_args)
+    }
+
+    // Also rejects NaN, which no range contains
+    fn get_float_at_in_range(&self, index: usize, name: &str, range: RangeInclusive<f32>) -> Result<f32, String> {
+        let value = self.get_float_at(index, name)?;
+        if !range.contains(&value) {
+            return Err(format!("{} float {} at {}th arg is outside of the allowed range {:?}", name, value, index, range));
+        }
+        Ok(value)
+    }
+
+    fn get_int_at_in_range(&self, index: usize, name: &str, range: RangeInclusive<i32>) -> Result<i32, String> {
+        let value = self.get_int_at(index, name)?;
+        if !range.contains(&value) {
+            return Err(format!("{} int {} at {}th arg is outside of the allowed range {:?}", name, value, index, range));
+        }
+        Ok(value)
+    }
+
+    fn get_positive_int_at(&self, index: usize, name: &str) -> Result<i32, String> {
+        let value = self.get_int_at(index, name)?;
+        if value <= 0 {
+            return Err(format!("{} int at {}th arg should be positive, got {}", name, index, value));
+        }
+        Ok(value)
+    }
+
+    // Parse a string arg into e.g. a waveform or mode enum, see unknown_variant
+    fn get_enum_at<T: FromStr>(&self, index: usize, name: &str) -> Result<T, String> where T::Err: fmt::Display {
+        let value = self.get_string_at(index, name)?;
+        T::from_str(&value).map_err(|e| format!("{} at {}th arg: {}", name, index, e))
+    }
+
+    // Check address and args against an expected layout, listing every problem, see MessageSchema
+    fn validate(&self, schema: &MessageSchema) -> Result<(), String> {
+        schema.validate(self)
+    }
+}
+
+
+/*
+    Plain Rust values accepted as OSC args by args! and OscArgWriter::with_arg.
+    Mirrors rosc's From impls, except that f64 becomes a Float too: untyped float literals
+        are f64, and JDW and SuperCollider expect floats. Pass OscType::Double for doubles.
+ */
+pub trait IntoOscArg {
+    fn into_osc_arg(self) -> OscType;
+}
+
+impl IntoOscArg for OscType {
+    fn into_osc_arg(self) -> OscType {
+        self
+    }
+}
+
+impl IntoOscArg for &str {
+    fn into_osc_arg(self) -> OscType {
+        OscType::String(self.to_string())
+    }
+}
+
+impl IntoOscArg for String {
+    fn into_osc_arg(self) -> OscType {
+        OscType::String(self)
+    }
+}
+
+impl IntoOscArg for f32 {
+    fn into_osc_arg(self) -> OscType {
+        OscType::Float(self)
+    }
+}
+
+impl IntoOscArg for f64 {
+    fn into_osc_arg(self) -> OscType {
+        OscType::Float(self as f32)
+    }
+}
+
+impl IntoOscArg for i32 {
+    fn into_osc_arg(self) -> OscType {
+        OscType::Int(self)
+    }
+}
+
+impl IntoOscArg for bool {
+    fn into_osc_arg(self) -> OscType {
+        OscType::Bool(self)
+    }
+}
+
+/*
+    Vec<OscType> from plain values, see IntoOscArg:
+    args!["kick", 440.0, "amp", 0.5] == vec![OscType::String("kick".to_string()), OscType::Float(440.0), ...]
+ */
+#[macro_export]
+macro_rules! args {
+    ($($arg:expr),* $(,)?) => {
+        $crate::core::args::arg_vec([$($crate::core::args::IntoOscArg::into_osc_arg($arg)),*])
+    };
+}
+
+// Used by args!, which can't name Vec in no_std crates
+#[doc(hidden)]
+pub fn arg_vec<const N: usize>(args: [OscType; N]) -> Vec<OscType> {
+    args.into()
+}
+
+/*
+    Setter counterparts to OscArgHandler, for rewriting messages in place (e.g. in middleware).
+    Setters refuse to change the type of an existing arg, since receivers parse by position.
+ */
+pub trait OscArgWriter {
+    fn set_float_at(&mut self, index: usize, name: &str, value: f32) -> Result<(), String>;
+    fn set_string_at(&mut self, index: usize, name: &str, value: &str) -> Result<(), String>;
+    fn upsert_named_arg(&mut self, name: &str, value: f32) -> Result<(), String>;
+    fn remove_named_arg(&mut self, name: &str) -> Result<Option<f32>, String>;
+    fn with_args_replaced(&self, replacements: &[(usize, OscType)]) -> Result<OscMessage, String>;
+    fn with_arg(self, value: impl IntoOscArg) -> Self where Self: Sized;
+}
+
+// Index of the value of the named arg, i.e. the arg right after the name
+pub(crate) fn named_arg_index(args: &[OscType], name: &str) -> Option<usize> {
+    args.iter()
+        .position(|arg| matches!(arg, OscType::String(arg_name) if arg_name == name))
+        .map(|name_index| name_index + 1)
+}
+
+impl OscArgWriter for OscMessage {
+
+    fn set_float_at(&mut self, index: usize, name: &str, value: f32) -> Result<(), String> {
+        match self.args.get_mut(index) {
+            Some(OscType::Float(current)) => {
+                *current = value;
+                Ok(())
+            },
+            Some(other) => Err(format!("Cannot set {} float as {}th arg, which is {:?}", name, index, other)),
+            None => Err(format!("Cannot set {} float as {}th arg, message has {} args", name, index, self.args.len()))
+        }
+    }
+
+    fn set_string_at(&mut self, index: usize, name: &str, value: &str) -> Result<(), String> {
+        match self.args.get_mut(index) {
+            Some(OscType::String(current)) => {
+                *current = value.to_string();
+                Ok(())
+            },
+            Some(other) => Err(format!("Cannot set {} string as {}th arg, which is {:?}", name, index, other)),
+            None => Err(format!("Cannot set {} string as {}th arg, message has {} args", name, index, self.args.len()))
+        }
+    }
+
+    // Sets the value following the first arg equal to name, or appends a new name/value pair
+    fn upsert_named_arg(&mut self, name: &str, value: f32) -> Result<(), String> {
+        match named_arg_index(&self.args, name) {
+            Some(index) => self.set_float_at(index, name, value),
+            None => {
+                self.args.push(OscType::String(name.to_string()));
+                self.args.push(OscType::Float(value));
+                Ok(())
+            }
+        }
+    }
+
+    // Removes the name/value pair, returning the value if it was present
+    fn remove_named_arg(&mut self, name: &str) -> Result<Option<f32>, String> {
+        let index = match named_arg_index(&self.args, name) {
+            Some(index) => index,
+            None => return Ok(None)
+        };
+
+        match self.args.get(index) {
+            Some(OscType::Float(value)) => {
+                let value = *value;
+                self.args.drain(index - 1..=index);
+                Ok(Some(value))
+            },
+            other => Err(format!("Named arg {} is not followed by a float but {:?}", name, other))
+        }
+    }
+
+    // Copy of the message with the args at the given indices replaced; any arg type is allowed
+    fn with_args_replaced(&self, replacements: &[(usize, OscType)]) -> Result<OscMessage, String> {
+        let mut msg = self.clone();
+        for (index, value) in replacements {
+            let len = msg.args.len();
+            let arg = msg.args.get_mut(*index)
+                .ok_or(format!("Cannot replace {}th arg, message has {} args", index, len))?;
+            *arg = value.clone();
+        }
+        Ok(msg)
+    }
+
+    // Appends the arg, for building messages in one expression
+    fn with_arg(mut self, value: impl IntoOscArg) -> OscMessage {
+        self.args.push(value.into_osc_arg());
+        self
+    }
+}
+
+// Trailing ("name", float) arg pairs following the fixed args of e.g. /note_on, in order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NamedVarArgs {
+    pairs: Vec<(String, f32)>
+}
+
+impl NamedVarArgs {
+    pub fn new() -> NamedVarArgs {
+        NamedVarArgs::default()
+    }
+
+    // Replaces the value if the name is already present
+    pub fn with(mut self, name: &str, value: f32) -> NamedVarArgs {
+        match self.pairs.iter_mut().find(|(existing, _)| existing == name) {
+            Some(pair) => pair.1 = value,
+            None => self.pairs.push((name.to_string(), value))
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.pairs.iter().find(|(existing, _)| existing == name).map(|(_, value)| *value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.pairs.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+
+    pub fn to_osc_args(&self) -> Vec<OscType> {
+        self.pairs.iter()
+            .flat_map(|(name, value)| [OscType::String(name.clone()), OscType::Float(*value)])
+            .collect()
+    }
+
+    pub fn from_osc_args(args: &[OscType]) -> Result<NamedVarArgs, String> {
+        validate_args(&args.to_vec())?;
+        args.chunks(2).try_fold(NamedVarArgs::new(), |named, pair| match pair {
+            [OscType::String(name), OscType::Float(value)] => Ok(named.with(name, *value)),
+            _ => Err("Malformed message: Custom arg name without value".to_string())
+        })
+    }
+}
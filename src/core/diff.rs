@@ -0,0 +1,158 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+/*
+    Field by field comparison of OSC values, reporting every difference with its position
+        instead of two Debug walls to compare by eye. Mostly used through assert_osc_eq!:
+
+    assert_osc_eq!(expected_bundle, actual_bundle);
+
+    OSC packets differ (expected vs actual):
+      content 1 > arg 1: expected Float(440.0), got Float(441.0)
+      content 2 > address: expected "/note_off", got "/note_on"
+ */
+pub trait OscDiff {
+    // Description of each difference, empty when equal
+    fn differences(&self, actual: &Self) -> Vec<String>;
+
+    // Readable report of all differences, None when equal
+    fn osc_diff(&self, actual: &Self) -> Option<String> {
+        let differences = self.differences(actual);
+        if differences.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = differences.iter().map(|difference| format!("  {}", difference)).collect();
+        Some(format!("OSC packets differ (expected vs actual):\n{}", lines.join("\n")))
+    }
+}
+
+/*
+    Panics with an OscDiff report unless both sides are equal. Works for OscPacket,
+        OscMessage, OscBundle and OscType values (both sides of the same type).
+ */
+#[macro_export]
+macro_rules! assert_osc_eq {
+    ($expected:expr, $actual:expr $(,)?) => {
+        if let Some(report) = $crate::core::diff::OscDiff::osc_diff(&$expected, &$actual) {
+            panic!("assert_osc_eq!({}, {}) failed\n{}", stringify!($expected), stringify!($actual), report);
+        }
+    };
+}
+
+fn prefixed(prefix: &str, differences: Vec<String>) -> Vec<String> {
+    differences.into_iter().map(|difference| format!("{} > {}", prefix, difference)).collect()
+}
+
+// Type name as in the OSC type tags, for pointing out type mismatches
+fn type_name(arg: &OscType) -> &'static str {
+    match arg {
+        OscType::Int(_) => "int",
+        OscType::Float(_) => "float",
+        OscType::String(_) => "string",
+        OscType::Blob(_) => "blob",
+        OscType::Time(_) => "time",
+        OscType::Long(_) => "long",
+        OscType::Double(_) => "double",
+        OscType::Char(_) => "char",
+        OscType::Color(_) => "color",
+        OscType::Midi(_) => "midi",
+        OscType::Bool(_) => "bool",
+        OscType::Array(_) => "array",
+        OscType::Nil => "nil",
+        OscType::Inf => "inf"
+    }
+}
+
+/*
+    Elementwise diff of two lists, noting missing and unexpected trailing items.
+    item_differences gets the position (e.g. "arg 2") to describe differences with.
+ */
+fn list_differences<T: core::fmt::Debug>(
+    label: &str,
+    expected: &[T],
+    actual: &[T],
+    item_differences: impl Fn(&str, &T, &T) -> Vec<String>
+) -> Vec<String> {
+    let mut differences = Vec::new();
+    for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+        differences.extend(item_differences(&format!("{} {}", label, index), expected, actual));
+    }
+    for (index, missing) in expected.iter().enumerate().skip(actual.len()) {
+        differences.push(format!("{} {}: expected {:?}, missing", label, index, missing));
+    }
+    for (index, unexpected) in actual.iter().enumerate().skip(expected.len()) {
+        differences.push(format!("{} {}: unexpected {:?}", label, index, unexpected));
+    }
+    differences
+}
+
+// Arrays are compared item by item, other values as a whole
+fn arg_differences(position: &str, expected: &OscType, actual: &OscType) -> Vec<String> {
+    match (expected, actual) {
+        (OscType::Array(expected), OscType::Array(actual)) =>
+            prefixed(position, list_differences("item", &expected.content, &actual.content, arg_differences)),
+        _ => expected.differences(actual).into_iter().map(|difference| format!("{}: {}", position, difference)).collect()
+    }
+}
+
+// Floats are compared bitwise as they travel on the wire, so that NaN matches NaN
+fn same_arg(expected: &OscType, actual: &OscType) -> bool {
+    match (expected, actual) {
+        (OscType::Float(expected), OscType::Float(actual)) => expected.to_bits() == actual.to_bits(),
+        (OscType::Double(expected), OscType::Double(actual)) => expected.to_bits() == actual.to_bits(),
+        _ => expected == actual
+    }
+}
+
+impl OscDiff for OscType {
+    fn differences(&self, actual: &Self) -> Vec<String> {
+        match (self, actual) {
+            (OscType::Array(expected), OscType::Array(actual)) =>
+                list_differences("item", &expected.content, &actual.content, arg_differences),
+            _ if same_arg(self, actual) => Vec::new(),
+            _ if type_name(self) != type_name(actual) =>
+                vec![format!("expected {:?}, got {:?} ({} instead of {})", self, actual, type_name(actual), type_name(self))],
+            _ => vec![format!("expected {:?}, got {:?}", self, actual)]
+        }
+    }
+}
+
+impl OscDiff for OscMessage {
+    fn differences(&self, actual: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.addr != actual.addr {
+            differences.push(format!("address: expected {:?}, got {:?}", self.addr, actual.addr));
+        }
+        differences.extend(list_differences("arg", &self.args, &actual.args, arg_differences));
+        differences
+    }
+}
+
+impl OscDiff for OscBundle {
+    fn differences(&self, actual: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.timetag != actual.timetag {
+            differences.push(format!("timetag: expected {:?}, got {:?}", self.timetag, actual.timetag));
+        }
+        differences.extend(list_differences("content", &self.content, &actual.content,
+            |position, expected, actual| prefixed(position, expected.differences(actual))));
+        differences
+    }
+}
+
+impl OscDiff for OscPacket {
+    fn differences(&self, actual: &Self) -> Vec<String> {
+        match (self, actual) {
+            (OscPacket::Message(expected), OscPacket::Message(actual)) => expected.differences(actual),
+            (OscPacket::Bundle(expected), OscPacket::Bundle(actual)) => expected.differences(actual),
+            (OscPacket::Message(expected), OscPacket::Bundle(_)) =>
+                vec![format!("expected message {}, got bundle", expected.addr)],
+            (OscPacket::Bundle(_), OscPacket::Message(actual)) =>
+                vec![format!("expected bundle, got message {}", actual.addr)]
+        }
+    }
+}
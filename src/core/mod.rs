@@ -0,0 +1,17 @@
+/*
+    The parsing layer of the crate: arg handling, the tagged bundle model, schema
+        validation and diffing for tests. Needs only alloc, so that it builds with default
+        features off for embedded controllers (Teensy/ESP32 gadgets) talking to a JDW rig,
+        adding the std feature on targets that have it (see lib.rs):
+
+    jdw-osc-lib = { version = "...", default-features = false }
+    jdw-osc-lib = { version = "...", default-features = false, features = ["std"] }
+
+    With the model feature args and tagged items are also available via model, and schemas
+        via the deprecated top-level schema module.
+ */
+
+pub mod args;
+pub mod diff;
+pub mod schema;
+pub mod tagged;
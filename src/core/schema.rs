@@ -0,0 +1,393 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::core::args::preview;
+use crate::core::tagged::TaggedBundle;
+
+/*
+    Expected content layout of a tagged bundle, to catch producer/consumer drift early.
+    Entries are matched in order against the bundle contents (after /bundle_info):
+
+    let schema = BundleSchema::new("nrt_record_request")
+        .message("/nrt_record_info")
+        .repeated(PacketSpec::Bundle(Some("timed_msg".to_string())), 1, None);
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketSpec {
+    // A message, optionally with a specific address
+    Message(Option<String>),
+    // A bundle, optionally a tagged bundle with a specific tag
+    Bundle(Option<String>),
+    Any
+}
+
+impl PacketSpec {
+    fn matches(&self, packet: &OscPacket) -> bool {
+        match (self, packet) {
+            (PacketSpec::Any, _) => true,
+            (PacketSpec::Message(addr), OscPacket::Message(msg)) => addr.as_ref().is_none_or(|addr| addr == &msg.addr),
+            (PacketSpec::Bundle(tag), OscPacket::Bundle(bundle)) => tag.as_ref().is_none_or(|tag| {
+                TaggedBundle::new(bundle).map(|tagged| &tagged.bundle_tag == tag).unwrap_or(false)
+            }),
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for PacketSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketSpec::Message(Some(addr)) => write!(f, "message {}", addr),
+            PacketSpec::Message(None) => write!(f, "message"),
+            PacketSpec::Bundle(Some(tag)) => write!(f, "{} bundle", tag),
+            PacketSpec::Bundle(None) => write!(f, "bundle"),
+            PacketSpec::Any => write!(f, "packet")
+        }
+    }
+}
+
+fn describe(packet: &OscPacket) -> String {
+    match packet {
+        OscPacket::Message(msg) => format!("message {}", msg.addr),
+        OscPacket::Bundle(bundle) => match TaggedBundle::new(bundle) {
+            Ok(tagged) => format!("{} bundle", tagged.bundle_tag),
+            Err(_) => "untagged bundle".to_string()
+        }
+    }
+}
+
+// One position in the layout: spec repeated between min and max (None = unbounded) times
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaEntry {
+    pub spec: PacketSpec,
+    pub min: usize,
+    pub max: Option<usize>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleSchema {
+    pub tag: String,
+    pub entries: Vec<SchemaEntry>
+}
+
+impl BundleSchema {
+    pub fn new(tag: &str) -> BundleSchema {
+        BundleSchema { tag: tag.to_string(), entries: vec![] }
+    }
+
+    // Exactly one message with the given address
+    pub fn message(self, addr: &str) -> BundleSchema {
+        self.repeated(PacketSpec::Message(Some(addr.to_string())), 1, Some(1))
+    }
+
+    // Exactly one tagged bundle with the given tag
+    pub fn bundle(self, tag: &str) -> BundleSchema {
+        self.repeated(PacketSpec::Bundle(Some(tag.to_string())), 1, Some(1))
+    }
+
+    pub fn repeated(mut self, spec: PacketSpec, min: usize, max: Option<usize>) -> BundleSchema {
+        self.entries.push(SchemaEntry { spec, min, max });
+        self
+    }
+
+    /*
+        Entries consume matching packets greedily, in order.
+        Errors name the exact content index where the layout broke.
+     */
+    pub fn validate(&self, bundle: &TaggedBundle) -> Result<(), String> {
+        if bundle.bundle_tag != self.tag {
+            return Err(format!("Expected {} bundle, got {}", self.tag, bundle.bundle_tag));
+        }
+
+        let mut index = 0;
+        for entry in &self.entries {
+            let mut count = 0;
+            while entry.max.is_none_or(|max| count < max)
+                && bundle.contents.get(index).is_some_and(|packet| entry.spec.matches(packet)) {
+                count += 1;
+                index += 1;
+            }
+
+            if count < entry.min {
+                let found = bundle.contents.get(index).map(describe).unwrap_or("end of bundle".to_string());
+                return Err(format!(
+                    "{} contents[{}]: expected {} (at least {}, found {}), got {}",
+                    self.tag, index, entry.spec, entry.min, count, found
+                ));
+            }
+        }
+
+        match bundle.contents.get(index) {
+            Some(packet) => Err(format!("{} contents[{}]: unexpected {} after end of layout", self.tag, index, describe(packet))),
+            None => Ok(())
+        }
+    }
+}
+
+/*
+    Size limits for a tagged bundle, checked before any handler sees it so that malformed
+        or malicious gigantic bundles can't multiply their memory use through funnels,
+        clones and queues. Depth counts bundle levels: the tagged bundle itself is depth 1,
+        a bundle among its contents depth 2 and so on.
+
+    let limits = BundleLimits::new().max_contents(512).max_depth(2);
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BundleLimits {
+    pub max_contents: Option<usize>,
+    pub max_depth: Option<usize>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitViolation {
+    // (contents, max) directly in the tagged bundle
+    TooManyContents(usize, usize),
+    // Nesting exceeded max; (max) since deeper levels are not counted any further
+    TooDeep(usize)
+}
+
+impl fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitViolation::TooManyContents(count, max) => write!(f, "{} contents exceed the limit of {}", count, max),
+            LimitViolation::TooDeep(max) => write!(f, "bundles nested deeper than the limit of {}", max)
+        }
+    }
+}
+
+impl BundleLimits {
+    pub fn new() -> BundleLimits {
+        BundleLimits::default()
+    }
+
+    pub fn max_contents(mut self, max: usize) -> BundleLimits {
+        self.max_contents = Some(max);
+        self
+    }
+
+    pub fn max_depth(mut self, max: usize) -> BundleLimits {
+        self.max_depth = Some(max);
+        self
+    }
+
+    pub fn check(&self, bundle: &TaggedBundle) -> Result<(), LimitViolation> {
+        if let Some(max) = self.max_contents.filter(|max| bundle.contents.len() > *max) {
+            return Err(LimitViolation::TooManyContents(bundle.contents.len(), max));
+        }
+        if let Some(max) = self.max_depth {
+            if max == 0 || exceeds_depth(&bundle.contents, max - 1) {
+                return Err(LimitViolation::TooDeep(max));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Stops descending as soon as the remaining allowance is used up
+fn exceeds_depth(contents: &[OscPacket], remaining: usize) -> bool {
+    contents.iter().any(|packet| match packet {
+        OscPacket::Bundle(_) if remaining == 0 => true,
+        OscPacket::Bundle(bundle) => exceeds_depth(&bundle.content, remaining - 1),
+        OscPacket::Message(_) => false
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    Int,
+    Float,
+    String
+}
+
+impl ArgType {
+    fn matches(&self, arg: &OscType) -> bool {
+        matches!(
+            (self, arg),
+            (ArgType::Int, OscType::Int(_)) | (ArgType::Float, OscType::Float(_)) | (ArgType::String, OscType::String(_))
+        )
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgType::Int => write!(f, "int"),
+            ArgType::Float => write!(f, "float"),
+            ArgType::String => write!(f, "string")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgSpec {
+    pub name: String,
+    pub arg_type: ArgType
+}
+
+/*
+    Expected args of a message: fixed, named args in order, optionally followed by
+        ("name", float) pairs (see NamedVarArgs). Shared between producers and consumers,
+        e.g. via OSCStack::on_message_with_schema and stubs::generate_client_stubs.
+
+    let note_on = MessageSchema::new("/note_on")
+        .arg("synth", ArgType::String)
+        .arg("freq", ArgType::Float)
+        .named_varargs();
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSchema {
+    pub addr: String,
+    pub args: Vec<ArgSpec>,
+    pub named_varargs: bool
+}
+
+impl MessageSchema {
+    pub fn new(addr: &str) -> MessageSchema {
+        MessageSchema { addr: addr.to_string(), args: vec![], named_varargs: false }
+    }
+
+    pub fn arg(mut self, name: &str, arg_type: ArgType) -> MessageSchema {
+        self.args.push(ArgSpec { name: name.to_string(), arg_type });
+        self
+    }
+
+    pub fn named_varargs(mut self) -> MessageSchema {
+        self.named_varargs = true;
+        self
+    }
+
+    /*
+        Checks every arg rather than stopping at the first problem, so that a single error
+            lists all of them:
+
+        /note_on has 2 problems: args[0] (synth): expected string, got Int(3); args[1] (freq): expected float, got end of args
+     */
+    pub fn validate(&self, msg: &OscMessage) -> Result<(), String> {
+        let mut problems = self.arg_problems(msg);
+        if msg.addr != self.addr {
+            problems.insert(0, format!("expected address {}, got {}", self.addr, msg.addr));
+        }
+        self.report(problems)
+    }
+
+    // As validate, but without checking the address, e.g. for pattern matched messages
+    pub fn validate_args(&self, msg: &OscMessage) -> Result<(), String> {
+        self.report(self.arg_problems(msg))
+    }
+
+    fn report(&self, problems: Vec<String>) -> Result<(), String> {
+        match problems.len() {
+            0 => Ok(()),
+            1 => Err(format!("{} {}", self.addr, problems[0])),
+            count => Err(format!("{} has {} problems: {}", self.addr, count, problems.join("; ")))
+        }
+    }
+
+    fn arg_problems(&self, msg: &OscMessage) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (index, spec) in self.args.iter().enumerate() {
+            match msg.args.get(index) {
+                Some(arg) if spec.arg_type.matches(arg) => {},
+                Some(arg) => problems.push(format!("args[{}] ({}): expected {}, got {}", index, spec.name, spec.arg_type, preview(arg))),
+                None => problems.push(format!("args[{}] ({}): expected {}, got end of args", index, spec.name, spec.arg_type))
+            }
+        }
+
+        let fixed = self.args.len();
+        let rest = msg.args.get(fixed..).unwrap_or_default();
+        if !self.named_varargs {
+            if !rest.is_empty() {
+                problems.push(format!("args[{}]: unexpected arg after end of layout", fixed));
+            }
+            return problems;
+        }
+
+        // ("name", float) pairs, see NamedVarArgs
+        for (pair_index, pair) in rest.chunks(2).enumerate() {
+            let index = fixed + pair_index * 2;
+            let name = match &pair[0] {
+                OscType::String(name) => name.as_str(),
+                other => {
+                    problems.push(format!("args[{}]: expected named arg name (string), got {}", index, preview(other)));
+                    "?"
+                }
+            };
+            match pair.get(1) {
+                Some(OscType::Float(_)) => {},
+                Some(other) => problems.push(format!("args[{}] ({}): expected float, got {}", index + 1, name, preview(other))),
+                None => problems.push(format!("args[{}] ({}): expected float, got end of args", index + 1, name))
+            }
+        }
+        problems
+    }
+}
+
+/*
+    Machine readable description of schemas, for other JDW language bindings to check
+        themselves against. Plain JSON, sorted by address and tag:
+
+    {"messages": [{"addr": "/note_on", "args": [{"name": "synth", "type": "string"}], "named_varargs": true}],
+     "bundles": [{"tag": "nrt_record_request", "entries": [{"kind": "message", "match": "/nrt_record_info", "min": 1, "max": 1}]}]}
+
+    Entry kinds are "message", "bundle" (match holds the tag) and "any"; a null match or max
+        means any address/tag or no upper limit.
+ */
+pub fn describe_schemas<'s>(
+    messages: impl IntoIterator<Item = &'s MessageSchema>,
+    bundles: impl IntoIterator<Item = &'s BundleSchema>
+) -> String {
+    let mut messages: Vec<&MessageSchema> = messages.into_iter().collect();
+    messages.sort_by(|a, b| a.addr.cmp(&b.addr));
+    let mut bundles: Vec<&BundleSchema> = bundles.into_iter().collect();
+    bundles.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+    let messages: Vec<String> = messages.iter().map(|schema| {
+        let args: Vec<String> = schema.args.iter()
+            .map(|spec| format!("{{\"name\": {}, \"type\": \"{}\"}}", json_string(&spec.name), spec.arg_type))
+            .collect();
+        format!("{{\"addr\": {}, \"args\": [{}], \"named_varargs\": {}}}", json_string(&schema.addr), args.join(", "), schema.named_varargs)
+    }).collect();
+
+    let bundles: Vec<String> = bundles.iter().map(|schema| {
+        let entries: Vec<String> = schema.entries.iter().map(|entry| {
+            let (kind, matching) = match &entry.spec {
+                PacketSpec::Message(addr) => ("message", addr.as_deref()),
+                PacketSpec::Bundle(tag) => ("bundle", tag.as_deref()),
+                PacketSpec::Any => ("any", None)
+            };
+            format!(
+                "{{\"kind\": \"{}\", \"match\": {}, \"min\": {}, \"max\": {}}}",
+                kind,
+                matching.map(json_string).unwrap_or("null".to_string()),
+                entry.min,
+                entry.max.map(|max| max.to_string()).unwrap_or("null".to_string())
+            )
+        }).collect();
+        format!("{{\"tag\": {}, \"entries\": [{}]}}", json_string(&schema.tag), entries.join(", "))
+    }).collect();
+
+    format!("{{\"messages\": [{}], \"bundles\": [{}]}}", messages.join(", "), bundles.join(", "))
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
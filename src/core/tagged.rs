@@ -0,0 +1,328 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::core::schema::BundleSchema;
+
+/*
+    In order to properly utilize bundles I have created a standard where the first
+        packet in every JDW-compatible bundle is an OSC message with a bundle type
+        string contained within, e.g.: ["/bundle_tag", "nrt_record_request"]
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedBundle {
+    pub bundle_tag: String,
+    pub contents: Vec<OscPacket>,
+    // The rest of the /bundle_info header; to_bundle() writes bundle_tag over info.tag
+    pub info: BundleInfo
+}
+
+impl TaggedBundle {
+    // Bundle with nothing in /bundle_info beyond the tag
+    pub fn with_contents(bundle_tag: &str, contents: Vec<OscPacket>) -> TaggedBundle {
+        TaggedBundle { bundle_tag: bundle_tag.to_string(), contents, info: BundleInfo::new(bundle_tag) }
+    }
+
+    // Info args are parsed leniently, see new_with_info for strict parsing
+    pub fn new(bundle: &OscBundle) -> Result<TaggedBundle, String> {
+        TaggedBundle::new_with_info(bundle, InfoParsing::Lenient)
+    }
+
+    pub fn new_with_info(bundle: &OscBundle, parsing: InfoParsing) -> Result<TaggedBundle, String> {
+        let first_msg = match bundle.content.first().ok_or("Empty bundle")?.clone() {
+            OscPacket::Message(msg) => { Option::Some(msg) }
+            OscPacket::Bundle(_) => {Option::None}
+        }.ok_or("First element in bundle not an info message!")?;
+
+        if first_msg.addr != "/bundle_info" {
+            return Err(format!("Expected /bundle_info as first message in bundle, got: {}", &first_msg.addr));
+        }
+
+        let bundle_tag = first_msg.args.first()
+            .ok_or("bundle info empty")?
+            .clone()
+            .string().ok_or("bundle info should be a string")?;
+
+        let info = BundleInfo::from_message(&first_msg, parsing)?;
+        let contents = if bundle.content.len() > 1 {bundle.content[1..].to_vec()} else {vec![]};
+
+        Ok(TaggedBundle {
+            bundle_tag,
+            contents,
+            info
+        })
+    }
+
+    /*
+        Lenient variant of new() for interop with older or drifting JDW services.
+        If strict parsing fails, the tag is recovered from:
+            1. A /bundle_* header message with the wrong address but a string first arg
+                (e.g. the older ["/bundle_tag", "nrt_record_request"] convention)
+            2. The provided fallback tag, in which case all bundle contents are kept
+        Any guess made is returned alongside the bundle so the caller can report it.
+     */
+    pub fn new_lenient(bundle: &OscBundle, fallback_tag: Option<&str>) -> Result<(TaggedBundle, Option<TagRecovery>), String> {
+        let strict_error = match TaggedBundle::new(bundle) {
+            Ok(tagged) => return Ok((tagged, None)),
+            Err(e) => e
+        };
+
+        if let Some(OscPacket::Message(header)) = bundle.content.first() {
+            let header_tag = header.args.first()
+                .and_then(|arg| arg.clone().string())
+                .filter(|_| header.addr.starts_with("/bundle_"));
+
+            if let Some(bundle_tag) = header_tag {
+                let recovery = TagRecovery {
+                    guessed_tag: bundle_tag.clone(),
+                    reason: format!("header address {} used in place of /bundle_info", &header.addr)
+                };
+
+                return Ok((TaggedBundle::with_contents(&bundle_tag, bundle.content[1..].to_vec()), Some(recovery)));
+            }
+        }
+
+        match fallback_tag {
+            Some(tag) => {
+                let recovery = TagRecovery {
+                    guessed_tag: tag.to_string(),
+                    reason: format!("fallback tag applied ({})", strict_error)
+                };
+
+                Ok((TaggedBundle::with_contents(tag, bundle.content.clone()), Some(recovery)))
+            },
+            None => Err(strict_error)
+        }
+    }
+
+    // Standard bundle with the /bundle_info header followed by the contents, as parsed by new()
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = BundleInfo { tag: self.bundle_tag.clone(), ..self.info.clone() }.to_message();
+        OscBundle {
+            timetag: OscTime { seconds: 0, fractional: 1 },
+            content: core::iter::once(OscPacket::Message(info)).chain(self.contents.iter().cloned()).collect()
+        }
+    }
+
+    pub fn get_packet(&self, content_index: usize) -> Result<OscPacket, String> {
+        self.contents.get(content_index)
+            .cloned()
+            .ok_or("Failed to fetch packet".to_string())
+    }
+
+    pub fn get_message(&self, content_index: usize) -> Result<OscMessage, String> {
+        self.contents.get(content_index)
+            .cloned()
+            .ok_or(format!("Could not get packet on index {} for bundle {:?}", content_index, &self))
+            .and_then(|pct| match pct {
+                OscPacket::Message(msg) => {
+                    Ok(msg)
+                }
+                _ => {Err("Not a message".to_string())}
+            })
+    }
+
+    // Check contents against an expected layout, see BundleSchema
+    pub fn validate(&self, schema: &BundleSchema) -> Result<(), String> {
+        schema.validate(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    // All messages in contents, in order, skipping any nested bundles
+    pub fn iter_messages(&self) -> impl Iterator<Item = &OscMessage> {
+        self.contents.iter().filter_map(|pct| match pct {
+            OscPacket::Message(msg) => Some(msg),
+            _ => None
+        })
+    }
+
+    // All nested bundles in contents, in order, skipping any messages
+    pub fn iter_bundles(&self) -> impl Iterator<Item = &OscBundle> {
+        self.contents.iter().filter_map(|pct| match pct {
+            OscPacket::Bundle(bundle) => Some(bundle),
+            _ => None
+        })
+    }
+
+    pub fn messages_with_addr<'b>(&'b self, addr: &'b str) -> impl Iterator<Item = &'b OscMessage> {
+        self.iter_messages().filter(move |msg| msg.addr == addr)
+    }
+
+    pub fn get_bundle(&self, content_index: usize) -> Result<OscBundle, String> {
+        self.contents.get(content_index)
+            .cloned()
+            .ok_or(format!("Could not get packet on index {} for bundle {:?}", content_index, &self))
+            .and_then(|pct| match pct {
+                OscPacket::Bundle(msg) => {
+                    Ok(msg)
+                }
+                _ => {Err("Not a bundle".to_string())}
+            })
+    }
+}
+
+/*
+    Implemented by structs that can be parsed from a specific kind of tagged bundle,
+        so that OSCStack::on_typed_tbundle can hand handlers fully typed values.
+ */
+pub trait FromTaggedBundle: Sized {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String>;
+}
+
+pub const INFO_VERSION_KEY: &str = "version";
+pub const INFO_ID_KEY: &str = "id";
+pub const INFO_SESSION_KEY: &str = "session";
+pub const INFO_SEQUENCE_KEY: &str = "seq";
+pub const INFO_CHECKSUM_KEY: &str = "checksum";
+pub const INFO_DEADLINE_KEY: &str = "deadline";
+
+// How BundleInfo treats /bundle_info args it can't make sense of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InfoParsing {
+    // Duplicate keys, keys without a value and values of the wrong type are errors
+    Strict,
+    // The first occurrence of a key wins and anything malformed is skipped
+    #[default]
+    Lenient
+}
+
+/*
+    Everything in /bundle_info besides the tag, as key/value pairs in any order:
+        ["/bundle_info", "note_on", "deadline", "1718000000.25", "seq", 41, "session", "live-a"]
+    Known keys are "deadline" (double or decimal string, see deadline.rs), "version" (int),
+        "id" (string), "session" (string, see session.rs), "seq" (int, see sequence.rs) and
+        "checksum" (int). Pairs with other keys are kept in extra, so that newer conventions
+        pass through older services.
+ */
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BundleInfo {
+    pub tag: String,
+    // Double or decimal string
+    pub deadline: Option<OscType>,
+    pub version: Option<i32>,
+    pub id: Option<String>,
+    pub session: Option<String>,
+    pub seq: Option<i32>,
+    pub checksum: Option<i32>,
+    pub extra: Vec<(String, OscType)>
+}
+
+impl BundleInfo {
+    pub fn new(tag: &str) -> BundleInfo {
+        BundleInfo { tag: tag.to_string(), ..BundleInfo::default() }
+    }
+
+    pub fn from_bundle(bundle: &OscBundle, parsing: InfoParsing) -> Result<BundleInfo, String> {
+        match bundle.content.first() {
+            Some(OscPacket::Message(header)) => BundleInfo::from_message(header, parsing),
+            _ => Err("First element in bundle not an info message!".to_string())
+        }
+    }
+
+    pub fn from_message(header: &OscMessage, parsing: InfoParsing) -> Result<BundleInfo, String> {
+        if header.addr != "/bundle_info" {
+            return Err(format!("Expected /bundle_info, got: {}", header.addr));
+        }
+
+        let mut args = header.args.iter();
+        let tag = args.next()
+            .ok_or("bundle info empty")?
+            .clone()
+            .string().ok_or("bundle info should be a string")?;
+        let mut info = BundleInfo::new(&tag);
+
+        let mut rest = args.as_slice();
+        let strict = parsing == InfoParsing::Strict;
+        let mut seen: Vec<&str> = Vec::new();
+        while let Some(key_arg) = rest.first() {
+            let OscType::String(key) = key_arg else {
+                if strict {
+                    return Err(format!("Expected a key in bundle info of {}, got {:?}", tag, key_arg));
+                }
+                rest = &rest[1..];
+                continue;
+            };
+            let Some(value) = rest.get(1) else {
+                if strict {
+                    return Err(format!("Key {} in bundle info of {} has no value", key, tag));
+                }
+                break;
+            };
+            rest = &rest[2..];
+
+            if seen.contains(&key.as_str()) {
+                if strict {
+                    return Err(format!("Key {} appears more than once in bundle info of {}", key, tag));
+                }
+                continue;
+            }
+            seen.push(key);
+
+            let stored = match (key.as_str(), value) {
+                (INFO_DEADLINE_KEY, OscType::Double(_) | OscType::String(_)) => { info.deadline = Some(value.clone()); true },
+                (INFO_VERSION_KEY, OscType::Int(version)) => { info.version = Some(*version); true },
+                (INFO_ID_KEY, OscType::String(id)) => { info.id = Some(id.clone()); true },
+                (INFO_SESSION_KEY, OscType::String(session)) => { info.session = Some(session.clone()); true },
+                (INFO_SEQUENCE_KEY, OscType::Int(seq)) => { info.seq = Some(*seq); true },
+                (INFO_CHECKSUM_KEY, OscType::Int(checksum)) => { info.checksum = Some(*checksum); true },
+                (INFO_DEADLINE_KEY | INFO_VERSION_KEY | INFO_ID_KEY | INFO_SESSION_KEY | INFO_SEQUENCE_KEY | INFO_CHECKSUM_KEY, _) => false,
+                (_, value) => { info.extra.push((key.clone(), value.clone())); true }
+            };
+            if !stored && strict {
+                return Err(format!("Unexpected value {:?} for {} in bundle info of {}", value, key, tag));
+            }
+        }
+
+        Ok(info)
+    }
+
+    // Known keys first, deadline leading, then extra pairs in their original order
+    pub fn to_message(&self) -> OscMessage {
+        let mut args = vec![OscType::String(self.tag.clone())];
+
+        let known = [
+            (INFO_DEADLINE_KEY, self.deadline.clone()),
+            (INFO_SEQUENCE_KEY, self.seq.map(OscType::Int)),
+            (INFO_SESSION_KEY, self.session.clone().map(OscType::String)),
+            (INFO_VERSION_KEY, self.version.map(OscType::Int)),
+            (INFO_ID_KEY, self.id.clone().map(OscType::String)),
+            (INFO_CHECKSUM_KEY, self.checksum.map(OscType::Int))
+        ];
+        for (key, value) in known {
+            if let Some(value) = value {
+                args.push(OscType::String(key.to_string()));
+                args.push(value);
+            }
+        }
+        for (key, value) in &self.extra {
+            args.push(OscType::String(key.clone()));
+            args.push(value.clone());
+        }
+
+        OscMessage { addr: "/bundle_info".to_string(), args }
+    }
+}
+
+// Describes what TaggedBundle::new_lenient guessed when the bundle header was not standard
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagRecovery {
+    pub guessed_tag: String,
+    pub reason: String
+}
+
+impl fmt::Display for TagRecovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "guessed bundle tag {}: {}", self.guessed_tag, self.reason)
+    }
+}
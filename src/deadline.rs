@@ -0,0 +1,61 @@
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::model::{BundleInfo, InfoParsing};
+
+/*
+    Deadline convention for packets that are worthless when late, e.g. note events after a
+        network hiccup. The deadline is a wall clock time in seconds since the unix epoch,
+        as a decimal string (like timed_msg times) or a double, given either:
+        1. Under the "deadline" key of /bundle_info (see BundleInfo):
+            ["/bundle_info", "note_on", "deadline", "1718000000.25"]
+        2. In a /jdw/deadline message first in a bundle without /bundle_info:
+            ["/jdw/deadline", "1718000000.25"]
+            OSCStack dispatches the rest of such a bundle's contents individually.
+    OSCStack drops bundles whose deadline has passed before dispatching them.
+ */
+
+pub const DEADLINE_ADDR: &str = "/jdw/deadline";
+
+fn to_epoch_secs(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:09}", since_epoch.as_secs(), since_epoch.subsec_nanos())
+}
+
+fn from_epoch_secs(arg: &OscType) -> Option<SystemTime> {
+    let secs = match arg {
+        OscType::String(s) => BigDecimal::from_str(s).ok()?.to_f64()?,
+        OscType::Double(d) => *d,
+        _ => return None
+    };
+    Duration::try_from_secs_f64(secs).ok().map(|since_epoch| UNIX_EPOCH + since_epoch)
+}
+
+// Tagged bundle header carrying a deadline
+pub fn bundle_info_with_deadline(tag: &str, valid_until: SystemTime) -> OscMessage {
+    BundleInfo { deadline: Some(OscType::String(to_epoch_secs(valid_until))), ..BundleInfo::new(tag) }.to_message()
+}
+
+// Header for bundles that are not tagged
+pub fn deadline_header(valid_until: SystemTime) -> OscMessage {
+    OscMessage {
+        addr: DEADLINE_ADDR.to_string(),
+        args: vec![OscType::String(to_epoch_secs(valid_until))]
+    }
+}
+
+// Deadline of the bundle according to either convention, None if it has none
+pub fn valid_until(bundle: &OscBundle) -> Option<SystemTime> {
+    match bundle.content.first()? {
+        OscPacket::Message(msg) if msg.addr == "/bundle_info" => from_epoch_secs(&BundleInfo::from_message(msg, InfoParsing::Lenient).ok()?.deadline?),
+        OscPacket::Message(msg) if msg.addr == DEADLINE_ADDR => from_epoch_secs(msg.args.first()?),
+        _ => None
+    }
+}
+
+pub fn is_expired(bundle: &OscBundle, now: SystemTime) -> bool {
+    valid_until(bundle).is_some_and(|deadline| deadline < now)
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, SystemTime};
+
+use rosc::{OscMessage, OscTime, OscType};
+
+/*
+    Echo convention for connectivity and latency checks between services:
+    ["/jdw/echo", "ping", 7]
+    is answered to the sender with the same args plus the time the echo was received:
+    ["/jdw/echo/reply", "ping", 7, <OSC time>]
+
+    OSCStack answers echoes itself when enabled with OSCStack::echo. Round trip latency is
+        measured by sending the local time as the first arg (see ping_message) and comparing
+        it on reply (see EchoReply::round_trip).
+ */
+
+pub const ECHO_ADDR: &str = "/jdw/echo";
+pub const ECHO_REPLY_ADDR: &str = "/jdw/echo/reply";
+
+pub fn echo_message(args: Vec<OscType>) -> OscMessage {
+    OscMessage { addr: ECHO_ADDR.to_string(), args }
+}
+
+// Echo carrying the current time, for measuring the round trip
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ping_message() -> OscMessage {
+    ping_message_at(SystemTime::now())
+}
+
+pub fn ping_message_at(sent_at: SystemTime) -> OscMessage {
+    echo_message(vec![OscType::Time(osc_time(sent_at))])
+}
+
+// Times before 1900 cannot be expressed in OSC, which no clock should report
+fn osc_time(time: SystemTime) -> OscTime {
+    OscTime::try_from(time).unwrap_or(OscTime { seconds: 0, fractional: 0 })
+}
+
+pub fn echo_reply(request: &OscMessage, received_at: SystemTime) -> OscMessage {
+    let mut args = request.args.clone();
+    args.push(OscType::Time(osc_time(received_at)));
+    OscMessage { addr: ECHO_REPLY_ADDR.to_string(), args }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EchoReply {
+    // As sent with the echo
+    pub args: Vec<OscType>,
+    pub received_at: SystemTime
+}
+
+impl EchoReply {
+    pub fn from_message(msg: &OscMessage) -> Result<EchoReply, String> {
+        if msg.addr != ECHO_REPLY_ADDR {
+            return Err(format!("{} is not an echo reply", msg.addr));
+        }
+
+        match msg.args.split_last() {
+            Some((OscType::Time(time), args)) => Ok(EchoReply { args: args.to_vec(), received_at: SystemTime::from(*time) }),
+            _ => Err("Echo reply does not end with a receive time".to_string())
+        }
+    }
+
+    // Time since the echo was sent, for replies to ping_message; None for other echoes
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn round_trip(&self) -> Option<Duration> {
+        self.round_trip_at(SystemTime::now())
+    }
+
+    pub fn round_trip_at(&self, now: SystemTime) -> Option<Duration> {
+        match self.args.first() {
+            Some(OscType::Time(sent)) => now.duration_since(SystemTime::from(*sent)).ok(),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use bigdecimal::{BigDecimal, Signed, ToPrimitive, Zero};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::model::{FromTaggedBundle, OscArgHandler, TaggedBundle, TimedOSCPacket};
+use crate::time_value::{TimePolicy, TimeValue};
+
+/*
+    Breakpoint envelopes for automation beyond straight ramps, e.g. ADSR-like shapes.
+    Each breakpoint holds a value at a time relative to the envelope start, and the shape
+        of the segment leading up to it from the previous breakpoint.
+
+    let env = Envelope::new(0.0)
+        .then(BigDecimal::from_str("0.1")?, 1.0, CurveShape::Linear)
+        .then(BigDecimal::from(2), 0.0, CurveShape::Curve(-4.0));
+
+    Envelopes travel over OSC as tagged bundles:
+    [/bundle_info, "envelope"]
+    [/envelope_point, "0.0", 0.0, "lin", 0.0]
+    [/envelope_point, "0.1", 1.0, "lin", 0.0]
+    [/envelope_point, "2", 0.0, "curve", -4.0]
+ */
+
+pub const ENVELOPE_TAG: &str = "envelope";
+pub const ENVELOPE_POINT_ADDR: &str = "/envelope_point";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CurveShape {
+    #[default]
+    Linear,
+    // Equal ratios over time; both ends must share a sign and not be 0, else linear is used
+    Exponential,
+    // Stays at the previous value and jumps at the breakpoint
+    Step,
+    // SuperCollider style curvature: negative bends early, positive bends late, 0 is linear
+    Curve(f32)
+}
+
+impl CurveShape {
+    fn name(&self) -> &'static str {
+        match self {
+            CurveShape::Linear => "lin",
+            CurveShape::Exponential => "exp",
+            CurveShape::Step => "step",
+            CurveShape::Curve(_) => "curve"
+        }
+    }
+
+    fn from_name(name: &str, curvature: f32) -> Result<CurveShape, String> {
+        match name {
+            "lin" => Ok(CurveShape::Linear),
+            "exp" => Ok(CurveShape::Exponential),
+            "step" => Ok(CurveShape::Step),
+            "curve" => Ok(CurveShape::Curve(curvature)),
+            _ => Err(format!("Unknown curve shape: {}", name))
+        }
+    }
+
+    // Value at progress (0.0 - 1.0) through a segment from start to end
+    fn interpolate(&self, start: f32, end: f32, progress: f64) -> f32 {
+        let (start_f, end_f) = (start as f64, end as f64);
+        let linear = start_f + (end_f - start_f) * progress;
+        let value = match self {
+            CurveShape::Linear => linear,
+            CurveShape::Exponential if start != 0.0 && end != 0.0 && start.signum() == end.signum() =>
+                start_f * (end_f / start_f).powf(progress),
+            CurveShape::Exponential => linear,
+            CurveShape::Step => if progress >= 1.0 { end_f } else { start_f },
+            CurveShape::Curve(c) if c.abs() < 0.001 => linear,
+            CurveShape::Curve(c) => {
+                let c = *c as f64;
+                start_f + (end_f - start_f) * (1.0 - (c * progress).exp()) / (1.0 - c.exp())
+            }
+        };
+        value as f32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub time: BigDecimal,
+    pub value: f32,
+    pub shape: CurveShape
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    // Sorted by time, always starting with a breakpoint at time 0
+    pub breakpoints: Vec<Breakpoint>
+}
+
+impl Envelope {
+    pub fn new(start_value: f32) -> Envelope {
+        Envelope {
+            breakpoints: vec![Breakpoint { time: BigDecimal::zero(), value: start_value, shape: CurveShape::Linear }]
+        }
+    }
+
+    // Add a breakpoint at the given time from envelope start; out of order times are sorted in
+    pub fn then(mut self, time: BigDecimal, value: f32, shape: CurveShape) -> Envelope {
+        let index = self.breakpoints.partition_point(|point| point.time <= time);
+        self.breakpoints.insert(index, Breakpoint { time, value, shape });
+        self
+    }
+
+    pub fn duration(&self) -> BigDecimal {
+        self.breakpoints.last().map(|point| point.time.clone()).unwrap_or_default()
+    }
+
+    pub fn value_at(&self, time: &BigDecimal) -> f32 {
+        let next_index = self.breakpoints.partition_point(|point| &point.time <= time);
+
+        let (previous, next) = match (next_index.checked_sub(1), self.breakpoints.get(next_index)) {
+            (Some(previous), Some(next)) => (&self.breakpoints[previous], next),
+            (Some(previous), None) => return self.breakpoints[previous].value,
+            (None, _) => return self.breakpoints.first().map(|point| point.value).unwrap_or_default()
+        };
+
+        let length = (&next.time - &previous.time).to_f64().unwrap_or_default();
+        let progress = (time - &previous.time).to_f64().unwrap_or_default() / length;
+        next.shape.interpolate(previous.value, next.value, progress)
+    }
+
+    /*
+        Sample the envelope every step (same time unit as the breakpoints) into timed packets,
+            always including the final value at the end of the envelope.
+        The packet for each value is up to the caller, e.g. an /n_set for a running synth.
+     */
+    pub fn sample(&self, step: &BigDecimal, to_packet: impl Fn(f32) -> OscPacket) -> Result<Vec<TimedOSCPacket>, String> {
+        if !step.is_positive() {
+            return Err(format!("Envelope sample step must be positive, got {}", step));
+        }
+
+        let duration = self.duration();
+        let mut packets = Vec::new();
+        let mut time = BigDecimal::zero();
+        while time < duration {
+            packets.push(TimedOSCPacket::new(time.clone(), to_packet(self.value_at(&time))));
+            time += step;
+        }
+
+        let end_value = self.breakpoints.last().map(|point| point.value).unwrap_or_default();
+        packets.push(TimedOSCPacket::new(duration, to_packet(end_value)));
+        Ok(packets)
+    }
+
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = OscMessage {
+            addr: "/bundle_info".to_string(),
+            args: vec![OscType::String(ENVELOPE_TAG.to_string())]
+        };
+
+        let points = self.breakpoints.iter().map(|point| OscPacket::Message(OscMessage {
+            addr: ENVELOPE_POINT_ADDR.to_string(),
+            args: vec![
+                TimeValue::new(&point.time, &TimePolicy::default()).to_osc_arg(),
+                OscType::Float(point.value),
+                OscType::String(point.shape.name().to_string()),
+                OscType::Float(match point.shape { CurveShape::Curve(c) => c, _ => 0.0 })
+            ]
+        }));
+
+        OscBundle {
+            timetag: OscTime { seconds: 0, fractional: 1 },
+            content: std::iter::once(OscPacket::Message(info)).chain(points).collect()
+        }
+    }
+}
+
+impl FromTaggedBundle for Envelope {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        if bundle.bundle_tag != ENVELOPE_TAG {
+            return Err(format!("Attempted to parse {} as {} bundle", bundle.bundle_tag, ENVELOPE_TAG));
+        }
+
+        let mut points = bundle.messages_with_addr(ENVELOPE_POINT_ADDR).map(|msg| {
+            let time_arg = msg.args.first().ok_or("Envelope point has no time")?;
+            let time = TimeValue::from_osc_arg(time_arg, &TimePolicy::default())?.into();
+            let value = msg.get_float_at(1, "value")?;
+            let shape = CurveShape::from_name(&msg.get_string_at(2, "shape")?, msg.get_float_at(3, "curvature").unwrap_or(0.0))?;
+            Ok(Breakpoint { time, value, shape })
+        }).collect::<Result<Vec<Breakpoint>, String>>()?;
+
+        points.sort_by(|a, b| a.time.cmp(&b.time));
+        match points.first() {
+            Some(first) if first.time.is_zero() => Ok(Envelope { breakpoints: points }),
+            Some(first) => Err(format!("Envelope should start at time 0, first point is at {}", first.time)),
+            None => Err("Envelope bundle has no points".to_string())
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Safety rules are shared by all functions and described once below
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::model::TimedOSCPacket;
+use crate::time_value::{TimePolicy, TimeValue};
+
+/*
+    Minimal C ABI for building and encoding JDW packets, so that other languages (mainly the
+        Python side via cffi) use the same wire format code instead of reimplementing it.
+    Build as a shared library with: cargo rustc --release --features ffi --crate-type cdylib
+
+    msg = jdw_message_new(b"/note_on")
+    jdw_message_push_string(msg, b"blip")
+    jdw_message_push_float(msg, 440.0)
+    timed = jdw_timed_msg_new(b"0.25", msg)          # takes ownership of msg
+    bundle = jdw_tbundle_new(b"batch")
+    jdw_tbundle_push_bundle(bundle, timed)          # takes ownership of timed
+    data = jdw_bundle_encode(bundle, len_ptr)
+    ...
+    jdw_bytes_free(data, len)
+    jdw_bundle_free(bundle)
+
+    Safety rules for all functions:
+        - Strings are NUL-terminated UTF-8
+        - Handles must come from the matching _new function and not be used after being
+            freed or handed to a function documented to take ownership
+        - Functions returning a handle or buffer return null on failure, see jdw_last_error
+        - Encode functions need out_len to report the buffer length, which jdw_bytes_free
+            requires; they fail when it is null
+ */
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(error: String) {
+    let error = CString::new(error).unwrap_or_else(|_| CString::from(c"Error message contained NUL"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+unsafe fn read_str<'s>(value: *const c_char) -> Result<&'s str, String> {
+    if value.is_null() {
+        return Err("Null string argument".to_string());
+    }
+    CStr::from_ptr(value).to_str().map_err(|e| format!("Invalid UTF-8 string argument: {}", e))
+}
+
+// Hands out the encoded bytes as a buffer to be freed with jdw_bytes_free
+unsafe fn encode(packet: &OscPacket, out_len: *mut usize) -> *mut u8 {
+    // Without the length the buffer could never be freed
+    if out_len.is_null() {
+        set_error("Null length argument".to_string());
+        return ptr::null_mut();
+    }
+
+    match rosc::encoder::encode(packet) {
+        Ok(bytes) => {
+            let mut bytes = bytes.into_boxed_slice();
+            *out_len = bytes.len();
+            let data = bytes.as_mut_ptr();
+            std::mem::forget(bytes);
+            data
+        },
+        Err(e) => {
+            set_error(format!("Failed to encode packet: {}", e));
+            ptr::null_mut()
+        }
+    }
+}
+
+// Error of the most recent failed call on this thread, null if none; valid until the next failure
+#[no_mangle]
+pub extern "C" fn jdw_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map(|error| error.as_ptr()).unwrap_or(ptr::null()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_message_new(addr: *const c_char) -> *mut OscMessage {
+    match read_str(addr) {
+        Ok(addr) => Box::into_raw(Box::new(OscMessage { addr: addr.to_string(), args: vec![] })),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_message_push_float(msg: *mut OscMessage, value: f32) {
+    if let Some(msg) = msg.as_mut() {
+        msg.args.push(OscType::Float(value));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_message_push_int(msg: *mut OscMessage, value: i32) {
+    if let Some(msg) = msg.as_mut() {
+        msg.args.push(OscType::Int(value));
+    }
+}
+
+// Returns false (see jdw_last_error) if the string is invalid
+#[no_mangle]
+pub unsafe extern "C" fn jdw_message_push_string(msg: *mut OscMessage, value: *const c_char) -> bool {
+    let Some(msg) = msg.as_mut() else { return false };
+    match read_str(value) {
+        Ok(value) => {
+            msg.args.push(OscType::String(value.to_string()));
+            true
+        },
+        Err(e) => {
+            set_error(e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_message_encode(msg: *const OscMessage, out_len: *mut usize) -> *mut u8 {
+    match msg.as_ref() {
+        Some(msg) => encode(&OscPacket::Message(msg.clone()), out_len),
+        None => {
+            set_error("Null message".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_message_free(msg: *mut OscMessage) {
+    if !msg.is_null() {
+        drop(Box::from_raw(msg));
+    }
+}
+
+// Tagged bundle holding only its /bundle_info header so far
+#[no_mangle]
+pub unsafe extern "C" fn jdw_tbundle_new(tag: *const c_char) -> *mut OscBundle {
+    match read_str(tag) {
+        Ok(tag) => Box::into_raw(Box::new(OscBundle {
+            timetag: OscTime { seconds: 0, fractional: 1 },
+            content: vec![OscPacket::Message(OscMessage {
+                addr: "/bundle_info".to_string(),
+                args: vec![OscType::String(tag.to_string())]
+            })]
+        })),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Takes ownership of msg
+#[no_mangle]
+pub unsafe extern "C" fn jdw_tbundle_push_message(bundle: *mut OscBundle, msg: *mut OscMessage) {
+    if msg.is_null() {
+        return;
+    }
+    let msg = Box::from_raw(msg);
+    if let Some(bundle) = bundle.as_mut() {
+        bundle.content.push(OscPacket::Message(*msg));
+    }
+}
+
+// Takes ownership of child
+#[no_mangle]
+pub unsafe extern "C" fn jdw_tbundle_push_bundle(bundle: *mut OscBundle, child: *mut OscBundle) {
+    if child.is_null() {
+        return;
+    }
+    let child = Box::from_raw(child);
+    if let Some(bundle) = bundle.as_mut() {
+        bundle.content.push(OscPacket::Bundle(*child));
+    }
+}
+
+/*
+    timed_msg bundle wrapping the message at the given time, written as a decimal string
+        (e.g. "0.25") to avoid float rounding. The time is parsed and rounded like any other
+        JDW time (see TimeValue). Takes ownership of msg, also on failure.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn jdw_timed_msg_new(time: *const c_char, msg: *mut OscMessage) -> *mut OscBundle {
+    if msg.is_null() {
+        set_error("Null message".to_string());
+        return ptr::null_mut();
+    }
+    let msg = Box::from_raw(msg);
+
+    let time = match read_str(time).and_then(|time| TimeValue::parse(time, &TimePolicy::default())) {
+        Ok(time) => time.as_bigdecimal().clone(),
+        Err(e) => {
+            set_error(e);
+            return ptr::null_mut();
+        }
+    };
+
+    Box::into_raw(Box::new(TimedOSCPacket::new(time, OscPacket::Message(*msg)).to_bundle()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_bundle_encode(bundle: *const OscBundle, out_len: *mut usize) -> *mut u8 {
+    match bundle.as_ref() {
+        Some(bundle) => encode(&OscPacket::Bundle(bundle.clone()), out_len),
+        None => {
+            set_error("Null bundle".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jdw_bundle_free(bundle: *mut OscBundle) {
+    if !bundle.is_null() {
+        drop(Box::from_raw(bundle));
+    }
+}
+
+// Frees a buffer returned by one of the encode functions, len as reported by it
+#[no_mangle]
+pub unsafe extern "C" fn jdw_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
@@ -0,0 +1,115 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+use rosc::{OscMessage, OscPacket};
+
+use crate::cancel::CancellationRegistry;
+use crate::client::OscClient;
+use crate::codec::SharedCodec;
+use crate::local;
+use crate::metrics::MetricsSnapshot;
+use crate::osc_stack::{DispatchContext, ReceiveTime};
+use crate::stack_controller::StackController;
+
+/*
+    Everything a handler may need from the stack besides the packet, passed to handlers
+        registered with OSCStack::on_message_with_context and on_tbundle_with_context:
+
+    stack.on_message_with_context("/render", &|msg, ctx| {
+        let token = ctx.cancellations().register("render-42");
+        ...
+        ctx.reply_message(reply::ok_reply(&msg))
+    })
+
+    Saves handlers from capturing controllers, clients and registries of their own.
+ */
+pub struct HandlerContext {
+    controller: StackController,
+    dispatch: DispatchContext,
+    replies: ReplySocket
+}
+
+impl HandlerContext {
+    pub(crate) fn new(controller: StackController, dispatch: DispatchContext, replies: ReplySocket) -> HandlerContext {
+        HandlerContext { controller, dispatch, replies }
+    }
+
+    // Address the packet was received from, None for packets not received over the wire
+    pub fn sender(&self) -> Option<SocketAddr> {
+        self.dispatch.sender
+    }
+
+    pub fn received(&self) -> Option<ReceiveTime> {
+        self.dispatch.received
+    }
+
+    // Timed_msg time, lineage and session of the packet, as from osc_stack::dispatch_context
+    pub fn dispatch(&self) -> &DispatchContext {
+        &self.dispatch
+    }
+
+    pub fn mode(&self) -> Option<String> {
+        self.controller.mode()
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.controller.metrics()
+    }
+
+    pub fn cancellations(&self) -> CancellationRegistry {
+        self.controller.cancellations()
+    }
+
+    pub fn controller(&self) -> &StackController {
+        &self.controller
+    }
+
+    // Sends the packet back to the sender, from the stack's own address once begin() is running
+    pub fn reply(&self, packet: &OscPacket) -> Result<(), String> {
+        let sender = self.sender().ok_or("Packet has no sender to reply to")?;
+        self.replies.send(&sender.to_string(), packet)
+    }
+
+    pub fn reply_message(&self, msg: OscMessage) -> Result<(), String> {
+        self.reply(&OscPacket::Message(msg))
+    }
+}
+
+/*
+    Answers to peers (replies, hellos, pings, gap reports) go out from the receiving socket
+        once the stack has bound it, so that answers to them come back to the stack. Nothing
+        is kept per target, as targets are whoever sent something. Before binding, and for
+        local endpoints, a throwaway client is used instead.
+    Shared by the stack and all its handler contexts.
+ */
+#[derive(Clone)]
+pub(crate) struct ReplySocket {
+    socket: Arc<Mutex<Option<UdpSocket>>>,
+    codec: SharedCodec
+}
+
+impl ReplySocket {
+    pub(crate) fn new(codec: SharedCodec) -> ReplySocket {
+        ReplySocket { socket: Arc::new(Mutex::new(None)), codec }
+    }
+
+    pub(crate) fn set(&self, socket: Option<UdpSocket>) {
+        *self.socket.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = socket;
+    }
+
+    pub(crate) fn send(&self, target: &str, packet: &OscPacket) -> Result<(), String> {
+        let socket = self.socket.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match &*socket {
+            Some(socket) if local::local_name(target).is_none() => {
+                let target_addr = target.to_socket_addrs()
+                    .map_err(|e| format!("Invalid target address {}: {}", target, e))?
+                    .next()
+                    .ok_or(format!("Target address {} did not resolve", target))?;
+                let bytes = self.codec.encode(packet)?;
+                socket.send_to(&bytes, target_addr).map_err(|e| format!("Failed to send to {}: {}", target, e))?;
+                Ok(())
+            },
+            _ => OscClient::new(target)?.with_codec(self.codec.clone()).send(packet)
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use rosc::{OscMessage, OscType};
+
+use crate::compat::WIRE_FORMAT_VERSION;
+
+/*
+    Capability handshake, so that services can check what a peer understands before talking
+        to it instead of finding out through dropped packets:
+    ["/jdw/hello", "sampler", 1, 2, "note_on", "timed_msg", "/s_new", "/n_set"]
+    carries the name of the sender, its wire format version (see compat.rs), the number of
+        bundle tags it handles followed by the tags, and then the message addresses it handles.
+    A service receiving a hello answers with its own capabilities on "/jdw/hello/reply",
+        in the same layout.
+
+    OSCStack answers hellos itself when enabled with OSCStack::hello, describing everything
+        registered with it. Peers can be greeted on startup or on demand with
+        OSCStack::say_hello or OscClient::send_hello.
+ */
+
+pub const HELLO_ADDR: &str = "/jdw/hello";
+pub const HELLO_REPLY_ADDR: &str = "/jdw/hello/reply";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub name: String,
+    pub wire_version: i32,
+    // Sorted
+    pub tags: Vec<String>,
+    // Sorted
+    pub addresses: Vec<String>
+}
+
+impl Capabilities {
+    pub fn new(name: &str) -> Capabilities {
+        Capabilities { name: name.to_string(), wire_version: WIRE_FORMAT_VERSION as i32, tags: Vec::new(), addresses: Vec::new() }
+    }
+
+    pub fn with_tags<S: ToString>(mut self, tags: impl IntoIterator<Item = S>) -> Capabilities {
+        self.tags.extend(tags.into_iter().map(|tag| tag.to_string()));
+        self.tags.sort();
+        self.tags.dedup();
+        self
+    }
+
+    pub fn with_addresses<S: ToString>(mut self, addresses: impl IntoIterator<Item = S>) -> Capabilities {
+        self.addresses.extend(addresses.into_iter().map(|addr| addr.to_string()));
+        self.addresses.sort();
+        self.addresses.dedup();
+        self
+    }
+
+    pub fn supports_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|supported| supported == tag)
+    }
+
+    pub fn supports_address(&self, addr: &str) -> bool {
+        self.addresses.iter().any(|supported| supported == addr)
+    }
+
+    pub fn is_wire_compatible(&self) -> bool {
+        self.wire_version == WIRE_FORMAT_VERSION as i32
+    }
+
+    /*
+        Fail fast check for a peer that is about to be sent the given tags and addresses.
+        The error names the peer along with everything it lacks, including a differing wire
+            format version.
+     */
+    pub fn require(&self, tags: &[&str], addresses: &[&str]) -> Result<(), String> {
+        let mut missing: Vec<String> = Vec::new();
+        if !self.is_wire_compatible() {
+            missing.push(format!("wire format {} (has {})", WIRE_FORMAT_VERSION, self.wire_version));
+        }
+        missing.extend(tags.iter().filter(|tag| !self.supports_tag(tag)).map(|tag| format!("tag {}", tag)));
+        missing.extend(addresses.iter().filter(|addr| !self.supports_address(addr)).map(|addr| format!("address {}", addr)));
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} does not support {}", self.name, missing.join(", ")))
+        }
+    }
+
+    pub fn hello_message(&self) -> OscMessage {
+        self.to_message(HELLO_ADDR)
+    }
+
+    pub fn reply_message(&self) -> OscMessage {
+        self.to_message(HELLO_REPLY_ADDR)
+    }
+
+    fn to_message(&self, addr: &str) -> OscMessage {
+        let mut args = vec![
+            OscType::String(self.name.clone()),
+            OscType::Int(self.wire_version),
+            OscType::Int(self.tags.len() as i32)
+        ];
+        args.extend(self.tags.iter().map(|tag| OscType::String(tag.clone())));
+        args.extend(self.addresses.iter().map(|addr| OscType::String(addr.clone())));
+        OscMessage { addr: addr.to_string(), args }
+    }
+
+    // Reads both hellos and replies
+    pub fn from_message(msg: &OscMessage) -> Result<Capabilities, String> {
+        if msg.addr != HELLO_ADDR && msg.addr != HELLO_REPLY_ADDR {
+            return Err(format!("{} is not a hello", msg.addr));
+        }
+
+        let (name, wire_version, tag_count) = match msg.args.as_slice() {
+            [OscType::String(name), OscType::Int(version), OscType::Int(count), ..] => (name, *version, *count),
+            _ => return Err("Hello should start with a name, a wire version and a tag count".to_string())
+        };
+
+        let names = msg.args[3..].iter()
+            .map(|arg| arg.clone().string().ok_or(format!("Expected only strings after the tag count in hello, got {:?}", arg)))
+            .collect::<Result<Vec<String>, String>>()?;
+        let tag_count = usize::try_from(tag_count).ok()
+            .filter(|count| *count <= names.len())
+            .ok_or(format!("Hello claims {} tags but has {} names", tag_count, names.len()))?;
+        let (tags, addresses) = names.split_at(tag_count);
+
+        let mut capabilities = Capabilities::new(name).with_tags(tags).with_addresses(addresses);
+        capabilities.wire_version = wire_version;
+        Ok(capabilities)
+    }
+}
@@ -0,0 +1,38 @@
+/*
+    Classic hex dumps for inspecting raw packets, e.g. from non-JDW OSC senders that fail to
+        decode. Sixteen bytes per line with offsets and printable ASCII alongside:
+
+    0000  2f 6e 6f 74 65 5f 6f 6e  00 00 00 00 2c 73 66 00  |/note_on....,sf.|
+    ... 12 more bytes
+ */
+
+const BYTES_PER_LINE: usize = 16;
+
+// Dump of at most the first max_bytes bytes, noting how many were left out
+pub fn hex_dump(bytes: &[u8], max_bytes: usize) -> String {
+    let shown = &bytes[..bytes.len().min(max_bytes)];
+
+    let mut lines: Vec<String> = shown.chunks(BYTES_PER_LINE).enumerate()
+        .map(|(line, chunk)| format!("{:04x}  {:<49} |{}|", line * BYTES_PER_LINE, hex_column(chunk), ascii_column(chunk)))
+        .collect();
+
+    if bytes.len() > shown.len() {
+        lines.push(format!("... {} more bytes", bytes.len() - shown.len()));
+    }
+    if bytes.is_empty() {
+        lines.push("(no bytes)".to_string());
+    }
+    lines.join("\n")
+}
+
+// Bytes in two groups of eight, like hexdump -C
+fn hex_column(chunk: &[u8]) -> String {
+    chunk.chunks(BYTES_PER_LINE / 2)
+        .map(|half| half.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" "))
+        .collect::<Vec<String>>()
+        .join("  ")
+}
+
+fn ascii_column(chunk: &[u8]) -> String {
+    chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect()
+}
@@ -0,0 +1,72 @@
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::model::{TaggedBundle, TimedOSCPacket};
+
+/*
+    Wrapper for packets that must take effect right away, e.g. a panic or all-notes-off:
+    [/bundle_info, "immediate"]
+    [/n_free, 1001]
+    ...
+
+    OSCStack dispatches the contents of immediate bundles as soon as they arrive, ahead of
+        anything else: they are exempt from rate limiting and ordered delivery, jump the queues
+        of dedicated and sharded handlers regardless of the memory budget, and timed_msg
+        contents are unwrapped and executed without waiting for their time.
+ */
+
+pub const IMMEDIATE_TAG: &str = "immediate";
+
+/*
+    The big red button: asks a service to stop making sound at once. OSCStack::on_panic
+        clears what the stack has queued and cancels running operations; services that
+        schedule timed packets themselves should drop them in their panic handler.
+    Sent wrapped in an immediate bundle, see panic_bundle and OscClient::panic.
+ */
+pub const PANIC_ADDR: &str = "/jdw/panic";
+
+pub fn panic_message() -> OscMessage {
+    OscMessage { addr: PANIC_ADDR.to_string(), args: vec![] }
+}
+
+pub fn panic_bundle() -> OscBundle {
+    immediate_messages(vec![panic_message()])
+}
+
+pub fn immediate_bundle(contents: Vec<OscPacket>) -> OscBundle {
+    let info = OscMessage {
+        addr: "/bundle_info".to_string(),
+        args: vec![OscType::String(IMMEDIATE_TAG.to_string())]
+    };
+
+    OscBundle {
+        timetag: OscTime { seconds: 0, fractional: 1 },
+        content: std::iter::once(OscPacket::Message(info)).chain(contents).collect()
+    }
+}
+
+pub fn immediate_messages(messages: Vec<OscMessage>) -> OscBundle {
+    immediate_bundle(messages.into_iter().map(OscPacket::Message).collect())
+}
+
+// Cheap check on the header only, for fast paths that run before full parsing
+pub fn is_immediate(packet: &OscPacket) -> bool {
+    let OscPacket::Bundle(bundle) = packet else { return false };
+    matches!(
+        bundle.content.first(),
+        Some(OscPacket::Message(info)) if info.addr == "/bundle_info"
+            && matches!(info.args.first(), Some(OscType::String(tag)) if tag == IMMEDIATE_TAG)
+    )
+}
+
+// Drops the time of timed packets, since immediate contents are executed right away
+pub fn untimed(packet: OscPacket) -> OscPacket {
+    match &packet {
+        OscPacket::Bundle(bundle) => match TaggedBundle::new(bundle) {
+            Ok(tagged) if tagged.bundle_tag == "timed_msg" => TimedOSCPacket::from_bundle(tagged)
+                .map(|timed| timed.packet)
+                .unwrap_or(packet),
+            _ => packet
+        },
+        OscPacket::Message(_) => packet
+    }
+}
@@ -1,4 +1,139 @@
-#![feature(result_flattening)]
+/*
+    Layers, each behind a cargo feature (all enabled by default):
+        - core: arg handling, tagged bundles and schemas; alloc only, builds as no_std
+        - model (std, bigdecimal): timed packets, codecs, text format and music helpers
+        - client: OscClient and in-process local endpoints
+        - stack: OSCStack, its receiver and runtime control
+    Lightweight consumers can depend on just the parsing layer (core), adding the std
+        feature on targets that have it:
 
-pub mod osc_stack;
+    jdw-osc-lib = { version = "...", default-features = false }
+    jdw-osc-lib = { version = "...", default-features = false, features = ["std"] }
+
+    The client and stack layers are socket and thread based and therefore left out of
+        wasm32-unknown-unknown builds, so that browser tools can still parse and build JDW
+        packets with the same code as the backend. The clock panics there as well, so model
+        code reading it is left out too (voices, ReorderBuffer::push, ping times); most of it
+        has a variant taking the current time as an argument (e.g. ReorderBuffer::push_at,
+        EchoReply::round_trip_at, deadline::is_expired). Check that the build still holds with:
+
+    cargo check --target wasm32-unknown-unknown --all-features
+
+    In the browser, the ws feature adds WsStack in place of OSCStack, receiving and sending
+        over a WebSocket, see ws_stack.rs.
+ */
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod core;
+pub mod prelude;
+
+#[cfg(feature = "model")]
 pub mod model;
+#[cfg(feature = "model")]
+#[deprecated(note = "Moved to jdw_osc_lib::core::schema")]
+pub mod schema;
+#[cfg(feature = "model")]
+pub mod sequence;
+#[cfg(feature = "model")]
+pub mod address;
+#[cfg(feature = "model")]
+pub mod supercollider;
+#[cfg(feature = "model")]
+pub mod notes;
+#[cfg(feature = "model")]
+pub mod nrt;
+#[cfg(feature = "model")]
+pub mod random;
+#[cfg(feature = "model")]
+pub mod roundtrip;
+#[cfg(feature = "model")]
+pub mod template;
+#[cfg(feature = "model")]
+pub mod text;
+#[cfg(feature = "model")]
+pub mod time_value;
+#[cfg(feature = "model")]
+pub mod codec;
+#[cfg(feature = "model")]
+pub mod compat;
+#[cfg(feature = "model")]
+pub mod deadline;
+#[cfg(feature = "model")]
+pub mod hexdump;
+#[cfg(feature = "model")]
+pub mod envelope;
+#[cfg(all(feature = "model", not(target_arch = "wasm32")))]
+pub mod voices;
+#[cfg(feature = "model")]
+pub mod music;
+#[cfg(feature = "model")]
+pub mod stubs;
+#[cfg(feature = "model")]
+pub mod memory;
+#[cfg(feature = "model")]
+pub mod reply;
+#[cfg(feature = "model")]
+pub mod progress;
+#[cfg(feature = "model")]
+pub mod cancel;
+#[cfg(feature = "model")]
+pub mod immediate;
+#[cfg(feature = "model")]
+pub mod session;
+#[cfg(feature = "model")]
+pub mod echo;
+#[cfg(feature = "model")]
+pub mod tracks;
+#[cfg(feature = "model")]
+pub mod queue_update;
+#[cfg(feature = "model")]
+pub mod hello;
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod client;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod local;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod loadgen;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod congestion;
+
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod osc_stack;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod receiver;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod stack_controller;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod metrics;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod config;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod routing;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod supervision;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod shared_state;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod repl;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod handler_context;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub mod peers;
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+mod workers;
+
+#[cfg(feature = "midi")]
+pub mod midi;
+
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(all(feature = "ws", target_arch = "wasm32"))]
+pub mod ws_stack;
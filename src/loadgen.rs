@@ -0,0 +1,320 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::client::OscClient;
+use crate::model::TaggedBundle;
+use crate::random::SeededRng;
+use crate::sequence;
+
+/*
+    Synthetic load for capacity planning a router or any other OSCStack service.
+    The generator sends a mix of messages and tagged bundles at a configurable rate, and a
+        LoadCounter on the receiving side tells how many of them made it:
+
+    let counter = LoadCounter::new();
+    let stack = OSCStack::init(url)
+        .on_message(LOAD_ADDR, &|msg| counter.observe_message(&msg))
+        .on_tbundle(LOAD_TAG, &|bundle| counter.observe_bundle(&bundle));
+    ...
+    let sent = LoadGenerator::new(url).rate(5000.0).burst(50).run()?;
+    println!("{} {}", sent, counter.report(sent.sent));
+
+    Every packet carries a sequence number: as the first arg of LOAD_ADDR messages, which
+        bundles also contain one of, and in the /bundle_info header of bundles so that the
+        stack's own sequence tracking (see sequence.rs) reports gaps as well.
+ */
+
+pub const LOAD_ADDR: &str = "/jdw/load";
+pub const LOAD_TAG: &str = "jdw_load";
+// Extra messages padding out bundles to the chosen size
+pub const LOAD_PADDING_ADDR: &str = "/jdw/load/padding";
+
+// Float args added to each packet (spread over its messages for bundles)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeDistribution {
+    Fixed(usize),
+    // Inclusive range, picked uniformly per packet
+    Uniform(usize, usize)
+}
+
+impl SizeDistribution {
+    fn pick(&self, rng: &mut SeededRng) -> usize {
+        match *self {
+            SizeDistribution::Fixed(size) => size,
+            SizeDistribution::Uniform(min, max) if max <= min => min,
+            SizeDistribution::Uniform(min, max) => min + (rng.next_u64() % (max - min + 1) as u64) as usize
+        }
+    }
+}
+
+// Floats per padding message in bundles
+const PADDING_CHUNK: usize = 16;
+
+pub struct LoadGenerator {
+    target: String,
+    // Packets per second, averaged over bursts
+    rate: f64,
+    duration: Duration,
+    // Share of packets sent as tagged bundles, 0.0 - 1.0
+    bundle_share: f64,
+    sizes: SizeDistribution,
+    // Packets sent back to back before pausing
+    burst: usize,
+    seed: u64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LoadReport {
+    pub sent: u64,
+    pub bundles: u64,
+    pub send_failures: u64,
+    pub elapsed: Duration
+}
+
+impl LoadReport {
+    // Packets per second actually handed to the socket
+    pub fn achieved_rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.sent as f64 / secs } else { 0.0 }
+    }
+}
+
+impl std::fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Sent {} packets ({} bundles, {} failed) in {:.2?}, {:.0} packets/s",
+               self.sent, self.bundles, self.send_failures, self.elapsed, self.achieved_rate())
+    }
+}
+
+impl LoadGenerator {
+    // Defaults to one second of 1000 small messages per second, without bursts
+    pub fn new(target_url: &str) -> LoadGenerator {
+        LoadGenerator {
+            target: target_url.to_string(),
+            rate: 1000.0,
+            duration: Duration::from_secs(1),
+            bundle_share: 0.0,
+            sizes: SizeDistribution::Fixed(4),
+            burst: 1,
+            seed: 0
+        }
+    }
+
+    pub fn rate(mut self, packets_per_second: f64) -> LoadGenerator {
+        self.rate = packets_per_second;
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> LoadGenerator {
+        self.duration = duration;
+        self
+    }
+
+    pub fn bundle_share(mut self, share: f64) -> LoadGenerator {
+        self.bundle_share = share.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn sizes(mut self, sizes: SizeDistribution) -> LoadGenerator {
+        self.sizes = sizes;
+        self
+    }
+
+    // Send packets in bursts of the given size, with longer pauses so the average rate holds
+    pub fn burst(mut self, packets: usize) -> LoadGenerator {
+        self.burst = packets.max(1);
+        self
+    }
+
+    // Equal seeds give equal packet mixes
+    pub fn seed(mut self, seed: u64) -> LoadGenerator {
+        self.seed = seed;
+        self
+    }
+
+    // Blocks for the configured duration, or as long as it takes to send when the rate can't be met
+    pub fn run(self) -> Result<LoadReport, String> {
+        if self.rate <= 0.0 || !self.rate.is_finite() {
+            return Err(format!("Load rate must be positive, got {}", self.rate));
+        }
+
+        let client = OscClient::new(&self.target)?;
+        let mut rng = SeededRng::new(self.seed);
+        let total = (self.rate * self.duration.as_secs_f64()).round() as u64;
+        let burst_interval = Duration::from_secs_f64(self.burst as f64 / self.rate);
+
+        let mut report = LoadReport::default();
+        let start = Instant::now();
+        let mut next_burst = start;
+
+        while report.sent < total {
+            if let Some(wait) = next_burst.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+            next_burst += burst_interval;
+
+            for _ in 0..(self.burst as u64).min(total - report.sent) {
+                let seq = report.sent as i32;
+                let size = self.sizes.pick(&mut rng);
+                let packet = if rng.next_f64() < self.bundle_share {
+                    report.bundles += 1;
+                    load_bundle(seq, size)
+                } else {
+                    OscPacket::Message(load_message(seq, size))
+                };
+
+                if client.send(&packet).is_err() {
+                    report.send_failures += 1;
+                }
+                report.sent += 1;
+            }
+        }
+
+        report.elapsed = start.elapsed();
+        Ok(report)
+    }
+}
+
+fn floats(count: usize) -> Vec<OscType> {
+    (0..count).map(|i| OscType::Float(i as f32)).collect()
+}
+
+fn load_message(seq: i32, size: usize) -> OscMessage {
+    let mut args = vec![OscType::Int(seq)];
+    args.extend(floats(size));
+    OscMessage { addr: LOAD_ADDR.to_string(), args }
+}
+
+fn load_bundle(seq: i32, size: usize) -> OscPacket {
+    let info = OscMessage { addr: "/bundle_info".to_string(), args: vec![OscType::String(LOAD_TAG.to_string())] };
+    let first = size.min(PADDING_CHUNK);
+
+    let mut content = vec![
+        OscPacket::Message(sequence::with_sequence(info, seq)),
+        OscPacket::Message(load_message(seq, first))
+    ];
+    let mut remaining = size - first;
+    while remaining > 0 {
+        let chunk = remaining.min(PADDING_CHUNK);
+        content.push(OscPacket::Message(OscMessage { addr: LOAD_PADDING_ADDR.to_string(), args: floats(chunk) }));
+        remaining -= chunk;
+    }
+
+    OscPacket::Bundle(OscBundle { timetag: OscTime { seconds: 0, fractional: 1 }, content })
+}
+
+// Receiving side tally of generated packets
+#[derive(Debug, Default)]
+pub struct LoadCounter {
+    state: Mutex<CounterState>
+}
+
+#[derive(Debug, Default)]
+struct CounterState {
+    seen: HashSet<i32>,
+    duplicates: u64,
+    reordered: u64,
+    highest: Option<i32>,
+    first_at: Option<Instant>,
+    last_at: Option<Instant>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LossReport {
+    pub sent: u64,
+    // Distinct packets received
+    pub received: u64,
+    pub lost: u64,
+    pub duplicates: u64,
+    // Packets arriving after one with a higher sequence number
+    pub reordered: u64,
+    // From the first to the last received packet
+    pub receive_span: Duration
+}
+
+impl LossReport {
+    // Share of sent packets that never arrived, 0.0 - 1.0
+    pub fn loss_ratio(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { self.lost as f64 / self.sent as f64 }
+    }
+
+    pub fn received_rate(&self) -> f64 {
+        let secs = self.receive_span.as_secs_f64();
+        if secs > 0.0 { self.received as f64 / secs } else { 0.0 }
+    }
+}
+
+impl std::fmt::Display for LossReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Received {} of {} packets ({:.2}% lost, {} duplicates, {} reordered), {:.0} packets/s",
+               self.received, self.sent, self.loss_ratio() * 100.0, self.duplicates, self.reordered, self.received_rate())
+    }
+}
+
+impl LoadCounter {
+    pub fn new() -> LoadCounter {
+        LoadCounter::default()
+    }
+
+    // Counts LOAD_ADDR messages, ignores anything else
+    pub fn observe_message(&self, msg: &OscMessage) {
+        if msg.addr != LOAD_ADDR {
+            return;
+        }
+        if let Some(OscType::Int(seq)) = msg.args.first() {
+            self.observe(*seq);
+        }
+    }
+
+    // Counts LOAD_TAG bundles by their LOAD_ADDR message, ignores anything else
+    pub fn observe_bundle(&self, bundle: &TaggedBundle) {
+        if bundle.bundle_tag != LOAD_TAG {
+            return;
+        }
+        if let Some(msg) = bundle.messages_with_addr(LOAD_ADDR).next() {
+            self.observe_message(msg);
+        }
+    }
+
+    // Compare against the amount of packets the generator reports as sent
+    pub fn report(&self, sent: u64) -> LossReport {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let received = state.seen.len() as u64;
+        LossReport {
+            sent,
+            received,
+            lost: sent.saturating_sub(received),
+            duplicates: state.duplicates,
+            reordered: state.reordered,
+            receive_span: match (state.first_at, state.last_at) {
+                (Some(first), Some(last)) => last.duration_since(first),
+                _ => Duration::ZERO
+            }
+        }
+    }
+
+    // Start over for the next run
+    pub fn reset(&self) {
+        *self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = CounterState::default();
+    }
+
+    fn observe(&self, seq: i32) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        state.first_at.get_or_insert(now);
+        state.last_at = Some(now);
+
+        if !state.seen.insert(seq) {
+            state.duplicates += 1;
+            return;
+        }
+        match state.highest {
+            Some(highest) if seq < highest => state.reordered += 1,
+            _ => state.highest = Some(seq)
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::mpsc::Sender;
+#[cfg(feature = "stack")]
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+#[cfg(feature = "stack")]
+use std::time::Duration;
+
+/*
+    In-process endpoints for single-process deployments and fast tests: a receiver (or
+        OSCStack) bound to "local:<name>" gets everything sent by clients targeting the same
+        url, without any sockets involved. Packets are still encoded and decoded with the
+        configured codecs, so handlers see exactly what they would over the network.
+
+    let stack = OSCStack::init("local:synth".to_string());
+    let client = OscClient::new("local:synth")?;
+ */
+
+pub const LOCAL_SCHEME: &str = "local:";
+
+// Sender address reported for packets from local clients, as they have no socket of their own
+pub const LOCAL_SENDER: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+
+// Endpoint name of a "local:<name>" url, None for network addresses
+pub fn local_name(url: &str) -> Option<&str> {
+    url.strip_prefix(LOCAL_SCHEME)
+}
+
+fn endpoints() -> &'static Mutex<HashMap<String, Sender<Vec<u8>>>> {
+    static ENDPOINTS: OnceLock<Mutex<HashMap<String, Sender<Vec<u8>>>>> = OnceLock::new();
+    ENDPOINTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Receiving end of a local url; the name is freed for binding again when this is dropped
+#[cfg(feature = "stack")]
+pub(crate) struct LocalEndpoint {
+    name: String,
+    receiver: Receiver<Vec<u8>>
+}
+
+#[cfg(feature = "stack")]
+impl LocalEndpoint {
+    pub(crate) fn bind(name: &str) -> Result<LocalEndpoint, String> {
+        let mut endpoints = endpoints().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if endpoints.contains_key(name) {
+            return Err(format!("Local endpoint {}{} is already bound", LOCAL_SCHEME, name));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        endpoints.insert(name.to_string(), sender);
+        Ok(LocalEndpoint { name: name.to_string(), receiver })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    // None blocks until something arrives
+    pub(crate) fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, RecvTimeoutError> {
+        match timeout {
+            Some(timeout) => self.receiver.recv_timeout(timeout),
+            None => self.receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        }
+    }
+}
+
+#[cfg(feature = "stack")]
+impl Drop for LocalEndpoint {
+    fn drop(&mut self) {
+        endpoints().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&self.name);
+    }
+}
+
+// Like sending to a closed UDP port, but reported since there is no network to blame
+pub(crate) fn send(name: &str, bytes: Vec<u8>) -> Result<(), String> {
+    let endpoints = endpoints().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    endpoints.get(name)
+        .and_then(|sender| sender.send(bytes).ok())
+        .ok_or(format!("Nothing is bound to {}{}", LOCAL_SCHEME, name))
+}
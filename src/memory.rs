@@ -0,0 +1,63 @@
+use std::mem::size_of;
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::model::TaggedBundle;
+
+/*
+    Rough in-memory footprint of packets, for budgeting what queues and buffers may hold
+        (see OSCStack::memory_budget). Counts the value itself plus its heap data, without
+        allocator overhead or unused capacity, so real usage is somewhat higher.
+ */
+pub trait ApproxSize {
+    fn approx_size(&self) -> usize;
+}
+
+impl ApproxSize for OscType {
+    fn approx_size(&self) -> usize {
+        size_of::<OscType>() + match self {
+            OscType::String(value) => value.len(),
+            OscType::Blob(value) => value.len(),
+            OscType::Array(array) => array.content.iter().map(ApproxSize::approx_size).sum(),
+            _ => 0
+        }
+    }
+}
+
+impl ApproxSize for OscMessage {
+    fn approx_size(&self) -> usize {
+        size_of::<OscMessage>() + self.addr.len() + self.args.iter().map(ApproxSize::approx_size).sum::<usize>()
+    }
+}
+
+impl ApproxSize for OscBundle {
+    fn approx_size(&self) -> usize {
+        size_of::<OscBundle>() + self.content.iter().map(ApproxSize::approx_size).sum::<usize>()
+    }
+}
+
+impl ApproxSize for OscPacket {
+    fn approx_size(&self) -> usize {
+        // The variant payload is already counted by the message or bundle
+        size_of::<OscPacket>() - size_of::<OscBundle>().min(size_of::<OscMessage>()) + match self {
+            OscPacket::Message(msg) => msg.approx_size(),
+            OscPacket::Bundle(bundle) => bundle.approx_size()
+        }
+    }
+}
+
+impl ApproxSize for TaggedBundle {
+    fn approx_size(&self) -> usize {
+        size_of::<TaggedBundle>() + self.bundle_tag.len() * 2 + self.info.extra.len() * size_of::<(String, OscType)>() + self.contents.iter().map(ApproxSize::approx_size).sum::<usize>()
+    }
+}
+
+// What to give up when queueing a packet would exceed the memory budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    // Drop the packet that no longer fits, keeping what is already queued
+    #[default]
+    DropNewest,
+    // Drop the oldest packets queued for the same handler until the new one fits
+    DropOldest
+}
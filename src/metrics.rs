@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/*
+    Counters kept by a running OSCStack, readable from any thread via the StackController.
+    Counters only ever increase; compare snapshots over time for rates.
+ */
+#[derive(Debug, Default)]
+pub struct StackMetrics {
+    datagrams_received: AtomicU64,
+    decode_failures: AtomicU64,
+    // Datagrams filling the entire receive buffer, meaning the OS likely cut them short
+    truncated_datagrams: AtomicU64,
+    // Datagrams with undecodable bytes left after the packet
+    trailing_byte_datagrams: AtomicU64,
+    // Packets dropped by the ignore lists in StackConfig
+    filtered_packets: AtomicU64,
+    // Packets dropped for exceeding StackConfig::max_packets_per_second
+    rate_limited_packets: AtomicU64,
+    // Bundles dropped because their deadline had passed
+    expired_packets: AtomicU64,
+    // Bundles missing according to sequence numbers, see sequence.rs
+    missing_packets: AtomicU64,
+    // Bundles arriving after a higher sequence number from the same source
+    reordered_packets: AtomicU64,
+    // Inline handler calls exceeding OSCStack::handler_time_limit
+    slow_handler_calls: AtomicU64,
+    // Packets dropped to stay within OSCStack::memory_budget
+    evicted_packets: AtomicU64,
+    // Packets dropped while paused with PausePolicy::Drop
+    paused_drops: AtomicU64,
+    // Per address or tag, only kept while a handler time limit is set
+    handler_timings: Mutex<HashMap<String, HandlerTiming>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetricsSnapshot {
+    pub datagrams_received: u64,
+    pub decode_failures: u64,
+    pub truncated_datagrams: u64,
+    pub trailing_byte_datagrams: u64,
+    pub filtered_packets: u64,
+    pub rate_limited_packets: u64,
+    pub expired_packets: u64,
+    pub missing_packets: u64,
+    pub reordered_packets: u64,
+    pub slow_handler_calls: u64,
+    pub evicted_packets: u64,
+    pub paused_drops: u64,
+}
+
+// Execution time of the inline handlers for an address or tag, see OSCStack::handler_time_limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HandlerTiming {
+    pub calls: u64,
+    pub slow_calls: u64,
+    pub total: Duration,
+    pub max: Duration
+}
+
+impl HandlerTiming {
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(0) => Duration::ZERO,
+            Ok(calls) => self.total / calls,
+            Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.calls as f64)
+        }
+    }
+}
+
+impl StackMetrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            datagrams_received: self.datagrams_received.load(Ordering::Relaxed),
+            decode_failures: self.decode_failures.load(Ordering::Relaxed),
+            truncated_datagrams: self.truncated_datagrams.load(Ordering::Relaxed),
+            trailing_byte_datagrams: self.trailing_byte_datagrams.load(Ordering::Relaxed),
+            filtered_packets: self.filtered_packets.load(Ordering::Relaxed),
+            rate_limited_packets: self.rate_limited_packets.load(Ordering::Relaxed),
+            expired_packets: self.expired_packets.load(Ordering::Relaxed),
+            missing_packets: self.missing_packets.load(Ordering::Relaxed),
+            reordered_packets: self.reordered_packets.load(Ordering::Relaxed),
+            slow_handler_calls: self.slow_handler_calls.load(Ordering::Relaxed),
+            evicted_packets: self.evicted_packets.load(Ordering::Relaxed),
+            paused_drops: self.paused_drops.load(Ordering::Relaxed),
+        }
+    }
+
+    // Sorted by address or tag
+    pub fn handler_timings(&self) -> Vec<(String, HandlerTiming)> {
+        let timings = self.handler_timings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut timings: Vec<(String, HandlerTiming)> = timings.iter().map(|(key, timing)| (key.clone(), *timing)).collect();
+        timings.sort_by(|a, b| a.0.cmp(&b.0));
+        timings
+    }
+
+    pub(crate) fn count_datagram(&self) {
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_decode_failure(&self) {
+        self.decode_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_truncated(&self) {
+        self.truncated_datagrams.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_trailing_bytes(&self) {
+        self.trailing_byte_datagrams.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_filtered(&self) {
+        self.filtered_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_rate_limited(&self) {
+        self.rate_limited_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_expired(&self) {
+        self.expired_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_missing(&self, amount: u64) {
+        self.missing_packets.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_reordered(&self) {
+        self.reordered_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_evicted(&self, amount: u64) {
+        self.evicted_packets.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_paused_drop(&self) {
+        self.paused_drops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_handler_time(&self, key: &str, elapsed: Duration, slow: bool) {
+        if slow {
+            self.slow_handler_calls.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut timings = self.handler_timings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let timing = match timings.get_mut(key) {
+            Some(timing) => timing,
+            None => timings.entry(key.to_string()).or_default()
+        };
+        timing.calls += 1;
+        timing.slow_calls += u64::from(slow);
+        timing.total += elapsed;
+        timing.max = timing.max.max(elapsed);
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::path::Path;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::model::TimedOSCPacket;
+use crate::music::midi_to_freq;
+use crate::notes::DURATION_KEY;
+
+/*
+    Standard MIDI File (format 0 and 1) to TimedOSCPacket conversion.
+
+    Every note (note on paired with its note off) becomes one timed message built from a
+        template, e.g. /note_on "synth" <freq> <amp>:
+
+    let template = MidiNoteTemplate::new("/note_on", vec![
+        TemplateArg::Literal(OscType::String("synth".to_string())),
+        TemplateArg::Frequency,
+        TemplateArg::Amplitude,
+    ]);
+    let packets = read_midi_file(path, &template, MidiTimeUnit::Seconds)?;
+ */
+
+// Values a template arg can be filled with for each note
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateArg {
+    Literal(OscType),
+    // Equal-tempered frequency in Hz (float)
+    Frequency,
+    // Midi note number (int)
+    Note,
+    // Velocity scaled to 0.0-1.0 (float)
+    Amplitude,
+    // Raw velocity 0-127 (int)
+    Velocity,
+    // Midi channel 0-15 (int)
+    Channel,
+    // Note length in the chosen time unit (float)
+    Duration
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiNoteTemplate {
+    pub address: String,
+    pub args: Vec<TemplateArg>
+}
+
+impl MidiNoteTemplate {
+    pub fn new(address: &str, args: Vec<TemplateArg>) -> MidiNoteTemplate {
+        MidiNoteTemplate { address: address.to_string(), args }
+    }
+
+    fn build(&self, note: &MidiNote, duration: &BigDecimal) -> OscMessage {
+        let args = self.args.iter().map(|arg| match arg {
+            TemplateArg::Literal(value) => value.clone(),
+            TemplateArg::Frequency => OscType::Float(midi_to_freq(note.note as f32)),
+            TemplateArg::Note => OscType::Int(note.note as i32),
+            TemplateArg::Amplitude => OscType::Float(note.velocity as f32 / 127.0),
+            TemplateArg::Velocity => OscType::Int(note.velocity as i32),
+            TemplateArg::Channel => OscType::Int(note.channel as i32),
+            TemplateArg::Duration => OscType::Float(duration.to_string().parse().unwrap_or(0.0))
+        }).collect();
+
+        OscMessage { addr: self.address.clone(), args }
+    }
+}
+
+// Beats are quarter notes, independent of tempo; seconds follow the file's tempo map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiTimeUnit {
+    Beats,
+    Seconds
+}
+
+struct MidiNote {
+    channel: u8,
+    note: u8,
+    velocity: u8,
+    start_tick: u64,
+    end_tick: u64
+}
+
+enum TrackEvent {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    // Microseconds per quarter note
+    Tempo(u32)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let slice = self.bytes.get(self.pos..self.pos + len)
+            .ok_or(format!("Unexpected end of midi data at byte {}", self.pos))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // Variable length quantity: 7 bits per byte, high bit set on all but the last
+    fn vlq(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(format!("Variable length value too long at byte {}", self.pos))
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+}
+
+fn read_track(data: &[u8]) -> Result<Vec<(u64, TrackEvent)>, String> {
+    let mut reader = Reader { bytes: data, pos: 0 };
+    let mut events = Vec::new();
+    let mut tick = 0u64;
+    let mut running_status: Option<u8> = None;
+
+    while !reader.done() {
+        tick += reader.vlq()? as u64;
+
+        let mut status = reader.u8()?;
+        if status < 0x80 {
+            // Running status: the byte just read is the first data byte
+            status = running_status.ok_or("Running status without previous status byte")?;
+            reader.pos -= 1;
+        }
+
+        match status {
+            0xFF => {
+                let meta_type = reader.u8()?;
+                let len = reader.vlq()? as usize;
+                let meta = reader.take(len)?;
+                match meta_type {
+                    0x51 if len == 3 => events.push((tick, TrackEvent::Tempo(
+                        u32::from_be_bytes([0, meta[0], meta[1], meta[2]])
+                    ))),
+                    0x2F => break,
+                    _ => {}
+                }
+            },
+            0xF0 | 0xF7 => {
+                let len = reader.vlq()? as usize;
+                reader.take(len)?;
+            },
+            _ => {
+                running_status = Some(status);
+                let channel = status & 0x0F;
+                match status & 0xF0 {
+                    0x80 => {
+                        let note = reader.u8()?;
+                        reader.u8()?;
+                        events.push((tick, TrackEvent::NoteOff { channel, note }));
+                    },
+                    0x90 => {
+                        let note = reader.u8()?;
+                        let velocity = reader.u8()?;
+                        // Note on with zero velocity is a note off by convention
+                        events.push((tick, match velocity {
+                            0 => TrackEvent::NoteOff { channel, note },
+                            _ => TrackEvent::NoteOn { channel, note, velocity }
+                        }));
+                    },
+                    0xA0 | 0xB0 | 0xE0 => { reader.take(2)?; },
+                    0xC0 | 0xD0 => { reader.take(1)?; },
+                    _ => return Err(format!("Unknown midi status byte {:#x}", status))
+                }
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+// Converts ticks to the chosen unit, following tempo changes for Seconds
+struct TickConverter {
+    ticks_per_beat: BigDecimal,
+    // (tick, tempo in microseconds per beat), sorted by tick
+    tempo_changes: Vec<(u64, u32)>,
+    unit: MidiTimeUnit
+}
+
+impl TickConverter {
+    fn convert(&self, tick: u64) -> BigDecimal {
+        if self.unit == MidiTimeUnit::Beats {
+            return (BigDecimal::from(tick) / &self.ticks_per_beat).normalized();
+        }
+
+        let mut seconds = BigDecimal::from(0);
+        let mut last_tick = 0u64;
+        // Default tempo is 120 bpm
+        let mut tempo = 500_000u32;
+        for (change_tick, change_tempo) in self.tempo_changes.iter().take_while(|(t, _)| *t < tick) {
+            seconds += self.ticks_to_seconds(change_tick - last_tick, tempo);
+            last_tick = *change_tick;
+            tempo = *change_tempo;
+        }
+        seconds += self.ticks_to_seconds(tick - last_tick, tempo);
+
+        seconds.round(9).normalized()
+    }
+
+    fn ticks_to_seconds(&self, ticks: u64, tempo: u32) -> BigDecimal {
+        BigDecimal::from(ticks) * BigDecimal::from(tempo) / (&self.ticks_per_beat * BigDecimal::from(1_000_000))
+    }
+}
+
+/*
+    Convert Standard MIDI File bytes to timed packets, ordered by start time.
+    Notes never released before the end of their track end at the last event of that track.
+ */
+pub fn read_midi(bytes: &[u8], template: &MidiNoteTemplate, unit: MidiTimeUnit) -> Result<Vec<TimedOSCPacket>, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != b"MThd" {
+        return Err("Not a midi file: missing MThd header".to_string());
+    }
+    let header_len = reader.u32()? as usize;
+    let mut header = Reader { bytes: reader.take(header_len)?, pos: 0 };
+    let _format = header.u16()?;
+    let _track_count = header.u16()?;
+    let division = header.u16()?;
+    if division & 0x8000 != 0 {
+        return Err("SMPTE time division is not supported".to_string());
+    }
+
+    let mut notes: Vec<MidiNote> = Vec::new();
+    let mut tempo_changes: Vec<(u64, u32)> = Vec::new();
+
+    while !reader.done() {
+        let chunk_type = reader.take(4)?;
+        let chunk_len = reader.u32()? as usize;
+        let chunk = reader.take(chunk_len)?;
+        if chunk_type != b"MTrk" {
+            continue;
+        }
+
+        let events = read_track(chunk)?;
+        let track_end = events.last().map(|(tick, _)| *tick).unwrap_or(0);
+        let mut held: Vec<MidiNote> = Vec::new();
+
+        for (tick, event) in events {
+            match event {
+                TrackEvent::Tempo(tempo) => tempo_changes.push((tick, tempo)),
+                TrackEvent::NoteOn { channel, note, velocity } => held.push(MidiNote {
+                    channel, note, velocity, start_tick: tick, end_tick: tick
+                }),
+                TrackEvent::NoteOff { channel, note } => {
+                    if let Some(index) = held.iter().position(|n| n.channel == channel && n.note == note) {
+                        let mut finished = held.remove(index);
+                        finished.end_tick = tick;
+                        notes.push(finished);
+                    }
+                }
+            }
+        }
+
+        for mut unfinished in held {
+            unfinished.end_tick = track_end;
+            notes.push(unfinished);
+        }
+    }
+
+    tempo_changes.sort_by_key(|(tick, _)| *tick);
+    notes.sort_by_key(|note| note.start_tick);
+
+    let converter = TickConverter {
+        ticks_per_beat: BigDecimal::from(division),
+        tempo_changes,
+        unit
+    };
+
+    Ok(notes.iter().map(|note| {
+        let time = converter.convert(note.start_tick);
+        let duration = converter.convert(note.end_tick) - &time;
+        let packet = TimedOSCPacket::new(time, OscPacket::Message(template.build(note, &duration)));
+        // Lets notes::with_note_offs complete the sequence
+        match duration.to_f32() {
+            Some(length) => packet.with_metadata(DURATION_KEY, length),
+            None => packet
+        }
+    }).collect())
+}
+
+pub fn read_midi_file(path: &Path, template: &MidiNoteTemplate, unit: MidiTimeUnit) -> Result<Vec<TimedOSCPacket>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read midi file {}: {}", path.display(), e))?;
+    read_midi(&bytes, template, unit)
+}
@@ -1,218 +1,207 @@
 use bigdecimal::BigDecimal;
-use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
 
 /*
     OSC structs for careful parsing and management of expected message and bundle types.
  */
 
-use std::str::FromStr;
-use std::option::Option;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
+use crate::random::SeededRng;
+use crate::time_value::{TimeEncoding, TimePolicy, TimeValue};
 
-/*
-    Adding some convenience functions for OscMessage args
- */
-
-// Verify that custom args follow the String,float,String,float... pattern
-// Note: This could possibly be a bit expensive time-wise!
-fn validate_args(args: &Vec<OscType>) -> Result<(), String> {
-
-    let mut next_is_string = true;
-
-    for arg in args {
-        match arg {
-            OscType::Float(_) => {
-                if next_is_string {
-                    return Err("Malformed message: Custom arg float where string expected".to_string());
-                }
+// Arg handling and the tagged bundle model live in core, so that they also build without std
+pub use crate::core::args::*;
+pub use crate::core::tagged::*;
 
-                next_is_string = true;
-            },
-            OscType::String(_) => {
-                if !next_is_string {
-                    return Err("Malformed message: Custom arg string where float expected".to_string());
-                }
-
-                next_is_string = false;
-            },
-            _ => {
-                return Err("Malformed message: Custom arg in message not of type string or float".to_string());
-            }
-        }
-    }
+// Reserved timed_msg info arg for ordering simultaneous packets
+pub const ORDER_KEY: &str = "order";
 
-    Ok(())
-}
-
-pub trait OscArgHandler {
-    fn expect_addr(&self, addr_name: &str) -> Result<(), String>;
-    fn expect_args(&self, amount: usize) -> Result<String, String>;
-    fn get_string_at(&self, index: usize, name: &str, ) -> Result<String, String>;
-    fn get_float_at(&self, index: usize, name: &str, ) -> Result<f32, String>;
-    fn get_int_at(&self, index: usize, name: &str, ) -> Result<i32, String>;
-    fn get_u64_at(&self, index: usize, name: &str) -> Result<u64, String>;
-    fn get_bigdecimal_at(&self, index: usize, name: &str) -> Result<BigDecimal, String>;
-    fn get_varargs(&self, start_index: usize) -> Result<Vec<OscType>, String>;
+/*
+    Timed osc packets are packets with a relative float time tag.
+    Used for all kinds of arbitrary ordering, such as relative execution time in a sequence.
+    [/bundle_info, "timed_msg"]
+    [/timed_msg_info, 0.0, (probability), (name, value)...]
+    [... packet ...]
+    The time is a decimal string, a float or a double, see TimeEncoding.
+    The optional probability (float, 0.0 - 1.0) marks the packet as generative: consumers
+        play it only if a random roll succeeds, see filter_by_probability.
+    The optional named float args are metadata such as "channel" 2.0 or "voice" 1.0, for
+        routing and voice allocation without inspecting the wrapped packet.
+    The reserved "order" arg sets the order among packets sharing a time (lower first);
+        packets with equal time and order keep their original sequence, see sort_timed.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedOSCPacket {
+    pub time: BigDecimal,
+    pub packet: OscPacket,
+    pub probability: Option<f32>,
+    pub metadata: BTreeMap<String, f32>,
+    pub order: i32,
 }
 
-impl OscArgHandler for OscMessage {
-
-    fn expect_addr(&self, addr_name: &str) -> Result<(), String> {
-        if &self.addr.to_string() != addr_name {
-            return Err(format!("Attempted to format {} as the wrong kind of message - this likely a human error in the source code", addr_name));
-        }
+impl TimedOSCPacket {
 
-        Ok(())
+    pub fn new(time: BigDecimal, packet: OscPacket) -> TimedOSCPacket {
+        TimedOSCPacket { time, packet, probability: None, metadata: BTreeMap::new(), order: 0 }
     }
 
-    fn expect_args(&self, amount: usize) -> Result<String, String> {
-
-        if self.args.len() < amount {
-            return Err(format!("Message did not contain the {} first required args.", amount));
-        }
-
-        Ok("Ok".to_string())
+    pub fn with_order(mut self, order: i32) -> TimedOSCPacket {
+        self.order = order;
+        self
     }
 
-    fn get_string_at(&self, index: usize, name: &str, ) -> Result<String, String> {
-        let err_msg = format!("{} string not found as {}th arg", name, index);
-        self.args
-            .get(index)
-            .map_or(None, |some| some.clone().string())
-            .map_or(Err(err_msg), |s| Ok(s))
+    // Execution order: by time, then by order
+    pub fn schedule_cmp(&self, other: &TimedOSCPacket) -> Ordering {
+        self.time.cmp(&other.time).then(self.order.cmp(&other.order))
     }
 
-    fn get_float_at(&self, index: usize, name: &str, ) -> Result<f32, String> {
-        let err_msg = format!("{} float not found as {}th arg", name, index);
-        self.args
-            .get(index)
-            .map_or(None, |some| some.clone().float())
-            .map_or(Err(err_msg), |s| Ok(s))
+    pub fn with_metadata(mut self, name: &str, value: f32) -> TimedOSCPacket {
+        self.metadata.insert(name.to_string(), value);
+        self
     }
 
-    fn get_int_at(&self, index: usize, name: &str, ) -> Result<i32, String> {
-        let err_msg = format!("{} float not found as {}th arg", name, index);
-        self.args
-            .get(index)
-            .map_or(None, |some| some.clone().int())
-            .map_or(Err(err_msg), |s| Ok(s))
+    pub fn metadata_value(&self, name: &str) -> Option<f32> {
+        self.metadata.get(name).copied()
     }
 
-    fn get_u64_at(&self, index: usize, name: &str) -> Result<u64, String> {
-        u64::try_from(self.get_int_at(index, name)?).map_err(|result| result.to_string())
+    pub fn with_probability(mut self, probability: f32) -> TimedOSCPacket {
+        self.probability = Some(probability);
+        self
     }
 
-    fn get_bigdecimal_at(&self, index: usize, name: &str) -> Result<BigDecimal, String> {
-        BigDecimal::from_str(&self.get_string_at(index, name)?).map_err(|result| result.to_string())
+    // Roll for whether the packet should play; always true without a probability
+    pub fn roll(&self, rng: &mut SeededRng) -> bool {
+        self.probability.is_none_or(|probability| rng.chance(probability))
     }
 
-    fn get_varargs(&self, start_index: usize) -> Result<Vec<OscType>, String> {
-        let named_args = if self.args.len() > start_index {(&self.args[start_index..].to_vec()).clone()} else {vec![]};
-        validate_args(&named_args)?;
-        return Ok(named_args);
+    pub fn to_bundle(&self) -> OscBundle {
+        self.bundle_with_time(TimeValue::new(&self.time, &TimePolicy::default()).to_osc_arg())
     }
-}
 
+    // As to_bundle, but fails if the time is not exactly representable in the given encoding
+    pub fn to_bundle_with(&self, encoding: TimeEncoding) -> Result<OscBundle, String> {
+        let time = TimeValue::new(&self.time, &TimePolicy::default()).to_osc_arg_encoded(encoding)?;
+        Ok(self.bundle_with_time(time))
+    }
 
-/*
-    In order to properly utilize bundles I have created a standard where the first
-        packet in every JDW-compatible bundle is an OSC message with a bundle type
-        string contained within, e.g.: ["/bundle_tag", "nrt_record_request"]
- */
-#[derive(Debug)]
-pub struct TaggedBundle {
-    pub bundle_tag: String,
-    pub contents: Vec<OscPacket>
-}
+    fn bundle_with_time(&self, time: OscType) -> OscBundle {
+        let mut info_args = vec![time];
+        if let Some(probability) = self.probability {
+            info_args.push(OscType::Float(probability));
+        }
+        for (name, value) in &self.metadata {
+            info_args.push(OscType::String(name.clone()));
+            info_args.push(OscType::Float(*value));
+        }
+        if self.order != 0 {
+            info_args.push(OscType::String(ORDER_KEY.to_string()));
+            info_args.push(OscType::Float(self.order as f32));
+        }
 
-impl TaggedBundle {
-    pub fn new(bundle: &OscBundle) -> Result<TaggedBundle, String> {
-        let first_msg = match bundle.content.get(0).ok_or("Empty bundle")?.clone() {
-            OscPacket::Message(msg) => { Option::Some(msg) }
-            OscPacket::Bundle(_) => {Option::None}
-        }.ok_or("First element in bundle not an info message!")?;
+        OscBundle {
+            timetag: OscTime { seconds: 0, fractional: 1 },
+            content: vec![
+                OscPacket::Message(OscMessage { addr: "/bundle_info".to_string(), args: vec![OscType::String("timed_msg".to_string())] }),
+                OscPacket::Message(OscMessage { addr: "/timed_msg_info".to_string(), args: info_args }),
+                self.packet.clone()
+            ]
+        }
+    }
 
-        if first_msg.addr != "/bundle_info" {
-            return Err(format!("Expected /bundle_info as first message in bundle, got: {}", &first_msg.addr));
+    pub fn from_bundle(bundle: TaggedBundle) -> Result<TimedOSCPacket, String>{
+        if &bundle.bundle_tag != "timed_msg" {
+            return Err(format!("Attempted to parse {} as timed_msg bundle", &bundle.bundle_tag));
         }
 
-        let bundle_tag = first_msg.args.get(0)
-            .ok_or("bundle info empty")?
-            .clone()
-            .string().ok_or("bundle info should be a string")?;
+        let info_msg = bundle.get_message(0)?;
+        let packet = bundle.get_packet(1)?;
+
+        info_msg.expect_addr("/timed_msg_info")?;
+        let time_arg = info_msg.args.first().ok_or("time not found as 0th arg")?;
+        let time = TimeValue::from_osc_arg(time_arg, &TimePolicy::default())?.into();
+        let probability = match info_msg.args.get(1) {
+            Some(OscType::Float(probability)) => Some(*probability),
+            _ => None
+        };
 
-        let contents = if bundle.content.len() > 1 {(&bundle.content[1..].to_vec()).clone()} else {vec![]};
+        let metadata_start = if probability.is_some() { 2 } else { 1 };
+        let mut metadata = metadata_from_args(info_msg.args.get(metadata_start..).unwrap_or_default());
 
-        Ok(TaggedBundle {
-            bundle_tag,
-            contents
+        let order = metadata.remove(ORDER_KEY).map(|order| order as i32).unwrap_or(0);
+
+        Ok(TimedOSCPacket {
+            time,
+            packet,
+            probability,
+            metadata,
+            order
         })
-    }
 
-    pub fn get_packet(&self, content_index: usize) -> Result<OscPacket, String> {
-        self.contents.get(content_index)
-            .map(|pct| pct.clone())
-            .ok_or("Failed to fetch packet".to_string())
     }
+}
 
-    pub fn get_message(&self, content_index: usize) -> Result<OscMessage, String> {
-        self.contents.get(content_index)
-            .map(|pct| pct.clone())
-            .ok_or(format!("Could not get packet on index {} for bundle {:?}", content_index, &self))
-            .map(|pct| match pct {
-                OscPacket::Message(msg) => {
-                    Ok(msg)
-                }
-                _ => {Err("Not a message".to_string())}
-            })
-            .flatten()
-    }
-
-    pub fn get_bundle(&self, content_index: usize) -> Result<OscBundle, String> {
-        self.contents.get(content_index)
-            .map(|pct| pct.clone())
-            .ok_or(format!("Could not get packet on index {} for bundle {:?}", content_index, &self))
-            .map(|pct| match pct {
-                OscPacket::Bundle(msg) => {
-                    Ok(msg)
-                }
-                _ => {Err("Not a bundle".to_string())}
-            })
-            .flatten()
+impl FromTaggedBundle for TimedOSCPacket {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        TimedOSCPacket::from_bundle(bundle)
     }
 }
 
 /*
-    Timed osc packets are packets with a relative float time tag.
-    Used for all kinds of arbitrary ordering, such as relative execution time in a sequence.
-    [/bundle_info, "timed_msg"]
-    [/timed_msg_info, 0.0]
-    [... packet ...]
+    Name, float pairs of timed_msg metadata. Lenient, as senders may add info args of their
+        own: an arg that is not a name, or a name not followed by a float, is skipped on its
+        own and reading continues with the next arg.
  */
-#[derive(Debug, Clone)]
-pub struct TimedOSCPacket {
-    pub time: BigDecimal,
-    pub packet: OscPacket,
-}
-
-impl TimedOSCPacket {
-
-    pub fn from_bundle(bundle: TaggedBundle) -> Result<TimedOSCPacket, String>{
-        if &bundle.bundle_tag != "timed_msg" {
-            return Err(format!("Attempted to parse {} as timed_msg bundle", &bundle.bundle_tag));
+fn metadata_from_args(args: &[OscType]) -> BTreeMap<String, f32> {
+    let mut metadata = BTreeMap::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let OscType::String(name) = arg else { continue };
+        if let Some(OscType::Float(value)) = args.peek() {
+            metadata.insert(name.clone(), *value);
+            args.next();
         }
+    }
+    metadata
+}
 
-        let info_msg = bundle.get_message(0)?;
-        let packet = bundle.get_packet(1)?;
+// Stable sort into execution order, see TimedOSCPacket::schedule_cmp
+pub fn sort_timed(packets: &mut [TimedOSCPacket]) {
+    packets.sort_by(|a, b| a.schedule_cmp(b));
+}
 
-        info_msg.expect_addr("/timed_msg_info")?;
-        let time_str = info_msg.get_string_at(0, "time")?;
-        let time = BigDecimal::from_str(&time_str).unwrap(); 
+// Keep the packets whose probability roll succeeds; use equal seeds for equal outcomes
+pub fn filter_by_probability(packets: Vec<TimedOSCPacket>, rng: &mut SeededRng) -> Vec<TimedOSCPacket> {
+    packets.into_iter().filter(|packet| packet.roll(rng)).collect()
+}
 
-        Ok(TimedOSCPacket {
-            time,
-            packet
-        })
+/*
+    Rewrites the times of all timed_msg bundles in the packet, nested ones included, in the
+        given encoding; times it can't represent exactly stay decimal strings. Used by
+        OscClient::with_time_encoding, so that one sender can switch encodings without
+        changing its builders.
+ */
+pub fn encode_times(packet: &OscPacket, encoding: TimeEncoding) -> OscPacket {
+    let OscPacket::Bundle(bundle) = packet else { return packet.clone() };
+    let timed = matches!(
+        bundle.content.first(),
+        Some(OscPacket::Message(info)) if info.addr == "/bundle_info"
+            && matches!(info.args.first(), Some(OscType::String(tag)) if tag == "timed_msg")
+    );
+
+    let content = bundle.content.iter().enumerate().map(|(index, packet)| match packet {
+        OscPacket::Message(info) if timed && index == 1 && info.addr == "/timed_msg_info" => {
+            let mut info = info.clone();
+            if let Some(time) = info.args.first_mut() {
+                if let Ok(value) = TimeValue::from_osc_arg(time, &TimePolicy::default()) {
+                    *time = value.to_osc_arg_preferring(encoding);
+                }
+            }
+            OscPacket::Message(info)
+        },
+        _ => encode_times(packet, encoding)
+    }).collect();
 
-    }
+    OscPacket::Bundle(OscBundle { timetag: bundle.timetag, content })
 }
@@ -0,0 +1,298 @@
+use rosc::{OscArray, OscBundle, OscColor, OscMessage, OscMidiMessage, OscPacket, OscTime, OscType};
+
+use crate::codec::PacketCodec;
+
+/*
+    MessagePack encoding of OSC packets, for internal JDW links where bandwidth matters
+        more than compatibility with other OSC software. Both ends must use this codec.
+
+    Message: [0, addr, [args...]]
+    Bundle:  [1, <time>, [packets...]]
+
+    Arg types are kept exact (i32 stays i32, f32 stays f32) so that decoding yields the
+        same OscPacket that was encoded. OSC types without a MessagePack counterpart are
+        stored as ext values, see the EXT_ constants.
+ */
+
+const KIND_MESSAGE: u8 = 0;
+const KIND_BUNDLE: u8 = 1;
+
+const EXT_TIME: i8 = 1;
+const EXT_CHAR: i8 = 2;
+const EXT_COLOR: i8 = 3;
+const EXT_MIDI: i8 = 4;
+const EXT_INF: i8 = 5;
+
+// Deepest nesting of arrays and bundles read, so that hostile input can't overflow the stack
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+impl PacketCodec for MsgPackCodec {
+    fn encode(&self, packet: &OscPacket) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        write_packet(&mut out, packet)?;
+        Ok(out)
+    }
+
+    fn decode<'b>(&self, bytes: &'b [u8]) -> Result<(&'b [u8], OscPacket), String> {
+        let mut reader = Reader { bytes, pos: 0, depth: 0 };
+        let packet = reader.packet()?;
+        Ok((&bytes[reader.pos..], packet))
+    }
+}
+
+fn write_packet(out: &mut Vec<u8>, packet: &OscPacket) -> Result<(), String> {
+    write_array_len(out, 3)?;
+    match packet {
+        OscPacket::Message(msg) => {
+            out.push(KIND_MESSAGE);
+            write_str(out, &msg.addr)?;
+            write_array_len(out, msg.args.len())?;
+            for arg in &msg.args {
+                write_arg(out, arg)?;
+            }
+        },
+        OscPacket::Bundle(bundle) => {
+            out.push(KIND_BUNDLE);
+            write_time(out, bundle.timetag);
+            write_array_len(out, bundle.content.len())?;
+            for packet in &bundle.content {
+                write_packet(out, packet)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_arg(out: &mut Vec<u8>, arg: &OscType) -> Result<(), String> {
+    match arg {
+        OscType::Int(i) => {
+            out.push(0xd2);
+            out.extend_from_slice(&i.to_be_bytes());
+        },
+        OscType::Long(l) => {
+            out.push(0xd3);
+            out.extend_from_slice(&l.to_be_bytes());
+        },
+        OscType::Float(f) => {
+            out.push(0xca);
+            out.extend_from_slice(&f.to_be_bytes());
+        },
+        OscType::Double(d) => {
+            out.push(0xcb);
+            out.extend_from_slice(&d.to_be_bytes());
+        },
+        OscType::String(s) => write_str(out, s)?,
+        OscType::Blob(bytes) => {
+            write_len(out, bytes.len(), [0xc4, 0xc5, 0xc6])?;
+            out.extend_from_slice(bytes);
+        },
+        OscType::Bool(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        OscType::Nil => out.push(0xc0),
+        OscType::Time(time) => write_time(out, *time),
+        OscType::Char(c) => write_fixext4(out, EXT_CHAR, (*c as u32).to_be_bytes()),
+        OscType::Color(c) => write_fixext4(out, EXT_COLOR, [c.red, c.green, c.blue, c.alpha]),
+        OscType::Midi(m) => write_fixext4(out, EXT_MIDI, [m.port, m.status, m.data1, m.data2]),
+        OscType::Inf => out.extend_from_slice(&[0xc7, 0, EXT_INF as u8]),
+        OscType::Array(array) => {
+            write_array_len(out, array.content.len())?;
+            for arg in &array.content {
+                write_arg(out, arg)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_time(out: &mut Vec<u8>, time: OscTime) {
+    out.extend_from_slice(&[0xd7, EXT_TIME as u8]);
+    out.extend_from_slice(&time.seconds.to_be_bytes());
+    out.extend_from_slice(&time.fractional.to_be_bytes());
+}
+
+fn write_fixext4(out: &mut Vec<u8>, ext: i8, data: [u8; 4]) {
+    out.extend_from_slice(&[0xd6, ext as u8]);
+    out.extend_from_slice(&data);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) -> Result<(), String> {
+    if s.len() < 32 {
+        out.push(0xa0 | s.len() as u8);
+    } else {
+        write_len(out, s.len(), [0xd9, 0xda, 0xdb])?;
+    }
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn write_array_len(out: &mut Vec<u8>, len: usize) -> Result<(), String> {
+    if len < 16 {
+        out.push(0x90 | len as u8);
+        return Ok(());
+    }
+
+    // Arrays have no 8 bit length variant
+    if let Ok(len) = u16::try_from(len) {
+        out.push(0xdc);
+        out.extend_from_slice(&len.to_be_bytes());
+        return Ok(());
+    }
+
+    let len = u32::try_from(len).map_err(|_| "Array too long for MessagePack".to_string())?;
+    out.push(0xdd);
+    out.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+// Writes the smallest of the 8/16/32 bit length markers that fits
+fn write_len(out: &mut Vec<u8>, len: usize, markers: [u8; 3]) -> Result<(), String> {
+    if let Ok(len) = u8::try_from(len) {
+        out.extend_from_slice(&[markers[0], len]);
+    } else if let Ok(len) = u16::try_from(len) {
+        out.push(markers[1]);
+        out.extend_from_slice(&len.to_be_bytes());
+    } else {
+        let len = u32::try_from(len).map_err(|_| "Value too long for MessagePack".to_string())?;
+        out.push(markers[2]);
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+    Ok(())
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+    // Arrays and bundles currently being read
+    depth: usize
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, n: usize) -> Result<&'b [u8], String> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.bytes.len())
+            .ok_or(format!("Unexpected end of data at byte {}", self.pos))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn len_of(&mut self, size: usize) -> Result<usize, String> {
+        Ok(match size {
+            1 => self.byte()? as usize,
+            2 => u16::from_be_bytes(self.array()?) as usize,
+            _ => u32::from_be_bytes(self.array()?) as usize
+        })
+    }
+
+    fn array_len(&mut self) -> Result<usize, String> {
+        match self.byte()? {
+            marker @ 0x90..=0x9f => Ok((marker & 0x0f) as usize),
+            0xdc => self.len_of(2),
+            0xdd => self.len_of(4),
+            marker => Err(format!("Expected array, got marker {:#04x} at byte {}", marker, self.pos - 1))
+        }
+    }
+
+    fn string_of(&mut self, len: usize) -> Result<String, String> {
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn nested<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("Nested deeper than {} levels at byte {}", MAX_DEPTH, self.pos));
+        }
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn packet(&mut self) -> Result<OscPacket, String> {
+        if self.array_len()? != 3 {
+            return Err("Packet should be a 3 element array".to_string());
+        }
+
+        match self.byte()? {
+            KIND_MESSAGE => {
+                let addr = match self.arg()? {
+                    OscType::String(addr) => addr,
+                    other => return Err(format!("Expected message address, got {:?}", other))
+                };
+                let len = self.array_len()?;
+                let args = (0..len).map(|_| self.arg()).collect::<Result<Vec<_>, _>>()?;
+                Ok(OscPacket::Message(OscMessage { addr, args }))
+            },
+            KIND_BUNDLE => {
+                let timetag = match self.arg()? {
+                    OscType::Time(time) => time,
+                    other => return Err(format!("Expected bundle time, got {:?}", other))
+                };
+                let len = self.array_len()?;
+                let content = self.nested(|reader| (0..len).map(|_| reader.packet()).collect::<Result<Vec<_>, _>>())?;
+                Ok(OscPacket::Bundle(OscBundle { timetag, content }))
+            },
+            kind => Err(format!("Unknown packet kind {}", kind))
+        }
+    }
+
+    fn arg(&mut self) -> Result<OscType, String> {
+        let marker = self.byte()?;
+        Ok(match marker {
+            0xa0..=0xbf => OscType::String(self.string_of((marker & 0x1f) as usize)?),
+            0xd9..=0xdb => {
+                let len = self.len_of(1 << (marker - 0xd9))?;
+                OscType::String(self.string_of(len)?)
+            },
+            0xc4..=0xc6 => {
+                let len = self.len_of(1 << (marker - 0xc4))?;
+                OscType::Blob(self.take(len)?.to_vec())
+            },
+            0x90..=0x9f | 0xdc | 0xdd => {
+                self.pos -= 1;
+                let len = self.array_len()?;
+                let content = self.nested(|reader| (0..len).map(|_| reader.arg()).collect::<Result<Vec<_>, _>>())?;
+                OscType::Array(OscArray { content })
+            },
+            0xd2 => OscType::Int(i32::from_be_bytes(self.array()?)),
+            0xd3 => OscType::Long(i64::from_be_bytes(self.array()?)),
+            0xca => OscType::Float(f32::from_be_bytes(self.array()?)),
+            0xcb => OscType::Double(f64::from_be_bytes(self.array()?)),
+            0xc0 => OscType::Nil,
+            0xc2 => OscType::Bool(false),
+            0xc3 => OscType::Bool(true),
+            0xd6 => {
+                let ext = self.byte()? as i8;
+                let data: [u8; 4] = self.array()?;
+                match ext {
+                    EXT_CHAR => OscType::Char(char::from_u32(u32::from_be_bytes(data)).ok_or("Invalid char")?),
+                    EXT_COLOR => OscType::Color(OscColor { red: data[0], green: data[1], blue: data[2], alpha: data[3] }),
+                    EXT_MIDI => OscType::Midi(OscMidiMessage { port: data[0], status: data[1], data1: data[2], data2: data[3] }),
+                    _ => return Err(format!("Unknown 4 byte ext type {}", ext))
+                }
+            },
+            0xd7 => match self.byte()? as i8 {
+                EXT_TIME => OscType::Time(OscTime {
+                    seconds: u32::from_be_bytes(self.array()?),
+                    fractional: u32::from_be_bytes(self.array()?)
+                }),
+                ext => return Err(format!("Unknown 8 byte ext type {}", ext))
+            },
+            0xc7 => match (self.byte()?, self.byte()? as i8) {
+                (0, EXT_INF) => OscType::Inf,
+                (_, ext) => return Err(format!("Unknown ext type {}", ext))
+            },
+            _ => return Err(format!("Unsupported marker {:#04x} at byte {}", marker, self.pos - 1))
+        })
+    }
+}
@@ -0,0 +1,225 @@
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::core::args::named_arg_index;
+use crate::model::TimedOSCPacket;
+
+/*
+    Music domain conversions shared by everything handling /note_on and similar messages.
+    Tuning is equal-tempered with A4 (midi note 69) at 440 Hz. Note names use the
+        "c4 is middle C (60)" convention, with sharps as "#" and flats as "b": "c#4", "eb3".
+ */
+
+pub const A4_FREQUENCY: f32 = 440.0;
+pub const A4_MIDI_NOTE: f32 = 69.0;
+
+const NOTE_NAMES: [&str; 12] = ["c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b"];
+
+// Fractional notes give frequencies between semitones
+pub fn midi_to_freq(note: f32) -> f32 {
+    A4_FREQUENCY * 2f32.powf((note - A4_MIDI_NOTE) / 12.0)
+}
+
+// Inverse of midi_to_freq; not rounded, so detuned frequencies give fractional notes
+pub fn freq_to_midi(freq: f32) -> Result<f32, String> {
+    if !(freq > 0.0 && freq.is_finite()) {
+        return Err(format!("Frequency should be positive, got {}", freq));
+    }
+    Ok(A4_MIDI_NOTE + 12.0 * (freq / A4_FREQUENCY).log2())
+}
+
+// Parse a name like "c4", "F#2", "bb-1" into its midi note number
+pub fn note_name_to_midi(name: &str) -> Result<u8, String> {
+    let lower = name.trim().to_lowercase();
+    let mut chars = lower.chars();
+
+    let base = match chars.next() {
+        Some('c') => 0, Some('d') => 2, Some('e') => 4, Some('f') => 5,
+        Some('g') => 7, Some('a') => 9, Some('b') => 11,
+        _ => return Err(format!("Invalid note name: {}", name))
+    };
+
+    let rest = chars.as_str();
+    let (accidental, octave) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        _ => (0, rest)
+    };
+
+    let octave: i32 = octave.parse().map_err(|_| format!("Invalid octave in note name: {}", name))?;
+    let note = (octave + 1) * 12 + base + accidental;
+    u8::try_from(note).ok().filter(|note| *note <= 127)
+        .ok_or(format!("Note {} is outside the midi range", name))
+}
+
+// Lowercase name using sharps, e.g. 61 -> "c#4"
+pub fn midi_to_note_name(note: u8) -> String {
+    let octave = note as i32 / 12 - 1;
+    format!("{}{}", NOTE_NAMES[note as usize % 12], octave)
+}
+
+// Linear amplitude to decibels relative to 1.0; 0.0 gives negative infinity
+pub fn amp_to_db(amp: f32) -> f32 {
+    20.0 * amp.abs().log10()
+}
+
+pub fn db_to_amp(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/*
+    Pitch transforms over note sequences. Which arg of a message holds its pitch differs per
+        message type, so a selector tells the transforms where to look:
+
+    let in_key = quantize(notes, &Scale::minor(9), pitch_at(1, PitchUnit::Frequency))?;
+    let moved = transpose_in_scale(notes, &Scale::major(0), 2, named_pitch("note", PitchUnit::Midi))?;
+
+    Messages for which the selector returns None, and bundles, are passed through untouched.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PitchUnit {
+    // Midi note number, either int or float arg
+    Midi,
+    // Hz as float arg
+    Frequency
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PitchArg {
+    pub index: usize,
+    pub unit: PitchUnit
+}
+
+// Pitch is always the arg at index
+pub fn pitch_at(index: usize, unit: PitchUnit) -> impl Fn(&OscMessage) -> Option<PitchArg> {
+    move |_| Some(PitchArg { index, unit })
+}
+
+// Pitch is the value of a named arg pair, e.g. ["freq", 440.0]; messages without it are skipped
+pub fn named_pitch(name: &str, unit: PitchUnit) -> impl Fn(&OscMessage) -> Option<PitchArg> + '_ {
+    move |msg| named_arg_index(&msg.args, name).map(|index| PitchArg { index, unit })
+}
+
+/*
+    A set of pitch classes (0 = C to 11 = B) above a root; chords are scales too for the
+        purpose of quantizing. Intervals are semitones from the root.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scale {
+    pitch_classes: Vec<u8>
+}
+
+impl Scale {
+    pub fn new(root: u8, intervals: &[u8]) -> Result<Scale, String> {
+        let mut pitch_classes: Vec<u8> = intervals.iter().map(|interval| ((root as u16 + *interval as u16) % 12) as u8).collect();
+        pitch_classes.sort();
+        pitch_classes.dedup();
+        if pitch_classes.is_empty() {
+            return Err("Scale needs at least one interval".to_string());
+        }
+        Ok(Scale { pitch_classes })
+    }
+
+    pub fn major(root: u8) -> Scale {
+        Scale::fixed(root, &[0, 2, 4, 5, 7, 9, 11])
+    }
+
+    // Natural minor
+    pub fn minor(root: u8) -> Scale {
+        Scale::fixed(root, &[0, 2, 3, 5, 7, 8, 10])
+    }
+
+    pub fn major_chord(root: u8) -> Scale {
+        Scale::fixed(root, &[0, 4, 7])
+    }
+
+    pub fn minor_chord(root: u8) -> Scale {
+        Scale::fixed(root, &[0, 3, 7])
+    }
+
+    fn fixed(root: u8, intervals: &[u8]) -> Scale {
+        Scale::new(root, intervals).expect("Built-in scales have intervals")
+    }
+
+    pub fn contains(&self, note: f32) -> bool {
+        self.pitch_classes.contains(&(note.round().rem_euclid(12.0) as u8))
+    }
+
+    // Nearest note in the scale after rounding to a semitone; ties resolve downwards
+    pub fn quantize(&self, note: f32) -> f32 {
+        let rounded = note.round();
+        (0..=6).flat_map(|offset| [rounded - offset as f32, rounded + offset as f32])
+            .find(|candidate| self.contains(*candidate))
+            .unwrap_or(rounded)
+    }
+
+    // Move the quantized note the given amount of scale degrees up (positive) or down;
+    //  non-finite notes are returned as is
+    pub fn step(&self, note: f32, degrees: i32) -> f32 {
+        if !note.is_finite() {
+            return note;
+        }
+
+        let mut note = self.quantize(note);
+        let direction = degrees.signum() as f32;
+        for _ in 0..degrees.abs() {
+            note += direction;
+            while !self.contains(note) {
+                note += direction;
+            }
+        }
+        note
+    }
+}
+
+// Shift every selected pitch by a fixed amount of semitones
+pub fn transpose(
+    packets: Vec<TimedOSCPacket>,
+    semitones: f32,
+    selector: impl Fn(&OscMessage) -> Option<PitchArg>
+) -> Result<Vec<TimedOSCPacket>, String> {
+    map_pitches(packets, selector, |note| note + semitones)
+}
+
+// Snap every selected pitch to the nearest note of the scale or chord
+pub fn quantize(
+    packets: Vec<TimedOSCPacket>,
+    scale: &Scale,
+    selector: impl Fn(&OscMessage) -> Option<PitchArg>
+) -> Result<Vec<TimedOSCPacket>, String> {
+    map_pitches(packets, selector, |note| scale.quantize(note))
+}
+
+// Move every selected pitch by scale degrees, quantizing it to the scale first
+pub fn transpose_in_scale(
+    packets: Vec<TimedOSCPacket>,
+    scale: &Scale,
+    degrees: i32,
+    selector: impl Fn(&OscMessage) -> Option<PitchArg>
+) -> Result<Vec<TimedOSCPacket>, String> {
+    map_pitches(packets, selector, |note| scale.step(note, degrees))
+}
+
+// Applies transform to pitches as midi notes, converting frequencies back and forth
+fn map_pitches(
+    packets: Vec<TimedOSCPacket>,
+    selector: impl Fn(&OscMessage) -> Option<PitchArg>,
+    transform: impl Fn(f32) -> f32
+) -> Result<Vec<TimedOSCPacket>, String> {
+    packets.into_iter().map(|mut timed| {
+        if let OscPacket::Message(msg) = &mut timed.packet {
+            if let Some(pitch) = selector(msg) {
+                let arg = msg.args.get_mut(pitch.index)
+                    .ok_or(format!("No pitch arg at index {} in {}", pitch.index, msg.addr))?;
+
+                *arg = match (pitch.unit, &*arg) {
+                    (PitchUnit::Midi, OscType::Int(note)) => OscType::Int(transform(*note as f32).round() as i32),
+                    (PitchUnit::Midi, OscType::Float(note)) => OscType::Float(transform(*note)),
+                    (PitchUnit::Frequency, OscType::Float(freq)) => OscType::Float(midi_to_freq(transform(freq_to_midi(*freq)?))),
+                    (unit, other) => return Err(format!("Expected {:?} pitch at index {} in {}, got {:?}", unit, pitch.index, msg.addr, other))
+                };
+            }
+        }
+        Ok(timed)
+    }).collect()
+}
@@ -0,0 +1,60 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use rosc::{OscMessage, OscPacket};
+
+use crate::model::TimedOSCPacket;
+use crate::supercollider::{NSet, SNew};
+
+/*
+    Completes a sequence of note-on packets with their note-offs, so that it can be played
+        as-is. Note length is read from the "duration" metadata of each TimedOSCPacket
+        (see the timed_msg convention in model.rs), in the same unit as its time.
+
+    let playable = with_note_offs(notes, gate_release);
+    let playable = with_note_offs(notes, note_off_for("/note_off", 0));
+ */
+
+pub const DURATION_KEY: &str = "duration";
+
+/*
+    Adds a packet built by note_off at time + duration for every message packet with duration
+        metadata. Packets without duration, or for which note_off returns None, are kept as-is.
+    The result is sorted by time, with note-offs first among packets sharing a time so that a
+        note ending exactly where the next one starts does not cut the new one short.
+ */
+pub fn with_note_offs(packets: Vec<TimedOSCPacket>, note_off: impl Fn(&OscMessage) -> Option<OscMessage>) -> Vec<TimedOSCPacket> {
+    let mut sequence: Vec<(TimedOSCPacket, bool)> = Vec::new();
+
+    for timed in packets {
+        let off = match (&timed.packet, timed.metadata_value(DURATION_KEY)) {
+            (OscPacket::Message(msg), Some(duration)) => note_off(msg).and_then(|off_msg| {
+                let duration = BigDecimal::from_str(&duration.to_string()).ok()?;
+                Some(TimedOSCPacket::new(&timed.time + duration, OscPacket::Message(off_msg)))
+            }),
+            _ => None
+        };
+
+        sequence.push((timed, false));
+        if let Some(off) = off {
+            sequence.push((off, true));
+        }
+    }
+
+    sequence.sort_by(|(a, a_is_off), (b, b_is_off)| a.time.cmp(&b.time).then(b_is_off.cmp(a_is_off)).then(a.order.cmp(&b.order)));
+    sequence.into_iter().map(|(timed, _)| timed).collect()
+}
+
+// For /s_new note-ons of gated synths: /n_set <node id> gate 0.0
+pub fn gate_release(msg: &OscMessage) -> Option<OscMessage> {
+    let s_new = SNew::from_message(msg).ok()?;
+    Some(NSet::new(s_new.node_id).with_control("gate", 0.0).to_message())
+}
+
+// For JDW style note-ons carrying an external id: <addr> <arg at id_index>
+pub fn note_off_for(addr: &str, id_index: usize) -> impl Fn(&OscMessage) -> Option<OscMessage> + '_ {
+    move |msg| Some(OscMessage {
+        addr: addr.to_string(),
+        args: vec![msg.args.get(id_index)?.clone()]
+    })
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::Path;
+
+use bigdecimal::{BigDecimal, RoundingMode, Signed, ToPrimitive};
+use rosc::{OscBundle, OscPacket, OscTime};
+
+use crate::model::TimedOSCPacket;
+
+/*
+    SuperCollider NRT score files: a plain concatenation of OSC bundles, each prefixed
+        with its size as a big-endian int32. The bundle time tag is the (relative) time
+        in seconds at which the contents should execute during the offline render.
+ */
+
+const FRACTIONS_PER_SECOND: u64 = 1 << 32;
+
+// Relative seconds to an OSC time tag, as used in NRT scores
+pub fn to_osc_time(time: &BigDecimal) -> Result<OscTime, String> {
+    if time.is_negative() {
+        return Err(format!("Negative time {} cannot be represented as an OSC time tag", time));
+    }
+
+    let seconds = time.with_scale_round(0, RoundingMode::Floor);
+    let fractional = ((time - &seconds) * BigDecimal::from(FRACTIONS_PER_SECOND))
+        .with_scale_round(0, RoundingMode::Floor);
+
+    Ok(OscTime {
+        seconds: seconds.to_u32().ok_or(format!("Time {} too large for an OSC time tag", time))?,
+        fractional: fractional.to_u32().unwrap_or(u32::MAX)
+    })
+}
+
+// OSC time tag to relative seconds, rounded to nanoseconds to undo time tag quantization
+pub fn from_osc_time(time: OscTime) -> BigDecimal {
+    let fraction = BigDecimal::from(time.fractional) / BigDecimal::from(FRACTIONS_PER_SECOND);
+    (BigDecimal::from(time.seconds) + fraction).round(9).normalized()
+}
+
+/*
+    Convert a sequence of timed packets into NRT score bytes.
+    Packets are ordered by time and order (stable when both are equal) and packets sharing the
+        same time are placed in the same score bundle.
+ */
+pub fn write_score(packets: &[TimedOSCPacket]) -> Result<Vec<u8>, String> {
+    let mut sorted: Vec<&TimedOSCPacket> = packets.iter().collect();
+    sorted.sort_by(|a, b| a.schedule_cmp(b));
+
+    let mut bundles: Vec<OscBundle> = Vec::new();
+    let mut last_time: Option<&BigDecimal> = None;
+
+    for timed in sorted {
+        match bundles.last_mut() {
+            Some(bundle) if last_time == Some(&timed.time) => bundle.content.push(timed.packet.clone()),
+            _ => bundles.push(OscBundle {
+                timetag: to_osc_time(&timed.time)?,
+                content: vec![timed.packet.clone()]
+            })
+        }
+        last_time = Some(&timed.time);
+    }
+
+    let mut score = Vec::new();
+    for bundle in bundles {
+        let bytes = rosc::encoder::encode(&OscPacket::Bundle(bundle)).map_err(|e| e.to_string())?;
+        let size = i32::try_from(bytes.len()).map_err(|e| e.to_string())?;
+        score.extend_from_slice(&size.to_be_bytes());
+        score.extend(bytes);
+    }
+
+    Ok(score)
+}
+
+pub fn write_score_file(path: &Path, packets: &[TimedOSCPacket]) -> Result<(), String> {
+    fs::write(path, write_score(packets)?).map_err(|e| format!("Failed to write score file {}: {}", path.display(), e))
+}
+
+/*
+    Parse NRT score bytes back into timed packets, one per packet in each score bundle.
+    Bundle order and content order are preserved.
+ */
+pub fn read_score(score: &[u8]) -> Result<Vec<TimedOSCPacket>, String> {
+    let mut timed_packets = Vec::new();
+    let mut remaining = score;
+
+    // Framing is done here since rosc decode_tcp does not limit bundle decoding to the size prefix
+    while !remaining.is_empty() {
+        let size = match remaining {
+            [a, b, c, d, ..] => u32::from_be_bytes([*a, *b, *c, *d]) as usize,
+            _ => return Err(format!("Score ends with {} bytes of incomplete size prefix", remaining.len()))
+        };
+        let frame = remaining.get(4..4 + size)
+            .ok_or(format!("Score ends with incomplete bundle, expected {} bytes", size))?;
+        remaining = &remaining[4 + size..];
+
+        let (_, packet) = rosc::decoder::decode_udp(frame).map_err(|e| e.to_string())?;
+        match packet {
+            OscPacket::Bundle(bundle) => {
+                let time = from_osc_time(bundle.timetag);
+                for packet in bundle.content {
+                    timed_packets.push(TimedOSCPacket::new(time.clone(), packet));
+                }
+            },
+            OscPacket::Message(msg) => return Err(format!("Expected only bundles in score, found message {}", msg.addr))
+        }
+    }
+
+    Ok(timed_packets)
+}
+
+pub fn read_score_file(path: &Path) -> Result<Vec<TimedOSCPacket>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read score file {}: {}", path.display(), e))?;
+    read_score(&bytes)
+}
@@ -2,125 +2,2172 @@
 
     Implements the following standard for polling incoming osc messages: 
 
-    OscPoll::init(<url>)
-        .on_message("/s_new", msg -> {...})
-        .on_tbundle("/queue_notes", msg -> {...})
-        .begin() 
+    OSCStack::init(<url>)
+        .on_message("/s_new", &|msg| {...})
+        .on_tbundle("queue_notes", &|bundle| {...})
+        .begin();
+
+    Registration methods take and return the stack by value, so a stack can be configured
+        in one chain and stored or passed on before begin() is called.
 
 */
 
-use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{debug, info, log_enabled, trace, warn, Level};
+extern crate rosc;
+
+use rosc::{OscBundle, OscPacket, OscMessage, OscType};
+
+use bigdecimal::BigDecimal;
+
+use crate::address::{CasePolicy, OscAddress};
+use crate::client::OscClient;
+use crate::codec::{default_codec, SharedCodec};
+use crate::compat::WIRE_FORMAT_VERSION;
+use crate::config::StackConfig;
+use crate::deadline;
+use crate::hexdump;
+use crate::sequence::{self, ReorderBuffer, SequenceEvent, SequenceTracker, GAP_REPORT_ADDR};
+use crate::receiver::{DatagramInfo, OscReceiver, RecvError};
+use crate::routing::{ForwardMatch, ForwardRule};
+use crate::core::schema::{self, BundleLimits, BundleSchema, LimitViolation, MessageSchema};
+use crate::stack_controller::{PausePolicy, StackController};
+use crate::supervision::{StackHealth, SupervisionPolicy, Supervisor};
+use crate::model::{FromTaggedBundle, TagRecovery, TaggedBundle, TimedOSCPacket};
+use crate::memory::{ApproxSize, EvictionPolicy};
+use crate::cancel::{self, CancellationToken, CANCEL_ADDR};
+use crate::immediate::{self, IMMEDIATE_TAG, PANIC_ADDR};
+use crate::progress::{Progress, PROGRESS_TAG};
+use crate::queue_update::{QueueUpdate, QUEUE_ADD_TAG, QUEUE_REMOVE_TAG, QUEUE_REPLACE_TAG};
+use crate::session;
+use crate::echo::{self, EchoReply, ECHO_ADDR, ECHO_REPLY_ADDR};
+use crate::hello::{Capabilities, HELLO_ADDR, HELLO_REPLY_ADDR};
+use crate::handler_context::{HandlerContext, ReplySocket};
+use crate::peers::PeerTable;
+use crate::congestion::AdaptiveRate;
+use crate::reply::{self, Reply, ERROR_REPLY_ADDR, OK_REPLY_ADDR};
+use crate::workers::{budgeted_queue, MemoryBudget, QueueError, QueueSender, Reservation, WorkerPool};
+
+/*
+    Structured description of anything the stack had to discard or could not make sense of.
+    Always logged, but also handed to the on_warning callback (if any) so that
+        applications can surface problems in their own UIs.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackWarning {
+    // Socket read failed
+    ReceiveFailure(String),
+    // Received bytes could not be decoded as an OSC packet
+    DecodeFailure(String),
+    // Bundle did not follow the /bundle_info tagging standard
+    UntaggedBundle(String),
+    // Tagged bundle arrived but no handler or funnel is registered for its tag
+    UnmatchedTag(String),
+    // Tagged bundle (tag, error) whose contents did not match its expected layout
+    MalformedBundle(String, String),
+    // Message (address, error) whose args were not what the receiving handler expected
+    MalformedMessage(String, String),
+    // Lenient tagging had to guess the tag of a non-standard bundle
+    RecoveredTag(TagRecovery),
+    // Bundle (tag or description) dropped because its deadline had passed, see deadline.rs
+    Expired(String),
+    // Forwarding a packet to the target (target, error) failed
+    ForwardFailure(String, String),
+    // Sequence numbers from the source skipped ahead; (source, expected, received)
+    SequenceGap(SocketAddr, i32, i32),
+    // Sequence number from the source arrived late or twice; (source, expected, received)
+    SequenceReordered(SocketAddr, i32, i32),
+    // Datagram of the given size filled the whole receive buffer and was likely cut short
+    PossiblyTruncated(usize),
+    // Datagram had the given amount of bytes left over after decoding its packet
+    TrailingBytes(usize),
+    // Thread of a dedicated handler for the address or tag has died, e.g. from a panic
+    HandlerStopped(String),
+    // Tagged bundle (tag, violation) exceeding the size limits declared for its tag
+    LimitExceeded(String, LimitViolation),
+    // Inline handler for the address or tag ran longer than the handler time limit; (key, elapsed)
+    SlowHandler(String, Duration),
+    // Raw bytes (sender, hex dump) following a DecodeFailure or UntaggedBundle, see OSCStack::hex_dump_failures
+    HexDump(Option<SocketAddr>, String),
+    // Packets (address or tag, count) dropped to stay within the memory budget, see OSCStack::memory_budget
+    Evicted(String, usize),
+}
+
+impl fmt::Display for StackWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackWarning::ReceiveFailure(e) => write!(f, "Failed to receive from socket {}", e),
+            StackWarning::DecodeFailure(e) => write!(f, "Failed to decode packet: {}", e),
+            StackWarning::UntaggedBundle(e) => write!(f, "Failed to parse bundle as tagged: {}", e),
+            StackWarning::UnmatchedTag(tag) => write!(f, "No operation registered for bundle tag: {}", tag),
+            StackWarning::MalformedBundle(tag, e) => write!(f, "Malformed {} bundle: {}", tag, e),
+            StackWarning::MalformedMessage(addr, e) => write!(f, "Malformed {} message: {}", addr, e),
+            StackWarning::RecoveredTag(recovery) => write!(f, "Recovered non-standard bundle, {}", recovery),
+            StackWarning::Expired(bundle) => write!(f, "Dropped {} bundle past its deadline", bundle),
+            StackWarning::ForwardFailure(target, e) => write!(f, "Failed to forward packet to {}: {}", target, e),
+            StackWarning::SequenceGap(source, expected, received) =>
+                write!(f, "Sequence gap from {}: expected {}, received {}", source, expected, received),
+            StackWarning::SequenceReordered(source, expected, received) =>
+                write!(f, "Out of order sequence number from {}: expected {}, received {}", source, expected, received),
+            StackWarning::PossiblyTruncated(size) => write!(f, "Datagram of {} bytes filled the receive buffer and was likely truncated", size),
+            StackWarning::TrailingBytes(count) => write!(f, "Datagram had {} trailing bytes after the decoded packets", count),
+            StackWarning::HandlerStopped(key) => write!(f, "Dedicated handler thread for {} has stopped", key),
+            StackWarning::LimitExceeded(tag, violation) => write!(f, "Rejected {} bundle: {}", tag, violation),
+            StackWarning::SlowHandler(key, elapsed) => write!(f, "Handler for {} took {:.2?}, stalling the receive loop", key, elapsed),
+            StackWarning::HexDump(Some(sender), dump) => write!(f, "Raw bytes from {}:\n{}", sender, dump),
+            StackWarning::HexDump(None, dump) => write!(f, "Raw bytes:\n{}", dump),
+            StackWarning::Evicted(key, count) => write!(f, "Dropped {} packets queued for {} to stay within the memory budget", count, key),
+        }
+    }
+}
+
+// Built-in address for switching the current mode, see OSCStack::on_message_in_mode
+pub const SET_MODE_ADDR: &str = "/jdw/set_mode";
+
+// How often a paused stack checks whether it was resumed while no packets arrive
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// How often silent peers are looked for when tracking peers, see OSCStack::track_peers
+const PEER_SWEEP_INTERVAL: Duration = Duration::from_millis(250);
+
+/*
+    Information about the packet currently being dispatched, beyond the packet itself.
+    Handlers keep their plain signatures and fetch this with dispatch_context() when needed.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct DispatchContext {
+    // Relative time of the timed_msg wrapper the packet was unwrapped from, if any
+    pub time: Option<BigDecimal>,
+    // Tags of the funneled (or unwrapped timed_msg) bundles the packet came from, outermost first
+    pub lineage: Vec<String>,
+    // Session id of the bundle the packet came in, see session.rs
+    pub session: Option<String>,
+    // When the packet arrived, None for packets not received over the wire
+    pub received: Option<ReceiveTime>,
+    // Where the packet came from, None for packets not received over the wire
+    pub sender: Option<SocketAddr>,
+}
+
+/*
+    Receive time of a packet, taken once per datagram as it is read from the socket. Packets
+        held for ordered delivery or while paused keep the time they actually arrived, so
+        that handlers can measure latency and schedule relative to arrival consistently.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiveTime {
+    // For durations and scheduling relative to the arrival
+    pub instant: Instant,
+    // For comparing with times from other machines, e.g. deadlines or sent timestamps
+    pub wall: SystemTime
+}
+
+impl ReceiveTime {
+    pub fn now() -> ReceiveTime {
+        ReceiveTime { instant: Instant::now(), wall: SystemTime::now() }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+}
+
+impl DispatchContext {
+    // Lineage as "outer > inner", or None for packets dispatched as received
+    pub fn describe_lineage(&self) -> Option<String> {
+        if self.lineage.is_empty() {
+            return None;
+        }
+        Some(self.lineage.join(" > "))
+    }
+}
+
+thread_local! {
+    static DISPATCH_CONTEXT: RefCell<DispatchContext> = RefCell::new(DispatchContext::default());
+}
+
+// Context of the packet currently being dispatched on this thread
+pub fn dispatch_context() -> DispatchContext {
+    DISPATCH_CONTEXT.with(|ctx| ctx.borrow().clone())
+}
+
+fn dispatch_session() -> Option<String> {
+    DISPATCH_CONTEXT.with(|ctx| ctx.borrow().session.clone())
+}
+
+// Restores the previous context when dropped, so that a panicking handler can't leak its context
+struct ContextRestore(Option<DispatchContext>);
+
+impl Drop for ContextRestore {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            DISPATCH_CONTEXT.with(|ctx| *ctx.borrow_mut() = previous);
+        }
+    }
+}
+
+// Run the given dispatch with a modified context, restoring the previous one afterwards
+fn with_dispatch_context(modify: impl FnOnce(&mut DispatchContext), dispatch: impl FnOnce()) {
+    let _restore = ContextRestore(Some(dispatch_context()));
+    DISPATCH_CONTEXT.with(|ctx| modify(&mut ctx.borrow_mut()));
+    dispatch();
+}
+
+/*
+    Where a message or tagged bundle handler runs, as declared when registering it:
+        on_message runs inline, on_message_parallel on the shared worker pool,
+        on_message_dedicated on a thread of its own and on_message_sharded on one of its own
+        threads picked by key. Same for the tbundle variants.
+ */
+enum Handler<'a, T> {
+    // On the receiving thread, before the next packet is dispatched
+    Inline(&'a dyn Fn(T)),
+    // Inline as well, along with a HandlerContext, see OSCStack::on_message_with_context
+    Contextual(&'a dyn Fn(T, &HandlerContext)),
+    // On any worker thread, possibly overlapping with other calls to the same handler
+    Parallel(Arc<dyn Fn(T) + Send + Sync>),
+    // On the handler's own thread, one call at a time in arrival order
+    Dedicated(QueueSender<(T, DispatchContext)>),
+    // On one of the handler's shard threads, picked by key, see OSCStack::on_message_sharded
+    Sharded(Shards<'a, T>)
+}
+
+// Threads of a sharded handler along with the key picking between them
+struct Shards<'a, T> {
+    key: Box<dyn Fn(&T) -> u64 + 'a>,
+    senders: Vec<QueueSender<(T, DispatchContext)>>
+}
+
+impl<'a, T: Send + 'static> Shards<'a, T> {
+    fn spawn<K: Hash>(
+        name: &str,
+        shards: usize,
+        budget: &Arc<MemoryBudget>,
+        key: impl Fn(&T) -> K + 'a,
+        operations: impl Fn(T) + Send + Sync + 'static
+    ) -> Shards<'a, T> {
+        let operations = Arc::new(operations);
+        let senders = (0..shards.max(1))
+            .map(|index| {
+                let operations = operations.clone();
+                spawn_dedicated(&format!("{}-{}", name, index), budget, move |arg| operations(arg))
+            })
+            .collect();
+
+        Shards {
+            key: Box::new(move |arg| {
+                let mut hasher = DefaultHasher::new();
+                key(arg).hash(&mut hasher);
+                hasher.finish()
+            }),
+            senders
+        }
+    }
+
+    fn sender_for(&self, arg: &T) -> &QueueSender<(T, DispatchContext)> {
+        &self.senders[((self.key)(arg) % self.senders.len() as u64) as usize]
+    }
+}
+
+/*
+    Shard key for on_message_sharded taking the arg at the given index, e.g. a note or voice id.
+    Messages without the arg all end up on the same shard.
+ */
+pub fn shard_by_arg(index: usize) -> impl Fn(&OscMessage) -> Option<String> {
+    move |msg| msg.args.get(index).map(|arg| match arg {
+        OscType::String(value) => value.clone(),
+        OscType::Int(value) => value.to_string(),
+        OscType::Long(value) => value.to_string(),
+        other => format!("{:?}", other)
+    })
+}
+
+// Starts the thread of a dedicated handler; it ends once the stack (and thus the sender) is dropped
+fn spawn_dedicated<T: Send + 'static>(
+    key: &str,
+    budget: &Arc<MemoryBudget>,
+    operations: impl Fn(T) + Send + 'static
+) -> QueueSender<(T, DispatchContext)> {
+    let (sender, receiver) = budgeted_queue::<(T, DispatchContext)>(budget.clone());
+    thread::Builder::new()
+        .name(format!("osc-handler-{}", key))
+        .spawn(move || {
+            while let Some((arg, context)) = receiver.recv() {
+                with_dispatch_context(|ctx| *ctx = context, || operations(arg));
+            }
+        })
+        .expect("Failed to spawn dedicated OSC handler thread");
+    sender
+}
+
+// Drops what is queued for a dedicated or sharded handler, returning the amount dropped
+fn clear_queue<T>(handler: &Handler<'_, T>) -> usize {
+    match handler {
+        Handler::Dedicated(sender) => sender.clear(),
+        Handler::Sharded(shards) => shards.senders.iter().map(|sender| sender.clear()).sum(),
+        Handler::Inline(_) | Handler::Contextual(_) | Handler::Parallel(_) => 0
+    }
+}
+
+// Parses the bundle into the registered type and calls the handler with it
+type TypedTbundleOperation<'a> = Box<dyn Fn(TaggedBundle) -> Result<(), String> + 'a>;
+
+// Operation id carried by a message, see OSCStack::on_message_cancellable
+type OperationId<'a> = Box<dyn Fn(&OscMessage) -> Option<String> + 'a>;
+
+// Handler on the worker pool that /jdw/cancel messages can abort
+struct CancellableOperation<'a> {
+    id: OperationId<'a>,
+    op: Arc<dyn Fn(OscMessage, CancellationToken) + Send + Sync>
+}
+
+// A registered handler along with the conditions under which it applies
+pub(crate) struct Route<T> {
+    pub(crate) op: T,
+    // Handler group the route was registered in, see OSCStack::group
+    group: Option<String>,
+    // Only applies while the stack is in this mode, see OSCStack::on_message_in_mode
+    mode: Option<String>,
+    // Temporary routes stop applying once expired, see OSCStack::on_message_once
+    expiry: Option<Expiry>,
+    // Only applies to bundles of this session, see OSCStack::session
+    session: Option<String>
+}
+
+struct Expiry {
+    deadline: Option<Instant>,
+    once: bool,
+    spent: Cell<bool>
+}
+
+impl<T> Route<T> {
+    // Applies regardless of group, mode and session until it expires
+    pub(crate) fn temporary(op: T, deadline: Option<Instant>, once: bool) -> Route<T> {
+        let expiry = Some(Expiry { deadline, once, spent: Cell::new(false) });
+        Route { op, group: None, mode: None, expiry, session: None }
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        self.expiry.as_ref().is_some_and(|expiry| {
+            expiry.spent.get() || expiry.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        })
+    }
+
+    pub(crate) fn consume(&self) {
+        if let Some(expiry) = self.expiry.as_ref().filter(|expiry| expiry.once) {
+            expiry.spent.set(true);
+        }
+    }
+}
+
+fn describe_bundle(bundle: &OscBundle) -> String {
+    TaggedBundle::new(bundle).map(|tagged| tagged.bundle_tag).unwrap_or("untagged".to_string())
+}
+
+/*
+    Handlers for one address or tag share its schema, so registering the same schema again
+        is fine. A different one would silently change what the earlier handlers accept.
+ */
+fn register_schema<S: PartialEq + fmt::Debug>(schemas: &mut HashMap<String, S>, key: String, schema: S) {
+    match schemas.entry(key) {
+        Entry::Occupied(existing) if *existing.get() != schema => panic!(
+            "Conflicting schemas registered for {}: {:?} and {:?}", existing.key(), existing.get(), schema
+        ),
+        Entry::Occupied(_) => {},
+        Entry::Vacant(entry) => {
+            entry.insert(schema);
+        }
+    }
+}
+
+// Fixed one second window counting admitted packets, for StackConfig::max_packets_per_second
+struct RateWindow {
+    started: Instant,
+    count: u32
+}
+
+impl RateWindow {
+    fn new() -> RateWindow {
+        RateWindow { started: Instant::now(), count: 0 }
+    }
+
+    fn admit(&mut self, limit: Option<u32>) -> bool {
+        let Some(limit) = limit else { return true };
+
+        if self.started.elapsed() >= Duration::from_secs(1) {
+            self.started = Instant::now();
+            self.count = 0;
+        }
+
+        self.count += 1;
+        self.count <= limit
+    }
+}
+
+// Routes per address or bundle tag, in registration order
+type Routes<T> = HashMap<String, Vec<Route<T>>>;
+
+// Where a packet handed to middleware came from
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PacketOrigin<'b> {
+    // The packet's original encoded bytes, when received over the wire and still available
+    pub bytes: Option<&'b [u8]>,
+    pub sender: Option<SocketAddr>,
+    pub received: Option<ReceiveTime>
+}
+
+// Packet received while paused, see StackController::pause
+struct PausedPacket {
+    sender: SocketAddr,
+    received: ReceiveTime,
+    packet: OscPacket,
+    // As charged to the memory budget
+    size: usize
+}
+
+/*
+    Runs on every received packet before dispatch, in registration order.
+    Returns the packet to pass on, possibly rewritten, or None to drop it.
+ */
+pub type Middleware<'a> = &'a dyn Fn(OscPacket, &PacketOrigin) -> Option<OscPacket>;
+
+/*
+    Route table for a stack at a glance, expanding to the matching builder calls:
+
+    let stack = osc_routes!(OSCStack::init(url), {
+        "/note_on" => handle_note,
+        tbundle "queue_notes" => handle_queue,
+        timed "/s_new" => handle_timed,
+        funnel "batch"
+    });
+
+    Handlers are functions or closures taken by reference as with the builder methods.
+    Message and timed addresses are validated at compile time, see osc_addr!.
+ */
+#[macro_export]
+macro_rules! osc_routes {
+    ($stack:expr, { $($routes:tt)* }) => {
+        $crate::osc_routes!(@route $stack; $($routes)*)
+    };
+    (@route $stack:expr;) => {
+        $stack
+    };
+    (@route $stack:expr; tbundle $tag:literal => $op:expr $(, $($rest:tt)*)?) => {
+        $crate::osc_routes!(@route $stack.on_tbundle($tag, &$op); $($($rest)*)?)
+    };
+    (@route $stack:expr; timed $addr:literal => $op:expr $(, $($rest:tt)*)?) => {
+        $crate::osc_routes!(@route $stack.on_timed($crate::osc_addr!($addr), &$op); $($($rest)*)?)
+    };
+    (@route $stack:expr; funnel $tag:literal $(, $($rest:tt)*)?) => {
+        $crate::osc_routes!(@route $stack.funnel_tbundle($tag); $($($rest)*)?)
+    };
+    (@route $stack:expr; $addr:literal => $op:expr $(, $($rest:tt)*)?) => {
+        $crate::osc_routes!(@route $stack.on_message($crate::osc_addr!($addr), &$op); $($($rest)*)?)
+    };
+}
+
+pub struct OSCStack<'a> {
+    // Several handlers may share an address or tag, they are called in registration order
+    message_operations: Routes<Handler<'a, OscMessage>>,
+    // Pruned as they expire, also while begin() is running, see OSCStack::on_message_once
+    temporary_message_operations: RefCell<Routes<&'a dyn Fn(OscMessage)>>,
+    cancellable_operations: Routes<CancellableOperation<'a>>,
+    tbundle_operations: Routes<Handler<'a, TaggedBundle>>,
+    timed_operations: Routes<&'a dyn Fn(BigDecimal, OscMessage)>,
+    reply_operations: Vec<Route<&'a dyn Fn(Reply)>>,
+    typed_tbundle_operations: Routes<TypedTbundleOperation<'a>>,
+    current_group: Option<String>,
+    // Session handlers are currently registered for, see OSCStack::session
+    current_session: Option<String>,
+    // Drop bundles of any other session, see OSCStack::only_session
+    session_filter: Option<String>,
+    tbundle_funnels: HashSet<String>,
+    tbundle_schemas: HashMap<String, BundleSchema>,
+    tbundle_limits: HashMap<String, BundleLimits>,
+    message_schemas: HashMap<String, MessageSchema>,
+    message_channels: HashMap<String, Vec<Sender<OscMessage>>>,
+    tbundle_channels: HashMap<String, Vec<Sender<TaggedBundle>>>,
+    middleware: Vec<Middleware<'a>>,
+    warning_operation: Option<&'a dyn Fn(StackWarning)>,
+    unmatched_operation: Option<&'a dyn Fn(OscPacket)>,
+    lenient_tagging: bool,
+    fallback_tag: Option<String>,
+    unwrap_timed_funnels: bool,
+    case_policy: CasePolicy,
+    controller: StackController,
+    codec: SharedCodec,
+    // Sends answers to peers, from the receiving socket once begin() has bound it
+    reply_socket: ReplySocket,
+    startup_banner: bool,
+    echo: bool,
+    // Name to answer hellos with, None to not answer them, see OSCStack::hello
+    hello_name: Option<String>,
+    hello_operation: Option<&'a dyn Fn(Capabilities)>,
+    sequences: RefCell<SequenceTracker>,
+    // Receive times of packets held for ordered delivery, by sender and sequence number
+    held_receive_times: RefCell<HashMap<(SocketAddr, i32), ReceiveTime>>,
+    ordered_tags: HashMap<String, Duration>,
+    // Clients for forwarding targets, created on first use
+    forward_clients: RefCell<HashMap<String, OscClient>>,
+    peers: Option<PeerTable>,
+    // Fed with acks, round trips and gap reports, see OSCStack::adapt_rate
+    adaptive_rate: Option<AdaptiveRate>,
+    report_gaps: bool,
+    // Started on the first parallel handler registration
+    workers: Option<WorkerPool>,
+    supervision: SupervisionPolicy,
+    health_operation: Option<&'a dyn Fn(StackHealth)>,
+    // Max bytes to dump for packets failing to decode or tag, None to not dump at all
+    hex_dump_limit: Option<usize>,
+    // Inline handlers running longer than this are reported, None to not time them at all
+    handler_time_limit: Option<Duration>,
+    // Sender of the packet being delivered, for reporting along with hex dumps
+    current_sender: Cell<Option<SocketAddr>>,
+    // What to drop when queued packets exceed the memory budget held by the controller
+    eviction_policy: EvictionPolicy,
+    // Set while dispatching the contents of an immediate bundle, see immediate.rs
+    dispatching_immediate: Cell<bool>,
+    panic_operation: Option<&'a dyn Fn()>,
+    // Set by a panic until the receive loop has dropped the packets held for ordered delivery
+    panic_pending: Cell<bool>,
+    host_url: String
+}
+
+impl <'a> OSCStack<'a> {
+    pub fn init(host_url: String) -> OSCStack<'a> {
+        OSCStack {
+            message_operations: HashMap::new(),
+            temporary_message_operations: RefCell::new(HashMap::new()),
+            cancellable_operations: HashMap::new(),
+            tbundle_operations: HashMap::new(),
+            timed_operations: HashMap::new(),
+            reply_operations: Vec::new(),
+            typed_tbundle_operations: HashMap::new(),
+            current_group: None,
+            current_session: None,
+            session_filter: None,
+            tbundle_funnels: HashSet::new(),
+            tbundle_schemas: HashMap::new(),
+            tbundle_limits: HashMap::new(),
+            message_schemas: HashMap::new(),
+            message_channels: HashMap::new(),
+            tbundle_channels: HashMap::new(),
+            middleware: Vec::new(),
+            warning_operation: None,
+            unmatched_operation: None,
+            lenient_tagging: false,
+            fallback_tag: None,
+            unwrap_timed_funnels: false,
+            case_policy: CasePolicy::Preserve,
+            controller: StackController::new(),
+            codec: default_codec(),
+            reply_socket: ReplySocket::new(default_codec()),
+            startup_banner: false,
+            echo: false,
+            hello_name: None,
+            hello_operation: None,
+            sequences: RefCell::new(SequenceTracker::new()),
+            held_receive_times: RefCell::new(HashMap::new()),
+            ordered_tags: HashMap::new(),
+            forward_clients: RefCell::new(HashMap::new()),
+            peers: None,
+            adaptive_rate: None,
+            report_gaps: false,
+            workers: None,
+            supervision: SupervisionPolicy::default(),
+            health_operation: None,
+            hex_dump_limit: None,
+            handler_time_limit: None,
+            current_sender: Cell::new(None),
+            eviction_policy: EvictionPolicy::default(),
+            dispatching_immediate: Cell::new(false),
+            panic_operation: None,
+            panic_pending: Cell::new(false),
+            host_url
+        }
+    }
+
+    // Adds to any handlers already registered for the address
+    pub fn on_message(mut self, addr: impl Into<OscAddress>, operations: &'a dyn Fn(OscMessage)) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let route = self.route(Handler::Inline(operations));
+        self.message_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    // As on_message, with access to the sender, metrics and more through a HandlerContext
+    pub fn on_message_with_context(mut self, addr: impl Into<OscAddress>, operations: &'a dyn Fn(OscMessage, &HandlerContext)) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let route = self.route(Handler::Contextual(operations));
+        self.message_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    /*
+        As on_message, but for thread-safe handlers that may run on a pool of worker threads,
+            so that slow handlers don't hold up the receive loop. Calls can overlap and finish
+            in any order; dispatch_context() is carried over to the worker.
+     */
+    pub fn on_message_parallel(mut self, addr: impl Into<OscAddress>, operations: impl Fn(OscMessage) + Send + Sync + 'static) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        self.workers.get_or_insert_with(WorkerPool::with_default_size);
+        let route = self.route(Handler::Parallel(Arc::new(operations)));
+        self.message_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    /*
+        As on_message, but the handler runs on a thread of its own, one message at a time.
+        For handlers that must stay on a single thread (e.g. owning GUI or device state) and
+            should not block the receive loop while doing so. The handler need not be Sync.
+     */
+    pub fn on_message_dedicated(mut self, addr: impl Into<OscAddress>, operations: impl Fn(OscMessage) + Send + 'static) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let route = self.route(Handler::Dedicated(spawn_dedicated(&key, self.controller.memory_budget(), operations)));
+        self.message_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    /*
+        As on_message_parallel, but messages with equal keys are handled one at a time in
+            arrival order, while messages with different keys may run in parallel. For streams
+            such as notes, where events for the same id must not overtake each other:
+
+        stack.on_message_sharded("/note_on", 4, shard_by_arg(0), |msg| play(msg))
+
+        The key is extracted on the receive loop and hashed onto one of the given number of
+            threads, which belong to this handler alone.
+     */
+    pub fn on_message_sharded<K: Hash>(
+        mut self,
+        addr: impl Into<OscAddress>,
+        shards: usize,
+        key: impl Fn(&OscMessage) -> K + 'a,
+        operations: impl Fn(OscMessage) + Send + Sync + 'static
+    ) -> OSCStack<'a> {
+        let addr_key = self.normalize(addr.into().as_str());
+        let route = self.route(Handler::Sharded(Shards::spawn(&addr_key, shards, self.controller.memory_budget(), key, operations)));
+        self.message_operations.entry(addr_key).or_default().push(route);
+        self
+    }
+
+    /*
+        Thread-safe handler for long running operations (renders, imports) that can be aborted
+            remotely, see cancel.rs. The handler runs on the worker pool with a token that trips
+            when a ["/jdw/cancel", id] message arrives for the id taken from the message:
+
+        stack.on_message_cancellable("/render", shard_by_arg(0), |msg, token| {
+            for bar in bars {
+                if token.is_cancelled() { return; }
+                render(bar);
+            }
+        })
+
+        The operation is registered under its id before the message leaves the receive loop,
+            and removed again once the handler returns or panics. Messages without an id are reported as
+            StackWarning::MalformedMessage.
+     */
+    pub fn on_message_cancellable(
+        mut self,
+        addr: impl Into<OscAddress>,
+        id: impl Fn(&OscMessage) -> Option<String> + 'a,
+        operations: impl Fn(OscMessage, CancellationToken) + Send + Sync + 'static
+    ) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        self.workers.get_or_insert_with(WorkerPool::with_default_size);
+        let route = self.route(CancellableOperation { id: Box::new(id), op: Arc::new(operations) });
+        self.cancellable_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    /*
+        Only called while the stack is in the given mode. Modes are switched at runtime
+            via StackController::set_mode or by sending the built-in /jdw/set_mode message:
+            ["/jdw/set_mode", "record"] enters "record", an arg-less message leaves any mode.
+     */
+    pub fn on_message_in_mode(mut self, mode: &str, addr: impl Into<OscAddress>, operations: &'a dyn Fn(OscMessage)) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let mut route = self.route(Handler::Inline(operations));
+        route.mode = Some(mode.to_string());
+        self.message_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    // Called for the first matching message only, after which the handler no longer applies
+    pub fn on_message_once(self, addr: impl Into<OscAddress>, operations: &'a dyn Fn(OscMessage)) -> OSCStack<'a> {
+        self.on_expiring_message(addr, operations, None, true)
+    }
+
+    /*
+        Called for every matching message until the duration has passed, counting from
+            registration. Useful for short-lived interactions such as awaiting replies.
+     */
+    pub fn on_message_for(self, addr: impl Into<OscAddress>, duration: Duration, operations: &'a dyn Fn(OscMessage)) -> OSCStack<'a> {
+        self.on_expiring_message(addr, operations, Some(Instant::now() + duration), false)
+    }
+
+    fn on_expiring_message(mut self, addr: impl Into<OscAddress>, operations: &'a dyn Fn(OscMessage), deadline: Option<Instant>, once: bool) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let mut route = self.route(operations);
+        route.expiry = Some(Expiry { deadline, once, spent: Cell::new(false) });
+        self.temporary_message_operations.get_mut().entry(key.clone()).or_default().push(route);
+        self.prune_temporary(&key);
+        self
+    }
+
+    // Drops the expired temporary routes for the address, and the address once none are left
+    fn prune_temporary(&self, key: &str) {
+        let mut temporary = self.temporary_message_operations.borrow_mut();
+        let Some(routes) = temporary.get_mut(key) else { return };
+        routes.retain(|route| !route.expired());
+        if routes.is_empty() {
+            temporary.remove(key);
+        }
+    }
+
+    // Same as on_message for schema.addr, but messages not matching the schema are rejected
+    //  with a StackWarning::MalformedMessage instead of reaching any handler for the address
+    // Panics if a different schema was already registered for the address
+    pub fn on_message_with_schema(mut self, schema: MessageSchema, operations: &'a dyn Fn(OscMessage)) -> OSCStack<'a> {
+        let key = self.normalize(&schema.addr);
+        register_schema(&mut self.message_schemas, key.clone(), schema);
+        let route = self.route(Handler::Inline(operations));
+        self.message_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    // Removes all handlers previously registered for the address before adding this one
+    pub fn replace_message_handler(mut self, addr: impl Into<OscAddress>, operations: &'a dyn Fn(OscMessage)) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let route = self.route(Handler::Inline(operations));
+        self.message_operations.insert(key, vec![route]);
+        self
+    }
+
+    // Adds to any handlers already registered for the tag
+    pub fn on_tbundle(mut self, tag: &str, operations: &'a dyn Fn(TaggedBundle)) -> OSCStack<'a> {
+        let route = self.route(Handler::Inline(operations));
+        self.tbundle_operations.entry(tag.to_string()).or_default().push(route);
+        self
+    }
+
+    // As on_tbundle, with a HandlerContext, see on_message_with_context
+    pub fn on_tbundle_with_context(mut self, tag: &str, operations: &'a dyn Fn(TaggedBundle, &HandlerContext)) -> OSCStack<'a> {
+        let route = self.route(Handler::Contextual(operations));
+        self.tbundle_operations.entry(tag.to_string()).or_default().push(route);
+        self
+    }
+
+    // Thread-safe on_tbundle handler running on the worker pool, see on_message_parallel
+    pub fn on_tbundle_parallel(mut self, tag: &str, operations: impl Fn(TaggedBundle) + Send + Sync + 'static) -> OSCStack<'a> {
+        self.workers.get_or_insert_with(WorkerPool::with_default_size);
+        let route = self.route(Handler::Parallel(Arc::new(operations)));
+        self.tbundle_operations.entry(tag.to_string()).or_default().push(route);
+        self
+    }
+
+    // on_tbundle handler running on a thread of its own, see on_message_dedicated
+    pub fn on_tbundle_dedicated(mut self, tag: &str, operations: impl Fn(TaggedBundle) + Send + 'static) -> OSCStack<'a> {
+        let route = self.route(Handler::Dedicated(spawn_dedicated(tag, self.controller.memory_budget(), operations)));
+        self.tbundle_operations.entry(tag.to_string()).or_default().push(route);
+        self
+    }
+
+    // on_tbundle handler sharded by key, see on_message_sharded
+    pub fn on_tbundle_sharded<K: Hash>(
+        mut self,
+        tag: &str,
+        shards: usize,
+        key: impl Fn(&TaggedBundle) -> K + 'a,
+        operations: impl Fn(TaggedBundle) + Send + Sync + 'static
+    ) -> OSCStack<'a> {
+        let route = self.route(Handler::Sharded(Shards::spawn(tag, shards, self.controller.memory_budget(), key, operations)));
+        self.tbundle_operations.entry(tag.to_string()).or_default().push(route);
+        self
+    }
+
+    // Removes all handlers previously registered for the tag before adding this one
+    pub fn replace_tbundle_handler(mut self, tag: &str, operations: &'a dyn Fn(TaggedBundle)) -> OSCStack<'a> {
+        let route = self.route(Handler::Inline(operations));
+        self.tbundle_operations.insert(tag.to_string(), vec![route]);
+        self
+    }
+
+    /*
+        Reject bundles with the tag exceeding the limits with a StackWarning::LimitExceeded,
+            before they are funneled, unwrapped or handed to any handler or subscriber:
+
+        stack.limit_tbundle("queue_notes", BundleLimits::new().max_contents(1000).max_depth(2))
+            .on_tbundle("queue_notes", &queue)
+     */
+    pub fn limit_tbundle(mut self, tag: &str, limits: BundleLimits) -> OSCStack<'a> {
+        self.tbundle_limits.insert(tag.to_string(), limits);
+        self
+    }
+
+    // Same as on_tbundle for schema.tag, but bundles not matching the schema layout are
+    //  rejected with a StackWarning::MalformedBundle instead of reaching any handler for the tag
+    // Panics if a different schema was already registered for the tag
+    pub fn on_tbundle_with_schema(mut self, schema: BundleSchema, operations: &'a dyn Fn(TaggedBundle)) -> OSCStack<'a> {
+        let tag = schema.tag.clone();
+        register_schema(&mut self.tbundle_schemas, tag.clone(), schema);
+        let route = self.route(Handler::Inline(operations));
+        self.tbundle_operations.entry(tag).or_default().push(route);
+        self
+    }
+
+    /*
+        Register a handler receiving the tagged bundle already parsed into T.
+        Bundles failing to parse are reported as StackWarning::MalformedBundle.
+        stack.on_typed_tbundle::<NRTRecordRequest>("nrt_record_request", &|req| {...})
+     */
+    pub fn on_typed_tbundle<T: FromTaggedBundle + 'a>(mut self, tag: &str, operations: &'a dyn Fn(T)) -> OSCStack<'a> {
+        let route = self.route::<TypedTbundleOperation<'a>>(
+            Box::new(move |bundle| T::from_tagged_bundle(bundle).map(operations))
+        );
+        self.typed_tbundle_operations.entry(tag.to_string()).or_default().push(route);
+        self
+    }
+
+    /*
+        Called with the progress updates of the operation with the given id, see progress.rs:
+
+        stack.on_progress("render-42", &|progress| bar.set(progress.pct))
+     */
+    pub fn on_progress(mut self, id: &str, operations: &'a dyn Fn(Progress)) -> OSCStack<'a> {
+        let id = id.to_string();
+        let route = self.route::<TypedTbundleOperation<'a>>(Box::new(move |bundle| {
+            let progress = Progress::from_tagged_bundle(bundle)?;
+            if progress.id == id {
+                operations(progress);
+            }
+            Ok(())
+        }));
+        self.typed_tbundle_operations.entry(PROGRESS_TAG.to_string()).or_default().push(route);
+        self
+    }
+
+    /*
+        Called with every queue_add, queue_remove and queue_replace bundle, see queue_update.rs:
+
+        stack.on_queue_update(&|update| if let Err(e) = queue.borrow_mut().apply(update) {...})
+     */
+    pub fn on_queue_update(self, operations: &'a dyn Fn(QueueUpdate)) -> OSCStack<'a> {
+        self.on_typed_tbundle(QUEUE_ADD_TAG, operations)
+            .on_typed_tbundle(QUEUE_REMOVE_TAG, operations)
+            .on_typed_tbundle(QUEUE_REPLACE_TAG, operations)
+    }
+
+    // Match timed_msg bundles whose wrapped packet is a message with the given address
+    // Takes precedence over any on_tbundle op registered for "timed_msg"
+    pub fn on_timed(mut self, addr: impl Into<OscAddress>, operations: &'a dyn Fn(BigDecimal, OscMessage)) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        let route = self.route(operations);
+        self.timed_operations.entry(key).or_default().push(route);
+        self
+    }
+
+    /*
+        Called with every ok and error reply received, parsed according to the convention in
+            reply.rs. Reply messages that don't parse are reported as
+            StackWarning::MalformedMessage. on_message handlers for the reply addresses still
+            get the raw messages. Progress updates are received with on_progress instead.
+     */
+    pub fn on_reply(mut self, operations: &'a dyn Fn(Reply)) -> OSCStack<'a> {
+        let route = self.route(operations);
+        self.reply_operations.push(route);
+        self
+    }
+
+    // True if the message is a reply and on_reply handlers were called with it
+    fn dispatch_reply(&self, osc_msg: &OscMessage) -> bool {
+        if !reply::is_reply_addr(&osc_msg.addr) || self.active(Some(&self.reply_operations)).next().is_none() {
+            return false;
+        }
+        match Reply::from_message(osc_msg) {
+            Ok(parsed) => {
+                for op in self.fire(Some(&self.reply_operations)) {
+                    self.timed_call(&osc_msg.addr, || op(parsed.clone()));
+                }
+            },
+            Err(e) => self.warn(StackWarning::MalformedMessage(osc_msg.addr.clone(), e))
+        }
+        true
+    }
+
+    /*
+        Channel-based alternative to on_message: matching messages are sent to the returned
+            receiver, so that they can be processed on another thread at its own pace.
+        Dropping the receiver simply stops delivery.
+     */
+    pub fn subscribe_message(&mut self, addr: impl Into<OscAddress>) -> Receiver<OscMessage> {
+        let (sender, receiver) = mpsc::channel();
+        let key = self.normalize(addr.into().as_str());
+        self.message_channels.entry(key).or_default().push(sender);
+        receiver
+    }
+
+    // Channel-based alternative to on_tbundle, see subscribe_message
+    pub fn subscribe_tbundle(&mut self, tag: &str) -> Receiver<TaggedBundle> {
+        let (sender, receiver) = mpsc::channel();
+        self.tbundle_channels.entry(tag.to_string()).or_default().push(sender);
+        receiver
+    }
+
+    // Funnel contents of tagged bundle to be interpreted individually
+    // This effectively invalidates any on_tbundle ops for the given bundle tag
+    pub fn funnel_tbundle(mut self, tag: &str) -> OSCStack<'a> {
+
+        self.tbundle_funnels.insert(tag.to_string());
+        self
+    }
+
+    // When funneling, dispatch the packet inside any timed_msg bundle directly instead of
+    //  the timed_msg bundle itself. The time is available to handlers via dispatch_context().
+    pub fn unwrap_timed_funnels(mut self) -> OSCStack<'a> {
+        self.unwrap_timed_funnels = true;
+        self
+    }
+
+    /*
+        All handlers registered within the closure belong to the named group, which can be
+            switched off and on at runtime via StackController::disable_group/enable_group.
+        let stack = stack.group("sampler", |g| g
+            .on_message("/play_sample", &play)
+            .on_message("/stop_sample", &stop));
+     */
+    pub fn group(mut self, name: &str, register: impl FnOnce(OSCStack<'a>) -> OSCStack<'a>) -> OSCStack<'a> {
+        let outer_group = self.current_group.replace(name.to_string());
+        let mut stack = register(self);
+        stack.current_group = outer_group;
+        stack
+    }
+
+    /*
+        All handlers registered within the closure only apply to bundles of the given session
+            (see session.rs), for demultiplexing several sessions sharing the port:
+
+        let stack = stack
+            .session("live-a", |s| s.on_tbundle("note_on", &play_a))
+            .session("live-b", |s| s.on_tbundle("note_on", &play_b));
+
+        Messages and bundles without a session id reach the handlers of all sessions. Handlers
+            registered outside of any session see every session; dispatch_context().session
+            tells them which one a packet belongs to.
+     */
+    pub fn session(mut self, id: &str, register: impl FnOnce(OSCStack<'a>) -> OSCStack<'a>) -> OSCStack<'a> {
+        let outer_session = self.current_session.replace(id.to_string());
+        let mut stack = register(self);
+        stack.current_session = outer_session;
+        stack
+    }
+
+    // Drop bundles scoped to any other session than the given one, counted as filtered
+    pub fn only_session(mut self, id: &str) -> OSCStack<'a> {
+        self.session_filter = Some(id.to_string());
+        self
+    }
+
+    fn route<T>(&self, op: T) -> Route<T> {
+        Route { op, group: self.current_group.clone(), mode: None, expiry: None, session: self.current_session.clone() }
+    }
+
+    fn active_routes<'r, T>(&'r self, routes: Option<&'r Vec<Route<T>>>) -> impl Iterator<Item = &'r Route<T>> + use<'r, 'a, T> {
+        let current_mode = self.controller.mode();
+        let current_session = dispatch_session();
+        routes.into_iter()
+            .flatten()
+            .filter(|route| !route.expired())
+            .filter(|route| route.group.as_ref().is_none_or(|group| self.controller.is_group_enabled(group)))
+            .filter(move |route| route.mode.is_none() || route.mode == current_mode)
+            .filter(move |route| current_session.is_none() || route.session.is_none() || route.session == current_session)
+    }
+
+    // Handlers of the given routes that currently apply
+    fn active<'r, T>(&'r self, routes: Option<&'r Vec<Route<T>>>) -> impl Iterator<Item = &'r T> + use<'r, 'a, T> {
+        self.active_routes(routes).map(|route| &route.op)
+    }
+
+    // As active(), but marks one-shot routes as spent since their handlers are about to be called
+    fn fire<'r, T>(&'r self, routes: Option<&'r Vec<Route<T>>>) -> impl Iterator<Item = &'r T> + use<'r, 'a, T> {
+        self.active_routes(routes).inspect(|route| route.consume()).map(|route| &route.op)
+    }
+
+    // JSON description of all schemas registered with the stack, see schema::describe_schemas
+    pub fn describe_schemas(&self) -> String {
+        schema::describe_schemas(self.message_schemas.values(), self.tbundle_schemas.values())
+    }
+
+    // Swap the configuration of the stack, also while begin() is running, see StackConfig
+    pub fn reload(&self, config: StackConfig) {
+        self.controller.reload(config);
+    }
+
+    // Handle for interacting with the stack from other threads once begin() is running
+    pub fn controller(&self) -> StackController {
+        self.controller.clone()
+    }
+
+    /*
+        Log the bound address, receive buffer and all registered addresses and tags at info
+            level when begin() is called, so that a wrong port or missing registration shows
+            up in the service log right away.
+     */
+    pub fn startup_banner(mut self, enabled: bool) -> OSCStack<'a> {
+        self.startup_banner = enabled;
+        self
+    }
+
+    /*
+        Answer /jdw/echo messages to their sender, see echo.rs. Handlers registered for the
+            address still run as well.
+     */
+    pub fn echo(mut self, enabled: bool) -> OSCStack<'a> {
+        self.echo = enabled;
+        self
+    }
+
+    fn apply_echo(&self, msg: &OscMessage) {
+        let Some(sender) = self.current_sender.get() else { return };
+        let received_at = dispatch_context().received.map_or_else(SystemTime::now, |received| received.wall);
+        let reply = OscPacket::Message(echo::echo_reply(msg, received_at));
+        if let Err(e) = self.send_from_stack(&sender.to_string(), &reply) {
+            warn!("Failed to answer echo from {}: {}", sender, e);
+        }
+    }
+
+    /*
+        Answer /jdw/hello messages to their sender with the capabilities of the stack under
+            the given service name, see hello.rs and OSCStack::capabilities.
+     */
+    pub fn hello(mut self, name: &str) -> OSCStack<'a> {
+        self.hello_name = Some(name.to_string());
+        self
+    }
+
+    // Called with the capabilities of peers, from both their hellos and their replies to ours
+    pub fn on_hello(mut self, operations: &'a dyn Fn(Capabilities)) -> OSCStack<'a> {
+        self.hello_operation = Some(operations);
+        self
+    }
+
+    /*
+        Everything registered with the stack along with the built-ins it currently answers.
+        Named as set with OSCStack::hello, or after the host url otherwise.
+     */
+    pub fn capabilities(&self) -> Capabilities {
+        let mut builtins = vec![SET_MODE_ADDR, CANCEL_ADDR];
+        if self.panic_operation.is_some() {
+            builtins.push(PANIC_ADDR);
+        }
+        if self.echo {
+            builtins.push(ECHO_ADDR);
+        }
+        if self.hello_name.is_some() {
+            builtins.push(HELLO_ADDR);
+        }
+        if !self.reply_operations.is_empty() {
+            builtins.extend([OK_REPLY_ADDR, ERROR_REPLY_ADDR]);
+        }
+
+        Capabilities::new(self.hello_name.as_deref().unwrap_or(&self.host_url))
+            .with_tags(self.registered_tags().into_iter().chain([IMMEDIATE_TAG]))
+            .with_addresses(self.registered_addresses().into_iter().chain(builtins))
+    }
+
+    /*
+        Sends the capabilities of the stack to a peer, which answers them if it has hello enabled.
+        Once begin() is running the hello is sent from the stack's own address, so that the
+            answer reaches on_hello; before that, peers only learn about this stack.
+     */
+    pub fn say_hello(&self, target: &str) -> Result<(), String> {
+        self.send_from_stack(target, &OscPacket::Message(self.capabilities().hello_message()))
+    }
+
+    /*
+        Keep the table up to date with every peer the stack hears from, see peers.rs.
+        Silent peers are swept out while begin() is running.
+     */
+    pub fn track_peers(mut self, table: PeerTable) -> OSCStack<'a> {
+        self.peers = Some(table);
+        self
+    }
+
+    /*
+        Sends an echo carrying the current time to a peer, from the stack's own address once
+            begin() is running. The round trip is recorded in the peer table when the peer
+            answers, see OSCStack::echo and OSCStack::track_peers.
+     */
+    pub fn ping(&self, target: &str) -> Result<(), String> {
+        self.send_from_stack(target, &OscPacket::Message(echo::ping_message()))
+    }
+
+    fn apply_echo_reply(&self, msg: &OscMessage) {
+        let Some(round_trip) = EchoReply::from_message(msg).ok().and_then(|reply| reply.round_trip()) else { return };
+        if let (Some(peers), Some(sender)) = (&self.peers, self.current_sender.get()) {
+            peers.record_latency(sender, round_trip);
+        }
+        if let Some(rate) = &self.adaptive_rate {
+            rate.on_round_trip(round_trip);
+        }
+    }
+
+    /*
+        Adjust the rate with the feedback this stack receives: ok replies, round trips of
+            pings and gap reports from the receiver, see congestion.rs. Clients sending with
+            the same rate then speed up or slow down, see OscClient::with_adaptive_rate.
+        The feedback messages still reach any handlers registered for them.
+     */
+    pub fn adapt_rate(mut self, rate: AdaptiveRate) -> OSCStack<'a> {
+        self.adaptive_rate = Some(rate);
+        self
+    }
+
+    fn apply_rate_feedback(&self, msg: &OscMessage) {
+        let Some(rate) = &self.adaptive_rate else { return };
+        match msg.addr.as_str() {
+            OK_REPLY_ADDR => rate.on_ack(),
+            GAP_REPORT_ADDR => match sequence::reported_missing(msg) {
+                Some(missing) => rate.on_loss(missing),
+                None => self.warn(StackWarning::MalformedMessage(msg.addr.clone(), "expected two sequence numbers".to_string()))
+            },
+            _ => {}
+        }
+    }
+
+    // Tell senders about gaps in their sequence numbers, see sequence.rs
+    pub fn report_gaps(mut self, enabled: bool) -> OSCStack<'a> {
+        self.report_gaps = enabled;
+        self
+    }
+
+    fn apply_hello(&self, msg: &OscMessage) {
+        let capabilities = match Capabilities::from_message(msg) {
+            Ok(capabilities) => capabilities,
+            Err(e) => return self.warn(StackWarning::MalformedMessage(msg.addr.clone(), e))
+        };
+
+        if !capabilities.is_wire_compatible() {
+            warn!("Peer {} uses wire format {}, this service {}", capabilities.name, capabilities.wire_version, WIRE_FORMAT_VERSION);
+        }
+
+        if let (Some(peers), Some(sender)) = (&self.peers, self.current_sender.get()) {
+            peers.set_capabilities(sender, capabilities.clone());
+        }
+
+        if msg.addr == HELLO_ADDR && self.hello_name.is_some() {
+            if let Some(sender) = self.current_sender.get() {
+                let reply = OscPacket::Message(self.capabilities().reply_message());
+                if let Err(e) = self.send_from_stack(&sender.to_string(), &reply) {
+                    warn!("Failed to answer hello from {}: {}", sender, e);
+                }
+            }
+        }
+
+        if let Some(op) = self.hello_operation {
+            op(capabilities);
+        }
+    }
+
+    // Timed handlers are reached through timed_msg bundles rather than their address
+    fn registered_addresses(&self) -> BTreeSet<&str> {
+        self.message_operations.keys()
+            .chain(self.cancellable_operations.keys())
+            .chain(self.message_channels.keys())
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn registered_tags(&self) -> BTreeSet<&str> {
+        let timed = (!self.timed_operations.is_empty()).then_some("timed_msg");
+        self.tbundle_operations.keys()
+            .chain(self.typed_tbundle_operations.keys())
+            .chain(self.tbundle_channels.keys())
+            .chain(self.tbundle_schemas.keys())
+            .chain(self.tbundle_funnels.iter())
+            .map(String::as_str)
+            .chain(timed)
+            .collect()
+    }
+
+    fn log_startup_banner(&self, receiver: &OscReceiver) {
+        let bound = receiver.local_url().unwrap_or_else(|e| format!("{} ({})", self.host_url, e));
+        info!("OSCStack listening on {} with a {} byte receive buffer", bound, receiver.buffer_size());
+
+        let addresses = self.registered_addresses();
+        let tags = self.registered_tags();
+        let timed: BTreeSet<&String> = self.timed_operations.keys().collect();
+
+        info!("Registered addresses ({}): {:?}", addresses.len(), addresses);
+        info!("Registered bundle tags ({}): {:?}", tags.len(), tags);
+        if !timed.is_empty() {
+            info!("Registered timed addresses ({}): {:?}", timed.len(), timed);
+        }
+    }
+
+    // Wire format of incoming datagrams, plain OSC by default
+    pub fn codec(mut self, codec: SharedCodec) -> OSCStack<'a> {
+        self.reply_socket = ReplySocket::new(codec.clone());
+        self.codec = codec;
+        self
+    }
+
+    // Case policy applied to both registered and incoming addresses, Preserve by default
+    // Must be set before any registrations to take effect for them
+    pub fn address_case_policy(mut self, policy: CasePolicy) -> OSCStack<'a> {
+        self.case_policy = policy;
+        self
+    }
+
+    fn normalize(&self, addr: &str) -> String {
+        OscAddress::with_case_policy(addr, self.case_policy).into()
+    }
+
+    pub fn middleware(mut self, operations: Middleware<'a>) -> OSCStack<'a> {
+        self.middleware.push(operations);
+        self
+    }
+
+    fn apply_middleware(&self, packet: OscPacket, origin: &PacketOrigin) -> Option<OscPacket> {
+        self.middleware.iter().try_fold(packet, |packet, op| op(packet, origin))
+    }
+
+    // Fallback for messages and tagged bundles that no registration matched
+    pub fn on_unmatched(mut self, operations: &'a dyn Fn(OscPacket)) -> OSCStack<'a> {
+        self.unmatched_operation = Some(operations);
+        self
+    }
+
+    // How begin() recovers from a failing socket, see SupervisionPolicy
+    pub fn supervision(mut self, policy: SupervisionPolicy) -> OSCStack<'a> {
+        self.supervision = policy;
+        self
+    }
+
+    // Called whenever receiving starts failing, and again once it works after all
+    pub fn on_health_change(mut self, operations: &'a dyn Fn(StackHealth)) -> OSCStack<'a> {
+        self.health_operation = Some(operations);
+        self
+    }
+
+    fn report_health(&self, health: Option<StackHealth>) {
+        let Some(health) = health else { return };
+        match &health {
+            StackHealth::Healthy => info!("OSCStack receiving again"),
+            StackHealth::Unhealthy(_) => warn!("OSCStack is {}", health)
+        }
+        if let Some(op) = self.health_operation {
+            op(health);
+        }
+    }
+
+    /*
+        Rebind the receiver, waiting out the backoff before every attempt. Retries until the
+            socket is back, reporting the stack unhealthy once the policy's attempts are used up.
+     */
+    fn rebind(&self, receiver: &mut OscReceiver, supervisor: &Supervisor) {
+        let policy = supervisor.policy();
+        let mut attempt: u32 = 0;
+        loop {
+            thread::sleep(policy.backoff(attempt));
+            attempt = attempt.saturating_add(1);
+            match receiver.rebind() {
+                Ok(()) => {
+                    info!("Rebound OSCStack socket after {} attempt(s)", attempt);
+                    self.share_socket(receiver);
+                    return;
+                },
+                Err(e) => {
+                    self.warn(StackWarning::ReceiveFailure(e.clone()));
+                    if attempt == policy.rebind_attempts_before_report {
+                        let error = format!("Failed to rebind after {} attempts, still retrying: {}", attempt, e);
+                        self.report_health(Some(StackHealth::Unhealthy(error)));
+                    }
+                }
+            }
+        }
+    }
 
-use log::warn;
-extern crate rosc;
+    // Receive every StackWarning as it happens, in addition to the regular log output
+    pub fn on_warning(mut self, operations: &'a dyn Fn(StackWarning)) -> OSCStack<'a> {
+        self.warning_operation = Some(operations);
+        self
+    }
 
-use std::net::{SocketAddrV4, UdpSocket};
-use std::str::FromStr;
+    /*
+        Follow each DecodeFailure and UntaggedBundle warning with a StackWarning::HexDump of
+            the first max_bytes bytes of the offending packet and its sender, for hunting interop
+            issues with non-JDW OSC senders. Untagged bundles are dumped re-encoded with the
+            stack's codec, which for plain OSC gives back the bytes as received.
+     */
+    pub fn hex_dump_failures(mut self, max_bytes: usize) -> OSCStack<'a> {
+        self.hex_dump_limit = Some(max_bytes);
+        self
+    }
 
-use rosc::{OscPacket, OscMessage};
+    /*
+        Time every handler running on the receive loop (inline handlers, including typed and
+            timed ones) and report calls taking longer than the limit as StackWarning::SlowHandler,
+            before such handlers start causing dropped packets. Timings per address or tag are
+            available from StackController::handler_timings.
+        Parallel and dedicated handlers don't hold up the receive loop and are not timed.
+     */
+    pub fn handler_time_limit(mut self, limit: Duration) -> OSCStack<'a> {
+        self.handler_time_limit = Some(limit);
+        self
+    }
 
-use crate::model::TaggedBundle;
+    /*
+        Cap the approximate memory taken by packets waiting on parallel, dedicated and sharded
+            handlers and in the ordered delivery buffer, so that a stalled handler can't run the
+            process out of memory:
 
-pub struct OSCStack<'a> {
-    message_operations: HashMap<String, &'a dyn Fn(OscMessage)>,
-    tbundle_operations: HashMap<String, &'a dyn Fn(TaggedBundle)>,
-    tbundle_funnels: HashSet<String>,
-    host_url: String
-}
+        stack.memory_budget(64 * 1024 * 1024, EvictionPolicy::DropOldest)
 
-impl <'a> OSCStack<'a> {
-    pub fn init(host_url: String) -> OSCStack<'a> {
-        OSCStack {
-            message_operations: HashMap::new(),
-            tbundle_operations: HashMap::new(),
-            tbundle_funnels: HashSet::new(),
-            host_url
+        Over budget, packets for handler queues are dropped as the policy says (parallel
+            handlers always drop the newest, as the pool's jobs can't be taken back) and reported
+            as StackWarning::Evicted. Packets held for ordered delivery are passed on early
+            instead. The current usage is available from StackController::queued_bytes.
+     */
+    pub fn memory_budget(mut self, max_bytes: usize, policy: EvictionPolicy) -> OSCStack<'a> {
+        self.controller.memory_budget().set_limit(max_bytes);
+        self.eviction_policy = policy;
+        self
+    }
+
+    fn timed_call<R>(&self, key: &str, call: impl FnOnce() -> R) -> R {
+        let Some(limit) = self.handler_time_limit else { return call() };
+
+        let started = Instant::now();
+        let result = call();
+        let elapsed = started.elapsed();
+
+        let slow = elapsed > limit;
+        self.controller.stack_metrics().record_handler_time(key, elapsed, slow);
+        if slow {
+            self.warn(StackWarning::SlowHandler(key.to_string(), elapsed));
         }
+        result
     }
 
-    pub fn on_message(&'a mut self, tag: &str, operations: &'a dyn Fn(OscMessage)) -> &mut OSCStack {
-        self.message_operations.insert(tag.to_string(), operations);
-        self
+    fn warn_hex_dump(&self, bytes: &[u8], sender: Option<SocketAddr>) {
+        if let Some(limit) = self.hex_dump_limit {
+            self.warn(StackWarning::HexDump(sender, hexdump::hex_dump(bytes, limit)));
+        }
     }
 
-    pub fn on_tbundle(&'a mut self, tag: &str, operations: &'a dyn Fn(TaggedBundle))  -> &mut OSCStack {
-        self.tbundle_operations.insert(tag.to_string(), operations);
+    // Attempt tag recovery for bundles without a proper /bundle_info header (see TaggedBundle::new_lenient)
+    // Each recovery is reported as a StackWarning::RecoveredTag
+    pub fn lenient_tagging(mut self, fallback_tag: Option<&str>) -> OSCStack<'a> {
+        self.lenient_tagging = true;
+        self.fallback_tag = fallback_tag.map(|tag| tag.to_string());
         self
     }
 
-    // Funnel contents of tagged bundle to be interpreted individually
-    // This effectively invalidates any on_tbundle ops for the given bundle tag
-    pub fn funnel_tbundle(&'a mut self, tag: &str) -> &mut OSCStack {
+    fn parse_tagged(&self, osc_bundle: &OscBundle) -> Result<TaggedBundle, String> {
+        if !self.lenient_tagging {
+            return TaggedBundle::new(osc_bundle);
+        }
 
-        self.tbundle_funnels.insert(tag.to_string());
-        self
+        let (tagged_bundle, recovery) = TaggedBundle::new_lenient(osc_bundle, self.fallback_tag.as_deref())?;
+        if let Some(recovery) = recovery {
+            self.warn(StackWarning::RecoveredTag(recovery));
+        }
+
+        Ok(tagged_bundle)
+    }
+
+    fn warn(&self, warning: StackWarning) {
+        match &warning {
+            // Unhandled tags are frequently intentional, no need to flood the log
+            StackWarning::UnmatchedTag(_) => debug!("{}", warning),
+            _ => warn!("{}", warning)
+        }
+
+        if let Some(op) = self.warning_operation {
+            op(warning);
+        }
     }
 
-    fn interpret(&self, packet: OscPacket) {
+    /*
+        Run a packet through the registered handlers as if it had been received.
+        Lets embedders feed packets from their own transports (stdin, files, other sockets)
+            instead of, or next to, begin(). Capture and middleware only apply to packets
+            received by begin().
+        Re-entrant: handlers may themselves call interpret, e.g. to re-dispatch rewritten packets.
+     */
+    pub fn interpret(&self, packet: OscPacket) {
         match packet {
             OscPacket::Message(osc_msg) => {
 
-                self.message_operations.get(&osc_msg.addr).map(|op| {
-                    op(osc_msg);
-                });
+                let addr = OscAddress::with_case_policy(&osc_msg.addr, self.case_policy);
+
+                // Built-in; user handlers for the address still run afterwards
+                if addr.as_str() == SET_MODE_ADDR {
+                    self.apply_set_mode(&osc_msg);
+                    if !self.has_message_route(addr.as_str()) {
+                        return;
+                    }
+                }
+
+                if let (PANIC_ADDR, Some(op)) = (addr.as_str(), self.panic_operation) {
+                    self.apply_panic(op);
+                    if !self.has_message_route(addr.as_str()) {
+                        return;
+                    }
+                }
+
+                if self.echo && addr.as_str() == ECHO_ADDR {
+                    self.apply_echo(&osc_msg);
+                    if !self.has_message_route(addr.as_str()) {
+                        return;
+                    }
+                }
+
+                let is_hello = addr.as_str() == HELLO_ADDR && self.hello_name.is_some();
+                let is_peer_hello = matches!(addr.as_str(), HELLO_ADDR | HELLO_REPLY_ADDR)
+                    && (self.hello_operation.is_some() || self.peers.is_some());
+                if is_hello || is_peer_hello {
+                    self.apply_hello(&osc_msg);
+                    if !self.has_message_route(addr.as_str()) {
+                        return;
+                    }
+                }
+
+                if (self.peers.is_some() || self.adaptive_rate.is_some()) && addr.as_str() == ECHO_REPLY_ADDR {
+                    self.apply_echo_reply(&osc_msg);
+                }
+
+                // Acks go on to reply handlers below as well
+                if self.adaptive_rate.is_some() && matches!(addr.as_str(), OK_REPLY_ADDR | GAP_REPORT_ADDR) {
+                    self.apply_rate_feedback(&osc_msg);
+                    if addr.as_str() == GAP_REPORT_ADDR && !self.has_message_route(addr.as_str()) {
+                        return;
+                    }
+                }
+
+                // Built-in as well, trips the token of the operation to cancel
+                if addr.as_str() == CANCEL_ADDR {
+                    self.apply_cancel(&osc_msg);
+                    if !self.has_message_route(addr.as_str()) {
+                        return;
+                    }
+                }
+
+                let replied = self.dispatch_reply(&osc_msg);
+                if self.has_message_route(addr.as_str()) {
+                    self.dispatch_message(addr.as_str(), osc_msg);
+                } else if addr.is_pattern() {
+                    // Incoming pattern addresses dispatch to every matching registration
+                    let temporary: Vec<String> = self.temporary_message_operations.borrow().keys().cloned().collect();
+                    let mut matching: Vec<&String> = self.message_operations.keys()
+                        .chain(temporary.iter())
+                        .chain(self.cancellable_operations.keys())
+                        .chain(self.message_channels.keys())
+                        .filter(|registered| addr.matches(registered))
+                        .collect();
+                    matching.sort();
+                    matching.dedup();
+                    for registered in matching {
+                        self.dispatch_message(registered, osc_msg.clone());
+                    }
+                } else if let (false, Some(op)) = (replied, self.unmatched_operation) {
+                    op(OscPacket::Message(osc_msg));
+                }
 
             },
             OscPacket::Bundle(osc_bundle) => {
 
-                match TaggedBundle::new(&osc_bundle) {
+                if let Some(session) = session::session_id(&osc_bundle) {
+                    if self.session_filter.as_ref().is_some_and(|only| *only != session) {
+                        self.controller.stack_metrics().count_filtered();
+                        return trace!("Dropped bundle of session {}", session);
+                    }
+                    // Dispatch the bundle, including anything unwrapped from it, in its session
+                    if dispatch_session().as_ref() != Some(&session) {
+                        let packet = OscPacket::Bundle(osc_bundle);
+                        return with_dispatch_context(|ctx| ctx.session = Some(session), || self.interpret(packet));
+                    }
+                }
+
+                if deadline::is_expired(&osc_bundle, SystemTime::now()) {
+                    self.controller.stack_metrics().count_expired();
+                    return self.warn(StackWarning::Expired(describe_bundle(&osc_bundle)));
+                }
+
+                if let Some(OscPacket::Message(header)) = osc_bundle.content.first() {
+                    if header.addr == deadline::DEADLINE_ADDR {
+                        for packet in osc_bundle.content.into_iter().skip(1) {
+                            self.interpret(packet);
+                        }
+                        return;
+                    }
+                }
+
+                match self.parse_tagged(&osc_bundle) {
                     Ok(tagged_bundle) => {
 
+                        if let Some(limits) = self.tbundle_limits.get(&tagged_bundle.bundle_tag) {
+                            if let Err(violation) = limits.check(&tagged_bundle) {
+                                return self.warn(StackWarning::LimitExceeded(tagged_bundle.bundle_tag, violation));
+                            }
+                        }
+
+                        if tagged_bundle.bundle_tag == IMMEDIATE_TAG {
+                            return self.dispatch_immediate(tagged_bundle);
+                        }
+
+                        // Bundles that skip dispatch_tbundle still reach StackController::await_tbundle
                         if self.tbundle_funnels.contains(&tagged_bundle.bundle_tag) {
-                            for packet in tagged_bundle.contents {
-                                self.interpret(packet);
+                            self.controller.offer_tbundle(&tagged_bundle);
+                            let tag = tagged_bundle.bundle_tag;
+                            with_dispatch_context(|ctx| ctx.lineage.push(tag), || {
+                                for packet in tagged_bundle.contents {
+                                    self.interpret_funneled(packet);
+                                }
+                            });
+                        } else if tagged_bundle.bundle_tag == "timed_msg" && self.has_timed_operation(&tagged_bundle) {
+                            self.controller.offer_tbundle(&tagged_bundle);
+                            match TimedOSCPacket::from_bundle(tagged_bundle) {
+                                Ok(timed) => self.dispatch_timed(timed),
+                                Err(msg) => self.warn(StackWarning::MalformedBundle("timed_msg".to_string(), msg))
                             }
+                        } else if self.has_tbundle_route(&tagged_bundle.bundle_tag) {
+                            self.dispatch_tbundle(tagged_bundle);
                         } else {
-                            self.tbundle_operations.get(&tagged_bundle.bundle_tag).map(|op| op(tagged_bundle));
+                            self.warn(StackWarning::UnmatchedTag(tagged_bundle.bundle_tag));
+                            if let Some(op) = self.unmatched_operation {
+                                op(OscPacket::Bundle(osc_bundle));
+                            }
                         }
 
                     },
-                    Err(msg) => warn!("Failed to parse bundle as tagged: {}", msg)
+                    Err(msg) => {
+                        self.warn(StackWarning::UntaggedBundle(msg));
+                        if self.hex_dump_limit.is_some() {
+                            match self.codec.encode(&OscPacket::Bundle(osc_bundle)) {
+                                Ok(bytes) => self.warn_hex_dump(&bytes, self.current_sender.get()),
+                                Err(e) => debug!("Could not encode untagged bundle for hex dump: {}", e)
+                            }
+                        }
+                    }
                 };
             }
         };
 
     }
 
-    pub fn begin(&self) {
+    fn has_message_route(&self, key: &str) -> bool {
+        self.message_channels.contains_key(key)
+            || self.active(self.message_operations.get(key)).next().is_some()
+            || self.active(self.temporary_message_operations.borrow().get(key)).next().is_some()
+            || self.active(self.cancellable_operations.get(key)).next().is_some()
+    }
 
+    fn dispatch_message(&self, key: &str, osc_msg: OscMessage) {
+        if log_enabled!(Level::Trace) {
+            if let Some(lineage) = dispatch_context().describe_lineage() {
+                trace!("Dispatching {} from {}", osc_msg.addr, lineage);
+            }
+        }
 
-        let addr = match SocketAddrV4::from_str(&self.host_url) {
-            Ok(addr) => addr,
-            Err(e) => panic!("{}", e),
+        if let Some(schema) = self.message_schemas.get(key) {
+            if let Err(e) = schema.validate_args(&osc_msg) {
+                return self.warn(StackWarning::MalformedMessage(osc_msg.addr, e));
+            }
+        }
+
+        for sender in self.message_channels.get(key).into_iter().flatten() {
+            // A dropped receiver just means the subscriber lost interest
+            let _ = sender.send(osc_msg.clone());
+        }
+
+        for handler in self.fire(self.message_operations.get(key)) {
+            self.call(handler, key, osc_msg.clone());
+        }
+
+        // Taken out first, so that the routes can be pruned once the handlers are done
+        let temporary: Vec<&'a dyn Fn(OscMessage)> = self.fire(self.temporary_message_operations.borrow().get(key)).copied().collect();
+        for op in temporary {
+            self.timed_call(key, || op(osc_msg.clone()));
+        }
+        self.prune_temporary(key);
+
+        for operation in self.fire(self.cancellable_operations.get(key)) {
+            self.start_cancellable(operation, key, osc_msg.clone());
+        }
+    }
+
+    fn start_cancellable(&self, operation: &CancellableOperation<'a>, key: &str, osc_msg: OscMessage) {
+        let Some(id) = (operation.id)(&osc_msg) else {
+            return self.warn(StackWarning::MalformedMessage(key.to_string(), "no operation id to register for cancellation".to_string()));
+        };
+
+        // Finished when the job ends, even if the handler panics
+        let registration = self.controller.cancellations().register_scoped(&id);
+        let op = operation.op.clone();
+        let context = dispatch_context();
+        if let Some(workers) = &self.workers {
+            workers.execute(Box::new(move || {
+                let token = registration.token();
+                with_dispatch_context(|ctx| *ctx = context, || op(osc_msg, token));
+            }));
+        }
+    }
+
+    /*
+        Handle /jdw/panic messages (see immediate.rs) as the big red button: drop everything
+            queued for dedicated and sharded handlers and held for ordered delivery, cancel all
+            operations registered for cancellation, then call the handler, e.g. to send
+            all-notes-off or clear a scheduler. Jobs already handed to the worker pool still run.
+        Without a panic handler, /jdw/panic is an ordinary message.
+     */
+    pub fn on_panic(mut self, operations: &'a dyn Fn()) -> OSCStack<'a> {
+        self.panic_operation = Some(operations);
+        self
+    }
+
+    fn apply_panic(&self, panic_operation: &dyn Fn()) {
+        let queued: usize = self.message_operations.values().flatten().map(|route| clear_queue(&route.op)).sum::<usize>()
+            + self.tbundle_operations.values().flatten().map(|route| clear_queue(&route.op)).sum::<usize>();
+        let cancelled = self.controller.cancellations().cancel_all();
+        self.panic_pending.set(true);
+        warn!("Panic: dropped {} queued packets and cancelled {} operations", queued, cancelled);
+        panic_operation();
+    }
+
+    // Contents are dispatched right away, ahead of anything queued for their handlers
+    fn dispatch_immediate(&self, tagged_bundle: TaggedBundle) {
+        let nested = self.dispatching_immediate.replace(true);
+        for packet in tagged_bundle.contents {
+            self.interpret(immediate::untimed(packet));
+        }
+        self.dispatching_immediate.set(nested);
+    }
+
+    fn apply_cancel(&self, msg: &OscMessage) {
+        match cancel::cancelled_id(msg) {
+            Some(id) if self.controller.cancellations().cancel(id) => info!("Cancelled operation {}", id),
+            Some(id) => debug!("Ignoring cancel for unknown operation {}", id),
+            None => self.warn(StackWarning::MalformedMessage(CANCEL_ADDR.to_string(), "operation id should be a string".to_string()))
+        }
+    }
+
+    fn apply_set_mode(&self, msg: &OscMessage) {
+        match msg.args.first().cloned() {
+            None => self.controller.set_mode(None),
+            Some(arg) => match arg.string() {
+                Some(mode) => self.controller.set_mode(Some(&mode)),
+                None => self.warn(StackWarning::MalformedMessage(
+                    SET_MODE_ADDR.to_string(),
+                    "mode should be a string".to_string()
+                ))
+            }
+        }
+    }
+
+    fn has_tbundle_route(&self, tag: &str) -> bool {
+        self.tbundle_channels.contains_key(tag)
+            || self.controller.awaits_tbundle(tag)
+            || self.active(self.tbundle_operations.get(tag)).next().is_some()
+            || self.active(self.typed_tbundle_operations.get(tag)).next().is_some()
+    }
+
+    fn dispatch_tbundle(&self, tagged_bundle: TaggedBundle) {
+        if log_enabled!(Level::Trace) {
+            if let Some(lineage) = dispatch_context().describe_lineage() {
+                trace!("Dispatching {} bundle from {}", tagged_bundle.bundle_tag, lineage);
+            }
+        }
+
+        if let Some(schema) = self.tbundle_schemas.get(&tagged_bundle.bundle_tag) {
+            if let Err(e) = schema.validate(&tagged_bundle) {
+                self.warn(StackWarning::MalformedBundle(tagged_bundle.bundle_tag, e));
+                return;
+            }
+        }
+
+        for sender in self.tbundle_channels.get(&tagged_bundle.bundle_tag).into_iter().flatten() {
+            let _ = sender.send(tagged_bundle.clone());
+        }
+        self.controller.offer_tbundle(&tagged_bundle);
+
+        for typed_op in self.fire(self.typed_tbundle_operations.get(&tagged_bundle.bundle_tag)) {
+            if let Err(e) = self.timed_call(&tagged_bundle.bundle_tag, || typed_op(tagged_bundle.clone())) {
+                self.warn(StackWarning::MalformedBundle(tagged_bundle.bundle_tag.clone(), e));
+            }
+        }
+
+        for handler in self.fire(self.tbundle_operations.get(&tagged_bundle.bundle_tag)) {
+            self.call(handler, &tagged_bundle.bundle_tag, tagged_bundle.clone());
+        }
+    }
+
+    // Run the handler where it was registered to run, see Handler
+    fn call<T: ApproxSize + Send + 'static>(&self, handler: &Handler<'a, T>, key: &str, arg: T) {
+        match handler {
+            Handler::Inline(op) => self.timed_call(key, || op(arg)),
+            Handler::Contextual(op) => {
+                let context = self.handler_context();
+                self.timed_call(key, || op(arg, &context))
+            },
+            Handler::Parallel(op) => {
+                // Queued jobs can't be taken back, so only the new one can be dropped
+                let budget = self.controller.memory_budget().clone();
+                let Some(reservation) = Reservation::try_new(budget, arg.approx_size()) else {
+                    self.evicted(key, 1);
+                    return;
+                };
+                let op = op.clone();
+                let context = dispatch_context();
+                if let Some(workers) = &self.workers {
+                    workers.execute(Box::new(move || {
+                        let _reservation = reservation;
+                        with_dispatch_context(|ctx| *ctx = context, || op(arg));
+                    }));
+                }
+            },
+            Handler::Dedicated(sender) => self.enqueue(sender, key, arg),
+            Handler::Sharded(shards) => self.enqueue(shards.sender_for(&arg), key, arg)
+        }
+    }
+
+    fn enqueue<T: ApproxSize>(&self, sender: &QueueSender<(T, DispatchContext)>, key: &str, arg: T) {
+        let size = arg.approx_size();
+        if self.dispatching_immediate.get() {
+            if let Err(QueueError::Closed) = sender.send_urgent((arg, dispatch_context()), size) {
+                self.warn(StackWarning::HandlerStopped(key.to_string()));
+            }
+            return;
+        }
+        match sender.send((arg, dispatch_context()), size, self.eviction_policy) {
+            Ok(0) => {},
+            Ok(evicted) => self.evicted(key, evicted),
+            Err(QueueError::OverBudget) => self.evicted(key, 1),
+            Err(QueueError::Closed) => self.warn(StackWarning::HandlerStopped(key.to_string()))
+        }
+    }
+
+    fn handler_context(&self) -> HandlerContext {
+        HandlerContext::new(self.controller.clone(), dispatch_context(), self.reply_socket.clone())
+    }
+
+    fn evicted(&self, key: &str, count: usize) {
+        self.controller.stack_metrics().count_evicted(count as u64);
+        self.warn(StackWarning::Evicted(key.to_string(), count));
+    }
+
+    fn interpret_funneled(&self, packet: OscPacket) {
+        if self.unwrap_timed_funnels {
+            if let OscPacket::Bundle(osc_bundle) = &packet {
+                if let Ok(tagged_bundle) = TaggedBundle::new(osc_bundle) {
+                    if tagged_bundle.bundle_tag == "timed_msg" {
+                        match TimedOSCPacket::from_bundle(tagged_bundle) {
+                            Ok(timed) => self.dispatch_timed(timed),
+                            Err(msg) => self.warn(StackWarning::MalformedBundle("timed_msg".to_string(), msg))
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+
+        self.interpret(packet);
+    }
+
+    fn has_timed_operation(&self, tagged_bundle: &TaggedBundle) -> bool {
+        tagged_bundle.get_message(1)
+            .map(|msg| self.active(self.timed_operations.get(&self.normalize(&msg.addr))).next().is_some())
+            .unwrap_or(false)
+    }
+
+    // Dispatch the wrapped packet of a timed_msg, with its time set in the dispatch context
+    fn dispatch_timed(&self, timed: TimedOSCPacket) {
+        let time = timed.time.clone();
+        let unwrapped = |ctx: &mut DispatchContext| {
+            ctx.time = Some(time);
+            ctx.lineage.push("timed_msg".to_string());
+        };
+        with_dispatch_context(unwrapped, || match timed.packet {
+            OscPacket::Message(msg) => {
+                let key = self.normalize(&msg.addr);
+                let mut ops = self.fire(self.timed_operations.get(&key)).peekable();
+                if ops.peek().is_none() {
+                    return self.interpret(OscPacket::Message(msg));
+                }
+                for op in ops {
+                    self.timed_call(&key, || op(timed.time.clone(), msg.clone()));
+                }
+            },
+            packet => self.interpret(packet)
+        });
+    }
+
+    /*
+        Deliver sequenced bundles with the given tag in sequence order per source. A bundle
+            arriving ahead of a missing predecessor is held for at most max_hold, after which
+            the missing one is given up on. See sequence.rs for the numbering convention.
+        Packets from sources using ordered delivery reach middleware without their raw bytes.
+     */
+    pub fn ordered_delivery(mut self, tag: &str, max_hold: Duration) -> OSCStack<'a> {
+        self.ordered_tags.insert(tag.to_string(), max_hold);
+        self
+    }
+
+    // Sequence number and hold time (None = don't hold) for packets subject to ordered delivery
+    fn ordering_of(&self, packet: &OscPacket) -> Option<(i32, Option<Duration>)> {
+        if self.ordered_tags.is_empty() {
+            return None;
+        }
+
+        let OscPacket::Bundle(bundle) = packet else { return None };
+        if immediate::is_immediate(packet) {
+            return None;
+        }
+        let seq = sequence::sequence_number(bundle)?;
+        let hold = TaggedBundle::new(bundle).ok()
+            .and_then(|tagged| self.ordered_tags.get(&tagged.bundle_tag).copied());
+        Some((seq, hold))
+    }
+
+    // Hand a received packet to capture, middleware, forwarding and finally the registered handlers
+    fn deliver(&self, packet: OscPacket, origin: &PacketOrigin) {
+        if let Some(packet) = self.controller.try_capture(packet)
+            .and_then(|packet| self.apply_middleware(packet, origin)) {
+            self.forward(&packet);
+            self.current_sender.set(origin.sender);
+            let modify = |ctx: &mut DispatchContext| {
+                ctx.received = origin.received;
+                ctx.sender = origin.sender;
+            };
+            with_dispatch_context(modify, || self.interpret(packet));
+            self.current_sender.set(None);
+        }
+    }
+
+    /*
+        Resend received messages with the given address (or matching it, if a pattern) to the
+            target, e.g. "127.0.0.1:57110". Forwarded packets are still dispatched locally.
+        Forwarding rules can be swapped at runtime, see StackController::load_routing_table.
+     */
+    pub fn forward_message(self, addr: impl Into<OscAddress>, target: &str) -> OSCStack<'a> {
+        let key = self.normalize(addr.into().as_str());
+        self.controller.add_forward_rule(ForwardRule { matching: ForwardMatch::Message(key), target: target.to_string() });
+        self
+    }
+
+    // Resend received tagged bundles with the given tag to the target
+    pub fn forward_tbundle(self, tag: &str, target: &str) -> OSCStack<'a> {
+        self.controller.add_forward_rule(ForwardRule { matching: ForwardMatch::Tbundle(tag.to_string()), target: target.to_string() });
+        self
+    }
+
+    fn forward(&self, packet: &OscPacket) {
+        for target in self.controller.forward_targets(packet) {
+            if let Err(e) = self.send_to(&target, packet) {
+                self.warn(StackWarning::ForwardFailure(target, e));
+            }
+        }
+    }
+
+    // Clients are kept per target, so that their sockets are reused; targets come from the forward rules
+    fn send_to(&self, target: &str, packet: &OscPacket) -> Result<(), String> {
+        let mut clients = self.forward_clients.borrow_mut();
+        if !clients.contains_key(target) {
+            let client = OscClient::new(target)?.with_codec(self.codec.clone());
+            clients.insert(target.to_string(), client);
+        }
+        clients[target].send(packet)
+    }
+
+    // As send_to, but from the receiving socket once bound, see ReplySocket
+    fn send_from_stack(&self, target: &str, packet: &OscPacket) -> Result<(), String> {
+        self.reply_socket.send(target, packet)
+    }
+
+    fn share_socket(&self, receiver: &OscReceiver) {
+        self.reply_socket.set(receiver.try_clone_socket().unwrap_or_else(|e| {
+            warn!("Answers will be sent from other sockets: {}", e);
+            None
+        }));
+    }
+
+    // Received packets that passed filtering and rate limiting go on to ordering and dispatch
+    fn accept(&self, packet: OscPacket, origin: &PacketOrigin, reorder: &mut ReorderBuffer) {
+        let Some(sender) = origin.sender else { return self.deliver(packet, origin) };
+        if let Some(SequenceEvent::Restarted(..)) = self.track_sequence(&packet, sender) {
+            for packet in reorder.restart(sender) {
+                self.deliver_ordered(sender, packet);
+            }
+        }
+        match self.ordering_of(&packet) {
+            Some((seq, hold)) => {
+                if let Some(received) = origin.received {
+                    self.held_receive_times.borrow_mut().insert((sender, seq), received);
+                }
+                for packet in reorder.push(sender, seq, packet, hold) {
+                    self.deliver_ordered(sender, packet);
+                }
+            },
+            None => self.deliver(packet, origin)
+        }
+    }
+
+    // Delivers a packet that went through the reorder buffer, with the time it was received
+    fn deliver_ordered(&self, sender: SocketAddr, packet: OscPacket) {
+        let seq = match &packet {
+            OscPacket::Bundle(bundle) => sequence::sequence_number(bundle),
+            OscPacket::Message(_) => None
         };
+        let received = seq.and_then(|seq| self.held_receive_times.borrow_mut().remove(&(sender, seq)));
+        self.deliver(packet, &PacketOrigin { bytes: None, sender: Some(sender), received });
+    }
+
+    // Buffers or drops a packet received while paused, see StackController::pause
+    fn hold_paused(&self, held: &mut VecDeque<PausedPacket>, packet: OscPacket, sender: SocketAddr, received: ReceiveTime) {
+        let metrics = self.controller.stack_metrics();
+        if self.controller.pause_policy() == PausePolicy::Drop {
+            return metrics.count_paused_drop();
+        }
+
+        let budget = self.controller.memory_budget();
+        let size = packet.approx_size();
+        budget.force_reserve(size);
+        held.push_back(PausedPacket { sender, received, packet, size });
 
-        let sock = UdpSocket::bind(addr).unwrap();
+        let mut evicted = 0;
+        while budget.is_exceeded() {
+            let dropped = match self.eviction_policy {
+                EvictionPolicy::DropNewest => held.pop_back(),
+                EvictionPolicy::DropOldest => held.pop_front()
+            };
+            let Some(dropped) = dropped else { break };
+            budget.release(dropped.size);
+            evicted += 1;
+            // Newest first means only the packet that did not fit
+            if self.eviction_policy == EvictionPolicy::DropNewest {
+                break;
+            }
+        }
+        if evicted > 0 {
+            self.evicted("paused stack", evicted);
+        }
+    }
 
-        let mut buf = [0u8; 333072];
+    fn release_paused(&self, held: &mut VecDeque<PausedPacket>, reorder: &mut ReorderBuffer) {
+        if !held.is_empty() {
+            debug!("OSCStack resumed, dispatching {} buffered packets", held.len());
+        }
+        while let Some(paused) = held.pop_front() {
+            self.controller.memory_budget().release(paused.size);
+            let origin = PacketOrigin { bytes: None, sender: Some(paused.sender), received: Some(paused.received) };
+            self.accept(paused.packet, &origin, reorder);
+        }
+    }
 
+    /*
+        Charges the packets held for ordered delivery to the memory budget. While over budget,
+            the longest held packets are passed on early rather than dropped, as they are
+            already late.
+     */
+    fn relieve_reorder_buffer(&self, reorder: &mut ReorderBuffer, charged: &mut usize) {
+        let budget = self.controller.memory_budget();
         loop {
+            budget.adjust(*charged, reorder.held_bytes());
+            *charged = reorder.held_bytes();
+            if *charged == 0 || !budget.is_exceeded() {
+                return;
+            }
+            for (sender, packet) in reorder.release_oldest() {
+                self.deliver_ordered(sender, packet);
+            }
+        }
+    }
+
+    // Report gaps and reordering in the sequence numbers of bundles from the sender
+    fn track_sequence(&self, packet: &OscPacket, sender: SocketAddr) -> Option<SequenceEvent> {
+        let seq = match packet {
+            OscPacket::Bundle(bundle) => sequence::sequence_number(bundle)?,
+            _ => return None
+        };
+
+        let event = self.sequences.borrow_mut().observe(sender, seq);
+        let metrics = self.controller.stack_metrics();
+        match event {
+            SequenceEvent::InOrder => {},
+            SequenceEvent::Gap(expected, received) => {
+                metrics.count_missing(sequence::missing_between(expected, received));
+                self.warn(StackWarning::SequenceGap(sender, expected, received));
+                if self.report_gaps {
+                    let report = OscPacket::Message(sequence::gap_report(expected, received));
+                    if let Err(e) = self.send_from_stack(&sender.to_string(), &report) {
+                        warn!("Failed to report gap to {}: {}", sender, e);
+                    }
+                }
+            },
+            SequenceEvent::Reordered(expected, received) => {
+                metrics.count_reordered();
+                self.warn(StackWarning::SequenceReordered(sender, expected, received));
+            },
+            SequenceEvent::Restarted(expected, received) => {
+                info!("{} restarted its sequence numbers at {} (expected {})", sender, received, expected);
+            }
+        }
+        Some(event)
+    }
+
+    // Count and report signs of lost data in a received datagram
+    fn inspect_datagram(&self, datagram: DatagramInfo) {
+        let metrics = self.controller.stack_metrics();
+        metrics.count_datagram();
+
+        if datagram.possibly_truncated {
+            metrics.count_truncated();
+            self.warn(StackWarning::PossiblyTruncated(datagram.size));
+        }
 
+        if datagram.trailing_bytes > 0 {
+            metrics.count_trailing_bytes();
+            self.warn(StackWarning::TrailingBytes(datagram.trailing_bytes));
+        }
+    }
+
+    pub fn begin(&self) {
+
+        let mut receiver = match OscReceiver::bind(&self.host_url) {
+            Ok(receiver) => receiver.with_codec(self.codec.clone()),
+            Err(e) => panic!("{}", e),
+        };
+
+        // Start from an unseen version so that a config loaded before begin() is applied
+        let mut config_version = u64::MAX;
+        let mut config = StackConfig::default();
+        let mut rate_window = RateWindow::new();
+        let mut reorder = ReorderBuffer::new();
+        // Bytes of held packets currently charged to the memory budget
+        let mut reorder_bytes = 0;
+        let mut held = VecDeque::new();
+        let mut datagram_received = ReceiveTime::now();
+        let mut supervisor = Supervisor::new(self.supervision);
+        let mut last_peer_sweep = Instant::now();
+        self.share_socket(&receiver);
 
-            //let buf = [0u8; rosc::decoder::MTU];
-            // TODO: Compare with size in struct declaration (should be same value)
-            // THe MTU constant is way too low... I think.
-            // Too low results in parts of large packets being dropped before receiving 
-            // Heck, might just be some kind of buffer thing where I'm supposed to read 
-            // multiple things but only end up reading the first.. . 
-            // UPDATE: Found no indication of this in documentation. :c
+        if let Some(initial) = self.controller.config_if_changed(&mut config_version) {
+            receiver = receiver.with_buffer_size(initial.buffer_size);
+            config = initial;
+        }
+
+        if self.startup_banner {
+            self.log_startup_banner(&receiver);
+        }
+
+        loop {
+
+            let paused = self.controller.is_paused();
+            if !paused {
+                self.release_paused(&mut held, &mut reorder);
+            }
 
-            match sock.recv_from(&mut buf) {
-                Ok((size, _)) => {
-                    let (_rem, packet) = rosc::decoder::decode_udp(&buf[..size]).unwrap();
+            // Wake up in time to release packets held for ordered delivery, and to notice a resume
+            let wait = reorder.next_release()
+                .map(|release_at| release_at.saturating_duration_since(Instant::now()))
+                .map(|wait| if paused { wait.min(PAUSE_POLL_INTERVAL) } else { wait })
+                .or(paused.then_some(PAUSE_POLL_INTERVAL))
+                .map(|wait| if self.peers.is_some() { wait.min(PEER_SWEEP_INTERVAL) } else { wait })
+                .or(self.peers.is_some().then_some(PEER_SWEEP_INTERVAL));
+            let received = match wait {
+                Some(wait) => receiver.recv_from_timeout(wait),
+                None => receiver.recv()
+            };
 
-                    self.interpret(packet);
+            // Checked after receiving, so that a reload made while waiting applies to the packet that ended the wait
+            if let Some(reloaded) = self.controller.config_if_changed(&mut config_version) {
+                if reloaded.buffer_size != config.buffer_size {
+                    receiver.set_buffer_size(reloaded.buffer_size);
+                }
+                info!("OSCStack configuration reloaded");
+                config = reloaded;
+            }
 
+            // Packets beyond the first of a datagram were already accounted for with it
+            let new_datagram = match &received {
+                Ok(_) => receiver.last_packet_index() == 0,
+                Err(e) => matches!(e, RecvError::Decode(_))
+            };
+            if new_datagram {
+                datagram_received = ReceiveTime::now();
+                self.inspect_datagram(receiver.last_datagram());
+                if let (Some(peers), Some(sender)) = (&self.peers, receiver.last_sender()) {
+                    peers.seen_at(sender, datagram_received.instant);
                 }
+            }
+
+            match &received {
+                Err(RecvError::Io(e)) => self.report_health(supervisor.failed(e)),
+                _ => self.report_health(supervisor.succeeded())
+            }
+
+            match received {
+                Ok((packet, _)) if config.ignores(&packet) => self.controller.stack_metrics().count_filtered(),
+                Ok((packet, _)) if !immediate::is_immediate(&packet) && !rate_window.admit(config.max_packets_per_second) => {
+                    self.controller.stack_metrics().count_rate_limited();
+                },
+                // Checked again, as the stack may have been paused while waiting for the packet
+                Ok((packet, sender)) if self.controller.is_paused() && !immediate::is_immediate(&packet) => {
+                    self.hold_paused(&mut held, packet, sender, datagram_received);
+                },
+                Ok((packet, sender)) => {
+                    let origin = PacketOrigin { bytes: Some(receiver.last_packet_bytes()), sender: Some(sender), received: Some(datagram_received) };
+                    self.accept(packet, &origin, &mut reorder);
+                },
+                Err(RecvError::Timeout) => {},
+                Err(RecvError::Decode(e)) => {
+                    self.controller.stack_metrics().count_decode_failure();
+                    self.warn(StackWarning::DecodeFailure(e));
+                    self.warn_hex_dump(receiver.last_datagram_bytes(), receiver.last_sender());
+                },
                 Err(e) => {
-                    warn!("Failed to receive from socket {}", e);
+                    self.warn(StackWarning::ReceiveFailure(e.to_string()));
+                    if supervisor.should_rebind() {
+                        self.rebind(&mut receiver, &supervisor);
+                    }
                 }
             };
 
+            if self.panic_pending.replace(false) {
+                for paused in held.drain(..) {
+                    self.controller.memory_budget().release(paused.size);
+                }
+                self.held_receive_times.borrow_mut().clear();
+                let dropped = reorder.clear();
+                if dropped > 0 {
+                    warn!("Panic: dropped {} packets held for ordered delivery", dropped);
+                }
+            }
+
+            for (sender, packet) in reorder.release_expired(Instant::now()) {
+                self.deliver_ordered(sender, packet);
+            }
+            self.relieve_reorder_buffer(&mut reorder, &mut reorder_bytes);
+
+            if let Some(peers) = &self.peers {
+                if last_peer_sweep.elapsed() >= PEER_SWEEP_INTERVAL {
+                    last_peer_sweep = Instant::now();
+                    for peer in peers.expire() {
+                        info!("Peer {} went silent after {:?}", peer.addr, peer.silent_for());
+                    }
+                }
+            }
+
         }
     }
 
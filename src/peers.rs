@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::hello::Capabilities;
+
+/*
+    Live view of the services a stack hears from, by source address, e.g. for showing which
+        JDW services are up in a GUI. OSCStack::track_peers keeps a table up to date:
+        - every datagram marks its sender as seen
+        - hellos and hello replies set the capabilities of their sender (see hello.rs)
+        - echo replies to OSCStack::ping set the round trip latency (see echo.rs)
+    Peers silent for longer than the table's silence limit are dropped on the next sweep,
+        calling the expiry callbacks. Cloned handles share the table, so it can be read
+        from other threads while begin() is running.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+    // As declared in the last hello from the peer, if any
+    pub capabilities: Option<Capabilities>,
+    // Last measured round trip
+    pub latency: Option<Duration>,
+    pub datagrams: u64
+}
+
+impl Peer {
+    fn new(addr: SocketAddr, at: Instant) -> Peer {
+        Peer { addr, first_seen: at, last_seen: at, capabilities: None, latency: None, datagrams: 0 }
+    }
+
+    pub fn silent_for(&self) -> Duration {
+        self.last_seen.elapsed()
+    }
+}
+
+type ExpiryOperation = Arc<dyn Fn(&Peer) + Send + Sync>;
+
+struct PeerState {
+    peers: HashMap<SocketAddr, Peer>,
+    expiry_operations: Vec<ExpiryOperation>
+}
+
+#[derive(Clone)]
+pub struct PeerTable {
+    silence: Duration,
+    state: Arc<Mutex<PeerState>>
+}
+
+impl PeerTable {
+    pub fn new(silence: Duration) -> PeerTable {
+        PeerTable {
+            silence,
+            state: Arc::new(Mutex::new(PeerState { peers: HashMap::new(), expiry_operations: Vec::new() }))
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, PeerState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn silence(&self) -> Duration {
+        self.silence
+    }
+
+    // Called with each peer dropped for going silent, from the thread sweeping the table
+    pub fn on_expired(&self, operations: impl Fn(&Peer) + Send + Sync + 'static) {
+        self.state().expiry_operations.push(Arc::new(operations));
+    }
+
+    // Marks a datagram from the address, returning true if the peer is new
+    pub fn seen(&self, addr: SocketAddr) -> bool {
+        self.seen_at(addr, Instant::now())
+    }
+
+    pub fn seen_at(&self, addr: SocketAddr, at: Instant) -> bool {
+        let mut state = self.state();
+        let is_new = !state.peers.contains_key(&addr);
+        let peer = state.peers.entry(addr).or_insert_with(|| Peer::new(addr, at));
+        peer.last_seen = peer.last_seen.max(at);
+        peer.datagrams += 1;
+        is_new
+    }
+
+    // Ignored for peers not seen yet or already expired
+    pub fn set_capabilities(&self, addr: SocketAddr, capabilities: Capabilities) {
+        if let Some(peer) = self.state().peers.get_mut(&addr) {
+            peer.capabilities = Some(capabilities);
+        }
+    }
+
+    // Ignored for peers not seen yet or already expired
+    pub fn record_latency(&self, addr: SocketAddr, latency: Duration) {
+        if let Some(peer) = self.state().peers.get_mut(&addr) {
+            peer.latency = Some(latency);
+        }
+    }
+
+    pub fn get(&self, addr: SocketAddr) -> Option<Peer> {
+        self.state().peers.get(&addr).cloned()
+    }
+
+    // Sorted by address
+    pub fn peers(&self) -> Vec<Peer> {
+        let mut peers: Vec<Peer> = self.state().peers.values().cloned().collect();
+        peers.sort_by_key(|peer| peer.addr);
+        peers
+    }
+
+    // Peers that declared the bundle tag in their hello
+    pub fn peers_supporting_tag(&self, tag: &str) -> Vec<Peer> {
+        self.peers().into_iter()
+            .filter(|peer| peer.capabilities.as_ref().is_some_and(|capabilities| capabilities.supports_tag(tag)))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().peers.is_empty()
+    }
+
+    // Drops a peer without calling the expiry callbacks, e.g. when it said goodbye
+    pub fn remove(&self, addr: SocketAddr) -> Option<Peer> {
+        self.state().peers.remove(&addr)
+    }
+
+    pub fn expire(&self) -> Vec<Peer> {
+        self.expire_at(Instant::now())
+    }
+
+    // Drops the peers silent for longer than the limit at the given time and reports them
+    pub fn expire_at(&self, now: Instant) -> Vec<Peer> {
+        let (expired, operations) = {
+            let mut state = self.state();
+            let silent: Vec<SocketAddr> = state.peers.values()
+                .filter(|peer| now.saturating_duration_since(peer.last_seen) > self.silence)
+                .map(|peer| peer.addr)
+                .collect();
+            let mut expired: Vec<Peer> = silent.iter().filter_map(|addr| state.peers.remove(addr)).collect();
+            expired.sort_by_key(|peer| peer.addr);
+            (expired, state.expiry_operations.clone())
+        };
+
+        // Outside the lock, so that callbacks can use the table
+        for peer in &expired {
+            for operation in &operations {
+                operation(peer);
+            }
+        }
+        expired
+    }
+}
@@ -0,0 +1,28 @@
+/*
+    One import line for downstream services:
+
+    use jdw_osc_lib::prelude::*;
+
+    Only the commonly used surface is exported here, and paths in this module are kept stable
+        across internal reorganizations. Items follow the enabled feature layers.
+ */
+
+pub use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+pub use crate::{args, assert_osc_eq};
+pub use crate::core::args::{IntoOscArg, NamedVarArgs, OscArgHandler, OscArgWriter};
+pub use crate::core::diff::OscDiff;
+pub use crate::core::tagged::{FromTaggedBundle, TaggedBundle};
+
+#[cfg(feature = "model")]
+pub use crate::model::TimedOSCPacket;
+#[cfg(feature = "model")]
+pub use crate::osc_addr;
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub use crate::client::OscClient;
+
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub use crate::osc_stack::{DispatchContext, OSCStack};
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub use crate::osc_routes;
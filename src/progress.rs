@@ -0,0 +1,73 @@
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::model::{FromTaggedBundle, OscArgHandler, TaggedBundle};
+
+/*
+    Progress convention for long running operations such as NRT renders, so that frontends
+        can show progress bars for any service. The service sends a bundle per update:
+    [/bundle_info, "progress"]
+    [/progress, "render-42", 37.5, "Rendering bar 12 of 32"]
+
+    The id is agreed on with the requester, e.g. sent along with the request, and the
+        percentage runs from 0 to 100. Subscribe to updates with OSCStack::on_progress.
+    For a single update without status text, see reply::progress_reply.
+ */
+
+pub const PROGRESS_TAG: &str = "progress";
+pub const PROGRESS_ADDR: &str = "/progress";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub id: String,
+    pub pct: f32,
+    // Human readable status, may be empty
+    pub message: String
+}
+
+impl Progress {
+    // Percentage is clamped to 0 - 100
+    pub fn new(id: &str, pct: f32, message: &str) -> Progress {
+        Progress { id: id.to_string(), pct: pct.clamp(0.0, 100.0), message: message.to_string() }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pct >= 100.0
+    }
+
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = OscMessage {
+            addr: "/bundle_info".to_string(),
+            args: vec![OscType::String(PROGRESS_TAG.to_string())]
+        };
+
+        let update = OscMessage {
+            addr: PROGRESS_ADDR.to_string(),
+            args: vec![
+                OscType::String(self.id.clone()),
+                OscType::Float(self.pct),
+                OscType::String(self.message.clone())
+            ]
+        };
+
+        OscBundle {
+            timetag: OscTime { seconds: 0, fractional: 1 },
+            content: vec![OscPacket::Message(info), OscPacket::Message(update)]
+        }
+    }
+}
+
+impl FromTaggedBundle for Progress {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        if bundle.bundle_tag != PROGRESS_TAG {
+            return Err(format!("Attempted to parse {} as {} bundle", bundle.bundle_tag, PROGRESS_TAG));
+        }
+
+        let update = bundle.messages_with_addr(PROGRESS_ADDR).next()
+            .ok_or(format!("Progress bundle has no {} message", PROGRESS_ADDR))?;
+        Ok(Progress {
+            id: update.get_string_at(0, "id")?,
+            pct: update.get_float_at(1, "pct")?,
+            message: update.get_string_at(2, "message")?
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::model::{sort_timed, FromTaggedBundle, OscArgHandler, TaggedBundle, TimedOSCPacket};
+
+/*
+    Incremental updates of a running queue (e.g. the loop of a sequencer), so that senders
+        only send what changed instead of the whole queue. Items are timed packets with an
+        id agreed on by the sender:
+
+    [/bundle_info, "queue_add"]           or "queue_replace"
+    [/queue_info, "loop-a"]
+    [
+        [/bundle_info, "queue_item"]
+        [/queue_item_info, "kick-1"]
+        [... timed_msg bundle ...]
+    ]
+    ...
+
+    [/bundle_info, "queue_remove"]
+    [/queue_info, "loop-a"]
+    [/queue_item_ids, "kick-1", "snare-2"]
+
+    Receivers keep their queues in a Queue and apply updates to it, see OSCStack::on_queue_update.
+ */
+
+pub const QUEUE_ADD_TAG: &str = "queue_add";
+pub const QUEUE_REMOVE_TAG: &str = "queue_remove";
+pub const QUEUE_REPLACE_TAG: &str = "queue_replace";
+pub const QUEUE_ITEM_TAG: &str = "queue_item";
+pub const QUEUE_INFO_ADDR: &str = "/queue_info";
+pub const QUEUE_ITEM_INFO_ADDR: &str = "/queue_item_info";
+pub const QUEUE_ITEM_IDS_ADDR: &str = "/queue_item_ids";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueItem {
+    pub id: String,
+    pub packet: TimedOSCPacket
+}
+
+impl QueueItem {
+    pub fn new(id: &str, packet: TimedOSCPacket) -> QueueItem {
+        QueueItem { id: id.to_string(), packet }
+    }
+
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = OscMessage { addr: QUEUE_ITEM_INFO_ADDR.to_string(), args: vec![OscType::String(self.id.clone())] };
+        TaggedBundle::with_contents(QUEUE_ITEM_TAG, vec![OscPacket::Message(info), OscPacket::Bundle(self.packet.to_bundle())]).to_bundle()
+    }
+}
+
+impl FromTaggedBundle for QueueItem {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        if bundle.bundle_tag != QUEUE_ITEM_TAG {
+            return Err(format!("Attempted to parse {} as {} bundle", bundle.bundle_tag, QUEUE_ITEM_TAG));
+        }
+
+        let info = bundle.get_message(0)?;
+        info.expect_addr(QUEUE_ITEM_INFO_ADDR)?;
+        let packet = TimedOSCPacket::from_bundle(TaggedBundle::new(&bundle.get_bundle(1)?)?)?;
+        Ok(QueueItem { id: info.get_string_at(0, "id")?, packet })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueUpdate {
+    // New items, appended to the queue
+    Add { queue: String, items: Vec<QueueItem> },
+    Remove { queue: String, ids: Vec<String> },
+    // New versions of items already in the queue, keeping their place
+    Replace { queue: String, items: Vec<QueueItem> }
+}
+
+impl QueueUpdate {
+    // Name of the queue the update applies to
+    pub fn queue(&self) -> &str {
+        match self {
+            QueueUpdate::Add { queue, .. } | QueueUpdate::Remove { queue, .. } | QueueUpdate::Replace { queue, .. } => queue
+        }
+    }
+
+    /*
+        Splits the update into updates of at most max_items items or ids each, e.g. to keep
+            datagrams small. Each part still applies all or nothing, but the whole no longer does.
+     */
+    pub fn chunks(&self, max_items: usize) -> Vec<QueueUpdate> {
+        let max_items = max_items.max(1);
+        let queue = self.queue().to_string();
+        let parts: Vec<QueueUpdate> = match self {
+            QueueUpdate::Add { items, .. } => items.chunks(max_items)
+                .map(|items| QueueUpdate::Add { queue: queue.clone(), items: items.to_vec() })
+                .collect(),
+            QueueUpdate::Replace { items, .. } => items.chunks(max_items)
+                .map(|items| QueueUpdate::Replace { queue: queue.clone(), items: items.to_vec() })
+                .collect(),
+            QueueUpdate::Remove { ids, .. } => ids.chunks(max_items)
+                .map(|ids| QueueUpdate::Remove { queue: queue.clone(), ids: ids.to_vec() })
+                .collect()
+        };
+
+        // Empty updates are kept as they are
+        if parts.is_empty() { vec![self.clone()] } else { parts }
+    }
+
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = OscPacket::Message(OscMessage {
+            addr: QUEUE_INFO_ADDR.to_string(),
+            args: vec![OscType::String(self.queue().to_string())]
+        });
+
+        let (tag, contents) = match self {
+            QueueUpdate::Add { items, .. } => (QUEUE_ADD_TAG, item_bundles(items)),
+            QueueUpdate::Replace { items, .. } => (QUEUE_REPLACE_TAG, item_bundles(items)),
+            QueueUpdate::Remove { ids, .. } => (QUEUE_REMOVE_TAG, vec![OscPacket::Message(OscMessage {
+                addr: QUEUE_ITEM_IDS_ADDR.to_string(),
+                args: ids.iter().map(|id| OscType::String(id.clone())).collect()
+            })])
+        };
+
+        TaggedBundle::with_contents(tag, std::iter::once(info).chain(contents).collect()).to_bundle()
+    }
+}
+
+fn item_bundles(items: &[QueueItem]) -> Vec<OscPacket> {
+    items.iter().map(|item| OscPacket::Bundle(item.to_bundle())).collect()
+}
+
+fn parse_items(bundle: &TaggedBundle) -> Result<Vec<QueueItem>, String> {
+    bundle.iter_bundles()
+        .map(|item| QueueItem::from_tagged_bundle(TaggedBundle::new(item)?))
+        .collect()
+}
+
+impl FromTaggedBundle for QueueUpdate {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        let info = bundle.get_message(0)?;
+        info.expect_addr(QUEUE_INFO_ADDR)?;
+        let queue = info.get_string_at(0, "queue")?;
+
+        match bundle.bundle_tag.as_str() {
+            QUEUE_ADD_TAG => Ok(QueueUpdate::Add { queue, items: parse_items(&bundle)? }),
+            QUEUE_REPLACE_TAG => Ok(QueueUpdate::Replace { queue, items: parse_items(&bundle)? }),
+            QUEUE_REMOVE_TAG => {
+                let ids = bundle.messages_with_addr(QUEUE_ITEM_IDS_ADDR).next()
+                    .ok_or(format!("Queue remove bundle has no {} message", QUEUE_ITEM_IDS_ADDR))?;
+                let ids = (0..ids.args.len())
+                    .map(|index| ids.get_string_at(index, "id"))
+                    .collect::<Result<Vec<String>, String>>()?;
+                Ok(QueueUpdate::Remove { queue, ids })
+            },
+            other => Err(format!("Attempted to parse {} as queue update bundle", other))
+        }
+    }
+}
+
+/*
+    Receiving end of queue updates for the named queue. Updates apply all or nothing: adding
+        an id that is already queued or replacing one that is not fails without changing the
+        queue, as does any update for another queue. Removing ids that are not queued is fine,
+        as they may have been removed already.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Queue {
+    name: String,
+    items: Vec<QueueItem>
+}
+
+impl Queue {
+    pub fn new(name: &str) -> Queue {
+        Queue { name: name.to_string(), items: Vec::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn apply(&mut self, update: QueueUpdate) -> Result<(), String> {
+        if update.queue() != self.name {
+            return Err(format!("Update for queue {} does not apply to queue {}", update.queue(), self.name));
+        }
+
+        match update {
+            QueueUpdate::Add { items, .. } => {
+                let duplicates: Vec<&str> = items.iter()
+                    .enumerate()
+                    .filter(|(index, item)| self.contains(&item.id) || items[..*index].iter().any(|earlier| earlier.id == item.id))
+                    .map(|(_, item)| item.id.as_str())
+                    .collect();
+                if !duplicates.is_empty() {
+                    return Err(format!("Queue already has items {}", duplicates.join(", ")));
+                }
+                self.items.extend(items);
+            },
+            QueueUpdate::Replace { items, .. } => {
+                let missing: Vec<&str> = items.iter()
+                    .filter(|item| !self.contains(&item.id))
+                    .map(|item| item.id.as_str())
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(format!("Queue has no items {} to replace", missing.join(", ")));
+                }
+                for item in items {
+                    if let Some(queued) = self.items.iter_mut().find(|queued| queued.id == item.id) {
+                        *queued = item;
+                    }
+                }
+            },
+            QueueUpdate::Remove { ids, .. } => self.items.retain(|item| !ids.contains(&item.id))
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.items.iter().any(|item| item.id == id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&QueueItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    // In the order they were added
+    pub fn items(&self) -> &[QueueItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // The queued packets in execution order, see sort_timed
+    pub fn packets(&self) -> Vec<TimedOSCPacket> {
+        let mut packets: Vec<TimedOSCPacket> = self.items.iter().map(|item| item.packet.clone()).collect();
+        sort_timed(&mut packets);
+        packets
+    }
+}
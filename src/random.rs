@@ -0,0 +1,43 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+    Small seedable random source (SplitMix64) for generative features such as timed packet
+        probabilities. Equal seeds give equal sequences on every platform, so JDW tools
+        sharing a seed make the same random choices.
+ */
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng { state: seed }
+    }
+
+    // Seeded from the current time, for when reproducibility does not matter
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_time() -> SeededRng {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        SeededRng::new(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0.0, 1.0)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // True with the given probability (0.0 - 1.0)
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f64() < probability as f64
+    }
+}
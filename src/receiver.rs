@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::ops::Range;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use rosc::OscPacket;
+
+use crate::codec::{default_codec, SharedCodec};
+use crate::local::{self, LocalEndpoint, LOCAL_SCHEME, LOCAL_SENDER};
+
+/*
+    Lowest level of incoming OSC: a bound socket that decodes one packet at a time.
+    Some senders pack several packets into a single datagram; these are all decoded and
+        handed out by consecutive recv calls before the socket is read again.
+    OSCStack is built on top of this; use it directly when registering handlers and
+        entering the receive loop is overkill, e.g. waiting for a single reply.
+    Binding "local:<name>" instead of a network address receives from in-process clients,
+        see local.rs.
+
+    let mut receiver = OscReceiver::bind("127.0.0.1:13338")?;
+    let reply = receiver.recv_timeout(Duration::from_secs(2))?;
+ */
+
+// Well above rosc::decoder::MTU, which truncates large packets
+pub const DEFAULT_BUFFER_SIZE: usize = 333072;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecvError {
+    // Nothing arrived within the given time
+    Timeout,
+    // Socket read failed
+    Io(String),
+    // Received bytes could not be decoded as an OSC packet
+    Decode(String)
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Timeout => write!(f, "Timed out waiting for packet"),
+            RecvError::Io(e) => write!(f, "Failed to receive from socket {}", e),
+            RecvError::Decode(e) => write!(f, "Failed to decode packet: {}", e)
+        }
+    }
+}
+
+// Size details of the most recently received datagram, for diagnosing lost data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DatagramInfo {
+    pub size: usize,
+    // The datagram filled the whole buffer, so the OS has likely discarded the rest of it
+    pub possibly_truncated: bool,
+    // Amount of packets decoded from the datagram
+    pub packets: usize,
+    // Bytes left over that could not be decoded as another packet
+    pub trailing_bytes: usize
+}
+
+enum Transport {
+    Udp {
+        socket: UdpSocket,
+        // Address the socket is bound to, including the port picked by the OS when binding port 0
+        bound_addr: SocketAddr
+    },
+    Local(LocalEndpoint)
+}
+
+pub struct OscReceiver {
+    transport: Transport,
+    buffer: Vec<u8>,
+    last_datagram: DatagramInfo,
+    codec: SharedCodec,
+    // Decoded packets of the last datagram not yet handed out, with their buffer positions
+    pending: VecDeque<(OscPacket, Range<usize>)>,
+    sender: Option<SocketAddr>,
+    last_packet: Range<usize>,
+    requested_buffer_size: Option<usize>
+}
+
+impl OscReceiver {
+    pub fn bind(host_url: &str) -> Result<OscReceiver, String> {
+        let transport = match local::local_name(host_url) {
+            Some(name) => Transport::Local(LocalEndpoint::bind(name)?),
+            None => {
+                let addr = host_url.to_socket_addrs()
+                    .map_err(|e| format!("Invalid host address {}: {}", host_url, e))?
+                    .next()
+                    .ok_or(format!("Host address {} did not resolve", host_url))?;
+
+                let socket = UdpSocket::bind(addr).map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+                let bound_addr = socket.local_addr().unwrap_or(addr);
+                Transport::Udp { socket, bound_addr }
+            }
+        };
+
+        Ok(OscReceiver {
+            transport,
+            buffer: vec![0u8; DEFAULT_BUFFER_SIZE],
+            last_datagram: DatagramInfo::default(),
+            codec: default_codec(),
+            pending: VecDeque::new(),
+            sender: None,
+            last_packet: 0..0,
+            requested_buffer_size: None
+        })
+    }
+
+    // Datagrams larger than the buffer are truncated by the OS
+    pub fn with_buffer_size(mut self, size: usize) -> OscReceiver {
+        self.buffer = vec![0u8; size];
+        self
+    }
+
+    // Decode datagrams with something other than plain OSC, see PacketCodec
+    pub fn with_codec(mut self, codec: SharedCodec) -> OscReceiver {
+        self.codec = codec;
+        self
+    }
+
+    // As with_buffer_size, but takes effect once the packets of the current datagram are handed out
+    pub fn set_buffer_size(&mut self, size: usize) {
+        self.requested_buffer_size = Some(size);
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Also updated when decoding the datagram failed
+    pub fn last_datagram(&self) -> DatagramInfo {
+        self.last_datagram
+    }
+
+    /*
+        Encoded bytes of the most recently decoded packet, exactly as received.
+        Lets proxies and loggers forward or checksum packets without re-encoding them.
+     */
+    pub fn last_packet_bytes(&self) -> &[u8] {
+        &self.buffer[self.last_packet.clone()]
+    }
+
+    // Whole most recently received datagram, also after decoding it failed
+    pub fn last_datagram_bytes(&self) -> &[u8] {
+        &self.buffer[..self.last_datagram.size]
+    }
+
+    // Sender of the most recently received datagram
+    pub fn last_sender(&self) -> Option<SocketAddr> {
+        self.sender
+    }
+
+    // Position of the most recent packet within its datagram, 0 for the first (or only) one
+    pub fn last_packet_index(&self) -> usize {
+        self.last_datagram.packets.saturating_sub(self.pending.len() + 1)
+    }
+
+    // Local endpoints have no address of their own, see local_url
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        match &self.transport {
+            Transport::Udp { socket, .. } => socket.local_addr().map_err(|e| e.to_string()),
+            Transport::Local(endpoint) => Err(format!("{}{} is not a network address", LOCAL_SCHEME, endpoint.name()))
+        }
+    }
+
+    // Another handle on the bound socket, for sending from the address peers answer to; None for local transports
+    pub fn try_clone_socket(&self) -> Result<Option<UdpSocket>, String> {
+        match &self.transport {
+            Transport::Udp { socket, .. } => socket.try_clone().map(Some).map_err(|e| format!("Failed to clone socket: {}", e)),
+            Transport::Local(_) => Ok(None)
+        }
+    }
+
+    // Url that clients can send to in order to reach this receiver
+    pub fn local_url(&self) -> Result<String, String> {
+        match &self.transport {
+            Transport::Local(endpoint) => Ok(format!("{}{}", LOCAL_SCHEME, endpoint.name())),
+            Transport::Udp { .. } => self.local_addr().map(|addr| addr.to_string())
+        }
+    }
+
+    /*
+        Replace the socket with a freshly bound one on the same address, e.g. after the network
+            interface went down. Packets still pending from the last datagram are discarded.
+        On failure the receiver is left without a working socket; retry until it succeeds.
+     */
+    pub fn rebind(&mut self) -> Result<(), String> {
+        self.pending.clear();
+
+        // Local endpoints can't fail in a way that rebinding fixes
+        let Transport::Udp { socket, bound_addr } = &mut self.transport else { return Ok(()) };
+
+        // The old socket has to be closed before its address can be bound again
+        let placeholder_addr = if bound_addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let placeholder = UdpSocket::bind(placeholder_addr).map_err(|e| format!("Failed to release socket: {}", e))?;
+        drop(std::mem::replace(socket, placeholder));
+
+        *socket = UdpSocket::bind(*bound_addr).map_err(|e| format!("Failed to rebind {}: {}", bound_addr, e))?;
+        Ok(())
+    }
+
+    // Block until a packet arrives
+    pub fn recv(&mut self) -> Result<(OscPacket, SocketAddr), RecvError> {
+        if let Some(pending) = self.next_pending() {
+            return Ok(pending);
+        }
+        self.recv_packet(None)
+    }
+
+    // Block until a packet arrives or the timeout passes
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<OscPacket, RecvError> {
+        self.recv_from_timeout(timeout).map(|(packet, _)| packet)
+    }
+
+    // As recv_timeout, but also returning the sender like recv
+    pub fn recv_from_timeout(&mut self, timeout: Duration) -> Result<(OscPacket, SocketAddr), RecvError> {
+        if let Some(pending) = self.next_pending() {
+            return Ok(pending);
+        }
+        // A zero duration is rejected by the socket, the shortest possible wait is the closest match
+        self.recv_packet(Some(timeout.max(Duration::from_nanos(1))))
+    }
+
+    /*
+        Endless blocking iterator over incoming packets, for use with standard combinators:
+        for packet in receiver.packets().filter_map(Result::ok) { ... }
+     */
+    pub fn packets(&mut self) -> Packets<'_> {
+        Packets { receiver: self }
+    }
+
+    // Read the next datagram into the buffer, returning its size and sender
+    fn recv_datagram(&mut self, timeout: Option<Duration>) -> Result<(usize, SocketAddr), RecvError> {
+        match &self.transport {
+            Transport::Udp { socket, .. } => {
+                socket.set_read_timeout(timeout).map_err(|e| RecvError::Io(e.to_string()))?;
+                socket.recv_from(&mut self.buffer).map_err(|e| match e.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut => RecvError::Timeout,
+                    _ => RecvError::Io(e.to_string())
+                })
+            },
+            Transport::Local(endpoint) => {
+                let bytes = endpoint.recv(timeout).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => RecvError::Timeout,
+                    RecvTimeoutError::Disconnected => RecvError::Io("Local endpoint disconnected".to_string())
+                })?;
+                // Truncated like an oversized UDP datagram would be
+                let size = bytes.len().min(self.buffer.len());
+                self.buffer[..size].copy_from_slice(&bytes[..size]);
+                Ok((size, LOCAL_SENDER))
+            }
+        }
+    }
+
+    fn recv_packet(&mut self, timeout: Option<Duration>) -> Result<(OscPacket, SocketAddr), RecvError> {
+        if let Some(size) = self.requested_buffer_size.take() {
+            self.buffer = vec![0u8; size];
+        }
+
+        let (size, source) = self.recv_datagram(timeout)?;
+
+        self.last_datagram = DatagramInfo {
+            size,
+            possibly_truncated: size >= self.buffer.len(),
+            packets: 0,
+            trailing_bytes: 0
+        };
+        self.sender = Some(source);
+        self.last_packet = 0..0;
+
+        // Only a failure to decode the first packet fails the datagram, later garbage is trailing
+        let mut start = 0;
+        while start < size {
+            match self.codec.decode(&self.buffer[start..size]) {
+                // A codec consuming nothing would otherwise loop forever
+                Ok((rem, _)) if rem.len() == size - start => break,
+                Ok((rem, packet)) => {
+                    let end = size - rem.len();
+                    self.pending.push_back((packet, start..end));
+                    start = end;
+                },
+                Err(e) if self.pending.is_empty() => return Err(RecvError::Decode(e)),
+                Err(_) => break
+            }
+        }
+        self.last_datagram.packets = self.pending.len();
+        self.last_datagram.trailing_bytes = size - start;
+
+        self.next_pending().ok_or(RecvError::Decode("Empty datagram".to_string()))
+    }
+
+    fn next_pending(&mut self) -> Option<(OscPacket, SocketAddr)> {
+        let (packet, range) = self.pending.pop_front()?;
+        self.last_packet = range;
+        Some((packet, self.sender?))
+    }
+}
+
+pub struct Packets<'r> {
+    receiver: &'r mut OscReceiver
+}
+
+impl Iterator for Packets<'_> {
+    type Item = Result<(OscPacket, SocketAddr), RecvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.receiver.recv())
+    }
+}
@@ -0,0 +1,61 @@
+use std::io::BufRead;
+use std::thread;
+
+use rosc::OscPacket;
+
+use crate::client::OscClient;
+use crate::osc_stack::OSCStack;
+use crate::text::{to_text, TextPacketReader};
+
+/*
+    Interactive console: packets written in the text protocol (see text.rs) are sent
+        through an OscClient, and anything arriving on the reply address is printed.
+
+    Repl::new(OscClient::new("127.0.0.1:13339")?)
+        .listen_on("127.0.0.1:13338")
+        .run(std::io::stdin().lock())
+ */
+pub struct Repl {
+    client: OscClient,
+    reply_url: Option<String>
+}
+
+impl Repl {
+    pub fn new(client: OscClient) -> Repl {
+        Repl { client, reply_url: None }
+    }
+
+    // Print all packets received on the given address while the repl runs
+    pub fn listen_on(mut self, reply_url: &str) -> Repl {
+        self.reply_url = Some(reply_url.to_string());
+        self
+    }
+
+    // Reads until end of input; invalid lines are reported and skipped
+    pub fn run<R: BufRead>(self, input: R) -> Result<(), String> {
+        if let Some(url) = self.reply_url.clone() {
+            thread::Builder::new()
+                .name("jdw-repl-listener".to_string())
+                .spawn(move || {
+                    let print = |packet: OscPacket| println!("< {}", to_text(&packet));
+                    OSCStack::init(url)
+                        .lenient_tagging(Some("untagged"))
+                        .on_unmatched(&print)
+                        .begin();
+                })
+                .map_err(|e| format!("Failed to start reply listener: {}", e))?;
+        }
+
+        for packet in TextPacketReader::new(input) {
+            match packet {
+                Ok(packet) => match self.client.send(&packet) {
+                    Ok(()) => println!("> {}", to_text(&packet)),
+                    Err(e) => eprintln!("! {}", e)
+                },
+                Err(e) => eprintln!("! {}", e)
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use rosc::{OscBundle, OscMessage, OscType};
+
+use crate::core::args::OscArgHandler;
+use crate::progress::Progress;
+
+/*
+    Reply convention for JDW services answering requests, so that callers can handle the
+        answers of any service the same way (see OSCStack::on_reply):
+
+    ["/jdw/reply/ok", "/nrt_record"]
+    ["/jdw/reply/error", "/nrt_record", "No synth named blip"]
+
+    Ok and error replies name the address of the request they answer. Progress is not a reply
+        of its own but follows the bundle convention in progress.rs, received with
+        OSCStack::on_progress.
+ */
+
+pub const OK_REPLY_ADDR: &str = "/jdw/reply/ok";
+pub const ERROR_REPLY_ADDR: &str = "/jdw/reply/error";
+
+pub fn ok_reply(for_msg: &OscMessage) -> OscMessage {
+    Reply::Ok { request: for_msg.addr.clone() }.to_message()
+}
+
+pub fn error_reply(for_msg: &OscMessage, reason: &str) -> OscMessage {
+    Reply::Error { request: for_msg.addr.clone(), reason: reason.to_string() }.to_message()
+}
+
+// Progress bundle without status text, see progress.rs. Percentage is clamped to 0 - 100
+pub fn progress_reply(id: &str, pct: f32) -> OscBundle {
+    Progress::new(id, pct, "").to_bundle()
+}
+
+pub fn is_reply_addr(addr: &str) -> bool {
+    matches!(addr, OK_REPLY_ADDR | ERROR_REPLY_ADDR)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Ok { request: String },
+    Error { request: String, reason: String }
+}
+
+impl Reply {
+    pub fn from_message(msg: &OscMessage) -> Result<Reply, String> {
+        match msg.addr.as_str() {
+            OK_REPLY_ADDR => Ok(Reply::Ok { request: msg.get_string_at(0, "request")? }),
+            ERROR_REPLY_ADDR => Ok(Reply::Error {
+                request: msg.get_string_at(0, "request")?,
+                reason: msg.get_string_at(1, "reason")?
+            }),
+            other => Err(format!("{} is not a reply address", other))
+        }
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        match self {
+            Reply::Ok { request } => OscMessage {
+                addr: OK_REPLY_ADDR.to_string(),
+                args: vec![OscType::String(request.clone())]
+            },
+            Reply::Error { request, reason } => OscMessage {
+                addr: ERROR_REPLY_ADDR.to_string(),
+                args: vec![OscType::String(request.clone()), OscType::String(reason.clone())]
+            }
+        }
+    }
+}
+
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Ok { request } => write!(f, "{} succeeded", request),
+            Reply::Error { request, reason } => write!(f, "{} failed: {}", request, reason)
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt::Debug;
+
+use rosc::{OscBundle, OscMessage, OscPacket};
+
+use crate::core::diff::OscDiff;
+use crate::envelope::Envelope;
+use crate::model::{FromTaggedBundle, TaggedBundle, TimedOSCPacket};
+use crate::progress::Progress;
+use crate::reply::Reply;
+use crate::supercollider::{BAllocRead, DRecv, NFree, NSet, SNew};
+use crate::queue_update::{QueueItem, QueueUpdate};
+use crate::tracks::{Project, Track};
+
+/*
+    Canonical build/parse pair of every model type sent as a whole packet. The crate
+        guarantees for each of them that, for any value its builders can produce:
+        1. parse(build(x)) == x, also after encoding and decoding the packet
+        2. build(parse(build(x))) gives the exact same packet, so values can be relayed
+            without drifting
+
+    Known limits of what builders can produce:
+        - Floats must not be NaN, which never equals itself
+        - Blobs must not be empty, rosc fails to decode the empty blobs it encodes
+        - TimedOSCPacket metadata must not use the reserved "order" key
+        - TimedOSCPacket times are sent with at most 9 decimals (see TimePolicy), so finer
+            times come back rounded
+        - TimedOSCPacket orders are sent as floats, so orders beyond +-2^24 come back rounded
+            to the nearest float
+        - BigDecimal values compare by value, so "0.50" may come back as "0.5"
+
+    roundtrip() checks both invariants for a value and is meant for tests:
+
+    roundtrip(&SNew::new("blip", 1001).with_control("freq", 440.0))?;
+ */
+pub trait Roundtrip: Sized + PartialEq + Debug {
+    fn build(&self) -> OscPacket;
+    fn parse(packet: &OscPacket) -> Result<Self, String>;
+}
+
+// Checks the invariants above for the value, returning the parsed copy
+pub fn roundtrip<T: Roundtrip>(value: &T) -> Result<T, String> {
+    let built = value.build();
+    let bytes = rosc::encoder::encode(&built).map_err(|e| format!("Failed to encode {:?}: {}", value, e))?;
+    let (_, decoded) = rosc::decoder::decode_udp(&bytes).map_err(|e| format!("Failed to decode {:?}: {}", value, e))?;
+
+    let parsed = T::parse(&decoded).map_err(|e| format!("Failed to parse {:?} back: {}", value, e))?;
+    if &parsed != value {
+        return Err(format!("Parsed back differently\n  built:  {:?}\n  parsed: {:?}", value, parsed));
+    }
+
+    match built.osc_diff(&parsed.build()) {
+        Some(report) => Err(format!("Rebuilding the parsed value gave a different packet\n{}", report)),
+        None => Ok(parsed)
+    }
+}
+
+fn expect_message(packet: &OscPacket) -> Result<&OscMessage, String> {
+    match packet {
+        OscPacket::Message(msg) => Ok(msg),
+        OscPacket::Bundle(_) => Err("Expected a message, got a bundle".to_string())
+    }
+}
+
+fn expect_bundle(packet: &OscPacket) -> Result<&OscBundle, String> {
+    match packet {
+        OscPacket::Bundle(bundle) => Ok(bundle),
+        OscPacket::Message(msg) => Err(format!("Expected a bundle, got message {}", msg.addr))
+    }
+}
+
+impl Roundtrip for TaggedBundle {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        TaggedBundle::new(expect_bundle(packet)?)
+    }
+}
+
+impl Roundtrip for TimedOSCPacket {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        TimedOSCPacket::from_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+impl Roundtrip for Progress {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        Progress::from_tagged_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+impl Roundtrip for Track {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        Track::from_tagged_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+impl Roundtrip for Project {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        Project::from_tagged_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+impl Roundtrip for QueueItem {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        QueueItem::from_tagged_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+impl Roundtrip for QueueUpdate {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        QueueUpdate::from_tagged_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+impl Roundtrip for Envelope {
+    fn build(&self) -> OscPacket {
+        OscPacket::Bundle(self.to_bundle())
+    }
+
+    fn parse(packet: &OscPacket) -> Result<Self, String> {
+        Envelope::from_tagged_bundle(TaggedBundle::new(expect_bundle(packet)?)?)
+    }
+}
+
+// SuperCollider commands and replies all pair to_message with from_message
+macro_rules! message_roundtrip {
+    ($($command:ty),*) => {
+        $(
+        impl Roundtrip for $command {
+            fn build(&self) -> OscPacket {
+                OscPacket::Message(self.to_message())
+            }
+
+            fn parse(packet: &OscPacket) -> Result<Self, String> {
+                <$command>::from_message(expect_message(packet)?)
+            }
+        }
+        )*
+    };
+}
+
+message_roundtrip!(SNew, NSet, NFree, BAllocRead, DRecv, Reply);
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::fs;
+
+use rosc::OscPacket;
+
+use crate::address::OscAddress;
+use crate::model::TaggedBundle;
+
+/*
+    Forwarding rules of a router-style OSCStack, see OSCStack::forward_message/forward_tbundle.
+    The table is swapped as a whole at runtime via StackController::load_routing_table, and
+        can be stored in and loaded from a plain text config file with one rule per line:
+
+    # kind    address/tag           target
+    message   /synth/{kick,snare}   127.0.0.1:57110
+    tbundle   nrt_record_request    127.0.0.1:13331
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForwardMatch {
+    // Messages with this address, or matching it if it is a pattern
+    Message(String),
+    // Tagged bundles with this tag
+    Tbundle(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardRule {
+    pub matching: ForwardMatch,
+    pub target: String
+}
+
+impl ForwardRule {
+    pub fn applies_to(&self, packet: &OscPacket) -> bool {
+        match (&self.matching, packet) {
+            (ForwardMatch::Message(addr), OscPacket::Message(msg)) => OscAddress::new(addr).matches(&msg.addr),
+            (ForwardMatch::Tbundle(tag), OscPacket::Bundle(bundle)) => TaggedBundle::new(bundle)
+                .is_ok_and(|tagged| &tagged.bundle_tag == tag),
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for ForwardRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.matching {
+            ForwardMatch::Message(addr) => write!(f, "message {} {}", addr, self.target),
+            ForwardMatch::Tbundle(tag) => write!(f, "tbundle {} {}", tag, self.target)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RoutingTable {
+    pub rules: Vec<ForwardRule>
+}
+
+impl RoutingTable {
+    pub fn new() -> RoutingTable {
+        RoutingTable::default()
+    }
+
+    // Targets of all rules applying to the packet, in rule order
+    pub fn targets_for(&self, packet: &OscPacket) -> Vec<&str> {
+        self.rules.iter()
+            .filter(|rule| rule.applies_to(packet))
+            .map(|rule| rule.target.as_str())
+            .collect()
+    }
+
+    pub fn parse(config: &str) -> Result<RoutingTable, String> {
+        let mut rules = Vec::new();
+
+        for (index, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [kind, key, target] = parts[..] else {
+                return Err(format!("Line {}: expected <kind> <address/tag> <target>, got: {}", index + 1, line));
+            };
+
+            let matching = match kind {
+                "message" => ForwardMatch::Message(key.to_string()),
+                "tbundle" => ForwardMatch::Tbundle(key.to_string()),
+                _ => return Err(format!("Line {}: unknown rule kind {}, expected message or tbundle", index + 1, kind))
+            };
+
+            rules.push(ForwardRule { matching, target: target.to_string() });
+        }
+
+        Ok(RoutingTable { rules })
+    }
+
+    pub fn load_file(path: &str) -> Result<RoutingTable, String> {
+        let config = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        RoutingTable::parse(&config)
+    }
+
+    pub fn save_file(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+}
+
+impl fmt::Display for RoutingTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+// Old path from before schemas moved to core, kept so existing imports keep compiling
+pub use crate::core::schema::*;
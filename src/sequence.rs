@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::memory::ApproxSize;
+use crate::model::{BundleInfo, InfoParsing, INFO_SEQUENCE_KEY};
+
+/*
+    Sequence number convention for making UDP loss between JDW services observable.
+    Senders number their tagged bundles with a "seq" pair after the tag in /bundle_info:
+        ["/bundle_info", "note_on", "seq", 41]
+    OSCStack tracks the numbers per source address and reports gaps and reordering, and
+        can restore the order of selected tags, see OSCStack::ordered_delivery. Senders that
+        restart from a lower number are followed after a large jump back or a run of late numbers.
+
+    Receivers can tell the sender about gaps, so that it can slow down (see congestion.rs):
+        ["/jdw/gap", 42, 45]
+    names the expected and the received sequence number, see OSCStack::report_gaps.
+ */
+
+pub const SEQUENCE_KEY: &str = INFO_SEQUENCE_KEY;
+pub const GAP_REPORT_ADDR: &str = "/jdw/gap";
+
+pub fn gap_report(expected: i32, received: i32) -> OscMessage {
+    OscMessage { addr: GAP_REPORT_ADDR.to_string(), args: vec![OscType::Int(expected), OscType::Int(received)] }
+}
+
+// Amount of bundles a gap report says were lost, None if the message is not a well-formed gap report
+pub fn reported_missing(msg: &OscMessage) -> Option<u64> {
+    match (msg.addr.as_str(), msg.args.as_slice()) {
+        (GAP_REPORT_ADDR, [OscType::Int(expected), OscType::Int(received), ..]) => Some(missing_between(*expected, *received)),
+        _ => None
+    }
+}
+
+// Numbers skipped when received came in place of expected, counting on across the i32 wrap
+pub fn missing_between(expected: i32, received: i32) -> u64 {
+    received.wrapping_sub(expected) as u32 as u64
+}
+
+// Sequence number of a tagged bundle, None if it has none
+pub fn sequence_number(bundle: &OscBundle) -> Option<i32> {
+    BundleInfo::from_bundle(bundle, InfoParsing::Lenient).ok()?.seq
+}
+
+// Adds the sequence number to a /bundle_info message
+pub fn with_sequence(mut bundle_info: OscMessage, seq: i32) -> OscMessage {
+    bundle_info.args.push(OscType::String(SEQUENCE_KEY.to_string()));
+    bundle_info.args.push(OscType::Int(seq));
+    bundle_info
+}
+
+// Hands out consecutive sequence numbers for outgoing bundles, starting at 0
+#[derive(Debug, Default)]
+pub struct SequenceCounter {
+    next: AtomicI32
+}
+
+impl SequenceCounter {
+    pub fn new() -> SequenceCounter {
+        SequenceCounter::default()
+    }
+
+    pub fn next_seq(&self) -> i32 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceEvent {
+    // First number seen from the source, or exactly the expected one
+    InOrder,
+    // Numbers were skipped; (expected, received)
+    Gap(i32, i32),
+    // Number at or below one already seen, i.e. late or duplicated; (expected, received)
+    Reordered(i32, i32),
+    // Number far below the expected one, or the latest of several late numbers in a row:
+    //  the source restarted its counter and tracking starts over; (expected, received)
+    Restarted(i32, i32)
+}
+
+// Numbers further than this behind the expected one are taken as a restarted counter
+const RESTART_DISTANCE: i64 = 1024;
+// As are this many late numbers in a row, e.g. after a restart shortly after startup
+const RESTART_AFTER_LATE: u32 = 8;
+
+#[derive(Debug, Clone, Copy)]
+struct SourceSequence {
+    last: i32,
+    // Late numbers received since the last number that was in order or ahead
+    late: u32
+}
+
+// Last sequence number seen per source address
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    sources: HashMap<SocketAddr, SourceSequence>
+}
+
+impl SequenceTracker {
+    pub fn new() -> SequenceTracker {
+        SequenceTracker::default()
+    }
+
+    pub fn observe(&mut self, source: SocketAddr, seq: i32) -> SequenceEvent {
+        let Some(state) = self.sources.get_mut(&source) else {
+            self.sources.insert(source, SourceSequence { last: seq, late: 0 });
+            return SequenceEvent::InOrder;
+        };
+
+        let expected = state.last.wrapping_add(1);
+        if seq >= expected {
+            *state = SourceSequence { last: seq, late: 0 };
+            return if seq == expected { SequenceEvent::InOrder } else { SequenceEvent::Gap(expected, seq) };
+        }
+
+        state.late += 1;
+        if expected as i64 - seq as i64 > RESTART_DISTANCE || state.late >= RESTART_AFTER_LATE {
+            *state = SourceSequence { last: seq, late: 0 };
+            SequenceEvent::Restarted(expected, seq)
+        } else {
+            SequenceEvent::Reordered(expected, seq)
+        }
+    }
+
+    // The next number expected from the source, if any were seen
+    pub fn expected(&self, source: SocketAddr) -> Option<i32> {
+        self.sources.get(&source).map(|state| state.last.wrapping_add(1))
+    }
+}
+
+struct Held {
+    // None for packets that were passed on right away and only occupy their number
+    packet: Option<OscPacket>,
+    release_at: Option<Instant>,
+    // Approximate size of the packet, see ApproxSize
+    size: usize
+}
+
+impl Held {
+    fn packet(packet: OscPacket, release_at: Option<Instant>) -> Held {
+        Held { size: packet.approx_size(), packet: Some(packet), release_at }
+    }
+}
+
+#[derive(Default)]
+struct SourceQueue {
+    next: Option<i32>,
+    held: BTreeMap<i32, Held>
+}
+
+impl SourceQueue {
+    // Passes on everything from next onwards that is no longer missing a predecessor, returning the bytes freed
+    fn drain_ready(&mut self, out: &mut Vec<OscPacket>) -> usize {
+        let mut freed = 0;
+        while let Some(next) = self.next {
+            match self.held.remove(&next) {
+                Some(held) => {
+                    freed += held.size;
+                    out.extend(held.packet);
+                    self.next = Some(next.wrapping_add(1));
+                },
+                None => break
+            }
+        }
+        freed
+    }
+
+    // Gives up on any gaps up to and including seq, returning the bytes freed
+    fn release_until(&mut self, seq: i32, out: &mut Vec<OscPacket>) -> usize {
+        let mut freed = 0;
+        let released: Vec<i32> = self.held.range(..=seq).map(|(held_seq, _)| *held_seq).collect();
+        for held_seq in released {
+            if let Some(held) = self.held.remove(&held_seq) {
+                freed += held.size;
+                out.extend(held.packet);
+            }
+        }
+        self.next = Some(seq.wrapping_add(1));
+        freed + self.drain_ready(out)
+    }
+}
+
+/*
+    Holds sequenced packets that arrive ahead of a missing predecessor from the same source,
+        passing them on in sequence once the gap is filled or the hold time runs out.
+    Packets that should not wait (hold false) are passed on immediately but still occupy
+        their number, so they never cause others to be held.
+    Packets arriving after their number was given up on are passed on immediately.
+    held_bytes() tells roughly how much memory the held packets take; release_oldest lets
+        an owner with a memory budget give up on gaps early instead of holding on.
+ */
+#[derive(Default)]
+pub struct ReorderBuffer {
+    sources: HashMap<SocketAddr, SourceQueue>,
+    held_bytes: usize
+}
+
+impl ReorderBuffer {
+    pub fn new() -> ReorderBuffer {
+        ReorderBuffer::default()
+    }
+
+    // Returns the packets that can be passed on now, in order
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn push(&mut self, source: SocketAddr, seq: i32, packet: OscPacket, hold: Option<Duration>) -> Vec<OscPacket> {
+        self.push_at(source, seq, packet, hold, Instant::now())
+    }
+
+    // As push, with hold times counting from the given time
+    pub fn push_at(&mut self, source: SocketAddr, seq: i32, packet: OscPacket, hold: Option<Duration>, now: Instant) -> Vec<OscPacket> {
+        let queue = self.sources.entry(source).or_default();
+        let next = *queue.next.get_or_insert(seq);
+
+        if seq < next {
+            return vec![packet];
+        }
+
+        let mut ready = Vec::new();
+        match hold {
+            Some(hold) if seq > next => {
+                let held = Held::packet(packet, Some(now + hold));
+                self.held_bytes += held.size;
+                if let Some(replaced) = queue.held.insert(seq, held) {
+                    self.held_bytes -= replaced.size;
+                }
+            },
+            _ if seq > next => {
+                ready.push(packet);
+                if let Some(replaced) = queue.held.insert(seq, Held { packet: None, release_at: None, size: 0 }) {
+                    self.held_bytes -= replaced.size;
+                }
+            },
+            _ => {
+                let held = Held::packet(packet, None);
+                self.held_bytes += held.size;
+                if let Some(replaced) = queue.held.insert(seq, held) {
+                    self.held_bytes -= replaced.size;
+                }
+            }
+        }
+
+        self.held_bytes -= queue.drain_ready(&mut ready);
+        ready
+    }
+
+    // Gives up on gaps in front of packets held past their hold time, returning what that frees up
+    pub fn release_expired(&mut self, now: Instant) -> Vec<(SocketAddr, OscPacket)> {
+        let mut ready = Vec::new();
+
+        for (source, queue) in self.sources.iter_mut() {
+            let mut released_packets = Vec::new();
+            let expired = queue.held.iter()
+                .filter(|(_, held)| held.release_at.is_some_and(|at| at <= now))
+                .map(|(seq, _)| *seq)
+                .max();
+
+            if let Some(last_expired) = expired {
+                self.held_bytes -= queue.release_until(last_expired, &mut released_packets);
+            }
+
+            ready.extend(released_packets.into_iter().map(|packet| (*source, packet)));
+        }
+
+        ready
+    }
+
+    // When release_expired should be called next, if anything is held
+    pub fn next_release(&self) -> Option<Instant> {
+        self.sources.values()
+            .flat_map(|queue| queue.held.values())
+            .filter_map(|held| held.release_at)
+            .min()
+    }
+
+    // Passes on everything held for the source in sequence and starts over with its next number, e.g. after a restart
+    pub fn restart(&mut self, source: SocketAddr) -> Vec<OscPacket> {
+        let Some(queue) = self.sources.remove(&source) else { return Vec::new() };
+        let mut ready = Vec::new();
+        for held in queue.held.into_values() {
+            self.held_bytes -= held.size;
+            ready.extend(held.packet);
+        }
+        ready
+    }
+
+    // Drops all held packets and starts over for every source, returning how many were dropped
+    pub fn clear(&mut self) -> usize {
+        let dropped = self.sources.values()
+            .flat_map(|queue| queue.held.values())
+            .filter(|held| held.packet.is_some())
+            .count();
+        self.sources.clear();
+        self.held_bytes = 0;
+        dropped
+    }
+
+    // Approximate memory taken by held packets
+    pub fn held_bytes(&self) -> usize {
+        self.held_bytes
+    }
+
+    /*
+        Gives up on the gap in front of the packet that has been waiting longest (the one
+            released first by release_expired), returning what that frees up.
+        Empty when nothing is held.
+     */
+    pub fn release_oldest(&mut self) -> Vec<(SocketAddr, OscPacket)> {
+        let oldest = self.sources.iter()
+            .flat_map(|(source, queue)| queue.held.iter()
+                .filter(|(_, held)| held.packet.is_some())
+                .map(move |(seq, held)| (held.release_at, *source, *seq)))
+            .min_by_key(|(release_at, _, _)| *release_at);
+
+        let Some((_, source, seq)) = oldest else {
+            return Vec::new();
+        };
+        let mut released_packets = Vec::new();
+        if let Some(queue) = self.sources.get_mut(&source) {
+            self.held_bytes -= queue.release_until(seq, &mut released_packets);
+        }
+        released_packets.into_iter().map(|packet| (source, packet)).collect()
+    }
+}
@@ -0,0 +1,37 @@
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::model::{BundleInfo, InfoParsing, INFO_SESSION_KEY};
+
+/*
+    Session id convention, so that several independent sessions (e.g. sequencer instances)
+        can share one port. Senders scope tagged bundles with a "session" pair after the tag
+        in /bundle_info, next to any other pairs such as "seq":
+        ["/bundle_info", "note_on", "session", "live-a"]
+    OSCStack can drop bundles of other sessions (see OSCStack::only_session) or run handlers
+        for one session only (see OSCStack::session). Bundles without a session id, and plain
+        messages, belong to every session.
+ */
+
+pub const SESSION_KEY: &str = INFO_SESSION_KEY;
+
+// Session id of a tagged bundle, None if it has none
+pub fn session_id(bundle: &OscBundle) -> Option<String> {
+    BundleInfo::from_bundle(bundle, InfoParsing::Lenient).ok()?.session
+}
+
+// Adds the session id to a /bundle_info message
+pub fn with_session(mut bundle_info: OscMessage, session: &str) -> OscMessage {
+    bundle_info.args.push(OscType::String(SESSION_KEY.to_string()));
+    bundle_info.args.push(OscType::String(session.to_string()));
+    bundle_info
+}
+
+// Scopes an already built tagged bundle to the session; bundles without /bundle_info are left as they are
+pub fn in_session(mut bundle: OscBundle, session: &str) -> OscBundle {
+    if let Some(OscPacket::Message(info)) = bundle.content.first_mut() {
+        if info.addr == "/bundle_info" {
+            *info = with_session(info.clone(), session);
+        }
+    }
+    bundle
+}
@@ -0,0 +1,109 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/*
+    State shared between handlers (and other threads) without the usual lock pitfalls:
+        - Locks are only held for the duration of a closure, so guards can't be leaked
+            into a nested handler call or kept across a blocking wait
+        - A panic inside a closure poisons nothing; the next access recovers the lock
+            and carries on with the state as the panicking closure left it
+        - try_read_for and try_modify_for give up after a timeout instead of hanging forever
+
+    let notes = SharedState::new(Vec::new());
+    let handler_notes = notes.clone();
+    let stack = stack.on_message_parallel("/note_on", move |msg| handler_notes.modify(|notes| notes.push(msg)));
+    let count = notes.read(|notes| notes.len());
+
+    Calling modify from within a read or modify closure on the same state still deadlocks;
+        use the try_ variants where that may happen.
+ */
+
+// Pause between lock attempts while waiting out a timeout
+const RETRY_INTERVAL: Duration = Duration::from_micros(200);
+
+#[derive(Debug, Default)]
+pub struct SharedState<T> {
+    lock: Arc<RwLock<T>>
+}
+
+// Clones share the same state
+impl<T> Clone for SharedState<T> {
+    fn clone(&self) -> Self {
+        SharedState { lock: self.lock.clone() }
+    }
+}
+
+impl<T> SharedState<T> {
+    pub fn new(value: T) -> SharedState<T> {
+        SharedState { lock: Arc::new(RwLock::new(value)) }
+    }
+
+    // Blocks until no modify is running; any number of reads may run at once
+    pub fn read<R>(&self, operation: impl FnOnce(&T) -> R) -> R {
+        operation(&self.read_guard())
+    }
+
+    // Blocks until no other read or modify is running
+    pub fn modify<R>(&self, operation: impl FnOnce(&mut T) -> R) -> R {
+        operation(&mut self.write_guard())
+    }
+
+    pub fn try_read_for<R>(&self, timeout: Duration, operation: impl FnOnce(&T) -> R) -> Result<R, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.lock.try_read() {
+                Ok(guard) => return Ok(operation(&guard)),
+                Err(TryLockError::Poisoned(poisoned)) => {
+                    self.lock.clear_poison();
+                    return Ok(operation(&poisoned.into_inner()));
+                },
+                Err(TryLockError::WouldBlock) if Instant::now() >= deadline =>
+                    return Err(format!("Timed out after {:?} waiting to read shared state", timeout)),
+                Err(TryLockError::WouldBlock) => thread::sleep(RETRY_INTERVAL)
+            }
+        }
+    }
+
+    pub fn try_modify_for<R>(&self, timeout: Duration, operation: impl FnOnce(&mut T) -> R) -> Result<R, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.lock.try_write() {
+                Ok(mut guard) => return Ok(operation(&mut guard)),
+                Err(TryLockError::Poisoned(poisoned)) => {
+                    self.lock.clear_poison();
+                    return Ok(operation(&mut poisoned.into_inner()));
+                },
+                Err(TryLockError::WouldBlock) if Instant::now() >= deadline =>
+                    return Err(format!("Timed out after {:?} waiting to modify shared state", timeout)),
+                Err(TryLockError::WouldBlock) => thread::sleep(RETRY_INTERVAL)
+            }
+        }
+    }
+
+    // Swap in a new value, returning the old one
+    pub fn replace(&self, value: T) -> T {
+        self.modify(|current| std::mem::replace(current, value))
+    }
+
+    fn read_guard(&self) -> RwLockReadGuard<'_, T> {
+        self.lock.read().unwrap_or_else(|poisoned| {
+            self.lock.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_guard(&self) -> RwLockWriteGuard<'_, T> {
+        self.lock.write().unwrap_or_else(|poisoned| {
+            self.lock.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+impl<T: Clone> SharedState<T> {
+    // Copy of the current value, for when holding the lock while using it is not an option
+    pub fn snapshot(&self) -> T {
+        self.read(|value| value.clone())
+    }
+}
@@ -0,0 +1,321 @@
+/*
+    Thread-safe handle for controlling a running OSCStack.
+
+    OSCStack::begin() blocks forever, so anything that needs to interact with the stack at
+        runtime does so through a StackController fetched before begin() is called:
+
+    let controller = stack.controller();
+    thread::spawn(move || {
+        let packets = controller.capture_next(1, Duration::from_secs(10)).unwrap_or_default();
+        ...
+    });
+    stack.begin();
+
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use rosc::OscPacket;
+
+use crate::cancel::CancellationRegistry;
+use crate::config::StackConfig;
+use crate::metrics::{HandlerTiming, MetricsSnapshot, StackMetrics};
+use crate::model::TaggedBundle;
+use crate::osc_stack::Route;
+use crate::routing::{ForwardRule, RoutingTable};
+use crate::workers::MemoryBudget;
+
+struct CaptureRequest {
+    id: u64,
+    remaining: usize,
+    sender: Sender<OscPacket>
+}
+
+// What a paused stack does with the packets it receives, see StackController::pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PausePolicy {
+    // Hold on to them and dispatch them in order on resume
+    #[default]
+    Buffer,
+    Drop
+}
+
+#[derive(Default)]
+struct ControllerState {
+    capture: Option<CaptureRequest>,
+    // Handler groups switched off at runtime, see OSCStack::group
+    disabled_groups: HashSet<String>,
+    // Current mode for mode-scoped handlers, see OSCStack::on_message_in_mode
+    mode: Option<String>,
+    // One-shot routes registered at runtime by tag, see StackController::await_tbundle
+    tbundle_waiters: HashMap<String, Vec<Route<Sender<TaggedBundle>>>>,
+    next_capture_id: u64,
+    config: StackConfig,
+    // Bumped on every reload so the receive loop can tell when to pick up a new config
+    config_version: u64,
+    routing: RoutingTable,
+    paused: bool,
+    pause_policy: PausePolicy
+}
+
+#[derive(Clone, Default)]
+pub struct StackController {
+    state: Arc<Mutex<ControllerState>>,
+    metrics: Arc<StackMetrics>,
+    memory_budget: Arc<MemoryBudget>,
+    cancellations: CancellationRegistry
+}
+
+impl StackController {
+    pub fn new() -> StackController {
+        StackController::default()
+    }
+
+    // A panicking handler must not render the controller unusable, so poisoning is ignored
+    fn state(&self) -> MutexGuard<'_, ControllerState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    // Empty unless the stack has a handler time limit, see OSCStack::handler_time_limit
+    pub fn handler_timings(&self) -> Vec<(String, HandlerTiming)> {
+        self.metrics.handler_timings()
+    }
+
+    pub(crate) fn stack_metrics(&self) -> &StackMetrics {
+        &self.metrics
+    }
+
+    // Approximate bytes of packets waiting on handlers or for ordered delivery, see OSCStack::memory_budget
+    pub fn queued_bytes(&self) -> usize {
+        self.memory_budget.used()
+    }
+
+    // Operations that /jdw/cancel messages can abort, see OSCStack::on_message_cancellable
+    pub fn cancellations(&self) -> CancellationRegistry {
+        self.cancellations.clone()
+    }
+
+    pub(crate) fn memory_budget(&self) -> &Arc<MemoryBudget> {
+        &self.memory_budget
+    }
+
+    // Handlers in a disabled group are skipped until the group is enabled again
+    pub fn disable_group(&self, name: &str) {
+        self.state().disabled_groups.insert(name.to_string());
+    }
+
+    pub fn enable_group(&self, name: &str) {
+        self.state().disabled_groups.remove(name);
+    }
+
+    pub fn is_group_enabled(&self, name: &str) -> bool {
+        !self.state().disabled_groups.contains(name)
+    }
+
+    /*
+        Swap the whole configuration of the running stack at once. Picked up before the next
+            received packet is handled, including one the stack was already waiting for; the
+            socket stays bound so no packets are lost in between.
+     */
+    pub fn reload(&self, config: StackConfig) {
+        let mut state = self.state();
+        state.config = config;
+        state.config_version += 1;
+    }
+
+    pub fn config(&self) -> StackConfig {
+        self.state().config.clone()
+    }
+
+    // The current config, if it was reloaded since the given version (which is then updated)
+    pub(crate) fn config_if_changed(&self, seen_version: &mut u64) -> Option<StackConfig> {
+        let state = self.state();
+        if state.config_version == *seen_version {
+            return None;
+        }
+        *seen_version = state.config_version;
+        Some(state.config.clone())
+    }
+
+    // Snapshot of the forwarding rules currently in use
+    pub fn routing_table(&self) -> RoutingTable {
+        self.state().routing.clone()
+    }
+
+    // Replace all forwarding rules at once; packets are never routed by a mix of old and new rules
+    pub fn load_routing_table(&self, table: RoutingTable) {
+        self.state().routing = table;
+    }
+
+    pub fn import_routing_file(&self, path: &str) -> Result<(), String> {
+        self.load_routing_table(RoutingTable::load_file(path)?);
+        Ok(())
+    }
+
+    pub fn export_routing_file(&self, path: &str) -> Result<(), String> {
+        self.routing_table().save_file(path)
+    }
+
+    pub(crate) fn add_forward_rule(&self, rule: ForwardRule) {
+        self.state().routing.rules.push(rule);
+    }
+
+    pub(crate) fn forward_targets(&self, packet: &OscPacket) -> Vec<String> {
+        self.state().routing.targets_for(packet).into_iter().map(|target| target.to_string()).collect()
+    }
+
+    /*
+        Stop dispatching received packets, e.g. for a clean boundary while reconfiguring
+            downstream state. Depending on the pause policy, packets received in the meantime
+            are either buffered and dispatched on resume, or dropped. Immediate bundles (see
+            immediate.rs) are still dispatched while paused.
+        Buffered packets count towards the memory budget; once over budget, packets are
+            dropped according to the stack's eviction policy.
+     */
+    pub fn pause(&self) {
+        self.state().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.state().paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+
+    // Applies to packets received from now on; packets already buffered stay buffered
+    pub fn set_pause_policy(&self, policy: PausePolicy) {
+        self.state().pause_policy = policy;
+    }
+
+    pub fn pause_policy(&self) -> PausePolicy {
+        self.state().pause_policy
+    }
+
+    // None leaves any mode, so that only handlers without a mode apply
+    pub fn set_mode(&self, mode: Option<&str>) {
+        self.state().mode = mode.map(|mode| mode.to_string());
+    }
+
+    pub fn mode(&self) -> Option<String> {
+        self.state().mode.clone()
+    }
+
+    /*
+        Block until a tagged bundle with the given tag is received by the running stack,
+            e.g. waiting for an NRT render to report that it finished.
+        Registers a temporary one-shot route (as OSCStack::on_message_once does) next to any
+            regular handlers for the tag, which expires when the bundle arrives or the timeout
+            passes. Bundles with the tag are seen however the stack handles them, including
+            funneled ones.
+     */
+    pub fn await_tbundle(&self, tag: &str, timeout: Duration) -> Result<TaggedBundle, String> {
+        let (sender, receiver) = mpsc::channel();
+        let route = Route::temporary(sender, Some(Instant::now() + timeout), true);
+        self.state().tbundle_waiters.entry(tag.to_string()).or_default().push(route);
+
+        let result = receiver.recv_timeout(timeout)
+            .map_err(|_| format!("Timed out awaiting {} bundle", tag));
+
+        prune_waiters(&mut self.state(), tag);
+        result
+    }
+
+    pub(crate) fn awaits_tbundle(&self, tag: &str) -> bool {
+        let mut state = self.state();
+        prune_waiters(&mut state, tag);
+        state.tbundle_waiters.contains_key(tag)
+    }
+
+    // Hands the bundle to all waiters for its tag, which are then removed
+    pub(crate) fn offer_tbundle(&self, bundle: &TaggedBundle) {
+        let mut state = self.state();
+        for waiter in state.tbundle_waiters.get(&bundle.bundle_tag).into_iter().flatten().filter(|waiter| !waiter.expired()) {
+            waiter.consume();
+            let _ = waiter.op.send(bundle.clone());
+        }
+        prune_waiters(&mut state, &bundle.bundle_tag);
+    }
+
+    /*
+        "Learn" mode: intercept the next n received packets regardless of address, bypassing
+            all registered handlers. Blocks until n packets arrived or the timeout passed,
+            returning whatever was captured in that time.
+        Useful for mapping UIs: "move the knob you want to assign".
+        Only one capture runs at a time; starting another while one is running is an error.
+     */
+    pub fn capture_next(&self, n: usize, timeout: Duration) -> Result<Vec<OscPacket>, String> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let id = {
+            let mut state = self.state();
+            if state.capture.is_some() {
+                return Err("Another capture is already running".to_string());
+            }
+            state.next_capture_id += 1;
+            let id = state.next_capture_id;
+            state.capture = Some(CaptureRequest { id, remaining: n, sender });
+            id
+        };
+
+        let deadline = Instant::now() + timeout;
+        let mut captured = Vec::new();
+        while captured.len() < n {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(left) {
+                Ok(packet) => captured.push(packet),
+                Err(_) => break
+            }
+        }
+
+        // The request is gone already if it was fulfilled, and a newer capture may have started since
+        {
+            let mut state = self.state();
+            if state.capture.as_ref().is_some_and(|request| request.id == id) {
+                state.capture = None;
+            }
+        }
+        // Packets captured between the timeout and clearing the request would be lost otherwise
+        captured.extend(receiver.try_iter());
+        Ok(captured)
+    }
+
+    // Called by the stack for every received packet; returns the packet if it was not captured
+    pub(crate) fn try_capture(&self, packet: OscPacket) -> Option<OscPacket> {
+        let mut state = self.state();
+
+        let request = match state.capture.as_mut() {
+            Some(request) => request,
+            None => return Some(packet)
+        };
+
+        let delivered = request.sender.send(packet.clone()).is_ok();
+        request.remaining = request.remaining.saturating_sub(1);
+        if !delivered || request.remaining == 0 {
+            state.capture = None;
+        }
+
+        if delivered { None } else { Some(packet) }
+    }
+}
+
+// Drops waiters that were served or timed out, and the tag once none are left
+fn prune_waiters(state: &mut ControllerState, tag: &str) {
+    if let Some(waiters) = state.tbundle_waiters.get_mut(tag) {
+        waiters.retain(|waiter| !waiter.expired());
+        if waiters.is_empty() {
+            state.tbundle_waiters.remove(tag);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::core::schema::{ArgType, MessageSchema};
+
+/*
+    Rust source for strongly typed send functions, one per message schema, so that producers
+        are generated from the same definitions consumers validate against. Meant for build
+        scripts, writing into OUT_DIR:
+
+    let source = generate_client_stubs(&protocol::schemas());
+    fs::write(Path::new(&env::var("OUT_DIR")?).join("jdw_stubs.rs"), source)?;
+    // In the crate: include!(concat!(env!("OUT_DIR"), "/jdw_stubs.rs"));
+
+    The schema for "/note_on" with a string synth, a float freq and named varargs yields:
+
+    pub fn send_note_on(client: &OscClient, synth: &str, freq: f32, args: &NamedVarArgs) -> Result<(), String>
+ */
+
+const KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "gen"
+];
+
+pub fn generate_client_stubs(schemas: &[MessageSchema]) -> String {
+    let mut source = String::from("// Generated from JDW message schemas, do not edit\n");
+    for schema in schemas {
+        source.push('\n');
+        source.push_str(&generate_client_stub(schema));
+    }
+    source
+}
+
+pub fn generate_client_stub(schema: &MessageSchema) -> String {
+    let mut params = vec!["client: &jdw_osc_lib::client::OscClient".to_string()];
+    let mut args = Vec::new();
+
+    for spec in &schema.args {
+        let name = param_identifier(&spec.name);
+        let (param_type, arg) = match spec.arg_type {
+            ArgType::Int => ("i32", format!("rosc::OscType::Int({})", name)),
+            ArgType::Float => ("f32", format!("rosc::OscType::Float({})", name)),
+            ArgType::String => ("&str", format!("rosc::OscType::String({}.to_string())", name))
+        };
+        params.push(format!("{}: {}", name, param_type));
+        args.push(arg);
+    }
+
+    let extend = if schema.named_varargs {
+        params.push("args: &jdw_osc_lib::model::NamedVarArgs".to_string());
+        "    osc_args.extend(args.to_osc_args());\n"
+    } else {
+        ""
+    };
+    let binding = if schema.named_varargs { "let mut osc_args" } else { "let osc_args" };
+
+    format!(
+        "pub fn send_{}({}) -> Result<(), String> {{\n    {} = vec![{}];\n{}    client.send_message(rosc::OscMessage {{ addr: {:?}.to_string(), args: osc_args }})\n}}\n",
+        sanitize(&schema.addr), params.join(", "), binding, args.join(", "), extend, schema.addr
+    )
+}
+
+// "/jdw/set_mode" -> "jdw_set_mode"
+fn sanitize(name: &str) -> String {
+    name.trim_start_matches('/').chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+// Generated functions use these names themselves
+const RESERVED: [&str; 3] = ["client", "args", "osc_args"];
+
+// Keywords, reserved names and names not starting with a letter get an underscore, e.g. "type" -> "type_"
+fn param_identifier(name: &str) -> String {
+    let cleaned = sanitize(name);
+    if cleaned.is_empty() || cleaned.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", cleaned)
+    } else if KEYWORDS.contains(&cleaned.as_str()) || RESERVED.contains(&cleaned.as_str()) {
+        format!("{}_", cleaned)
+    } else {
+        cleaned
+    }
+}
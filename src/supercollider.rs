@@ -0,0 +1,361 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use bigdecimal::{BigDecimal, One, Signed, Zero};
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::envelope::{CurveShape, Envelope};
+use crate::model::{OscArgHandler, TimedOSCPacket};
+
+/*
+    Typed builders/parsers for the SuperCollider server commands used across JDW.
+    Controls use the same String,float named arg pattern as other JDW messages,
+        which SuperCollider accepts as control name/value pairs.
+
+    let msg = SNew::new("kick", nodes.next_id())
+        .with_control("freq", 440.0)
+        .to_message();
+ */
+
+fn controls_to_args(controls: &[(String, f32)]) -> Vec<OscType> {
+    controls.iter()
+        .flat_map(|(name, value)| vec![OscType::String(name.clone()), OscType::Float(*value)])
+        .collect()
+}
+
+fn controls_from_args(msg: &OscMessage, start_index: usize) -> Result<Vec<(String, f32)>, String> {
+    let args = msg.get_varargs(start_index)?;
+    Ok(args.chunks(2)
+        .filter_map(|pair| match pair {
+            [OscType::String(name), OscType::Float(value)] => Some((name.clone(), *value)),
+            _ => None
+        })
+        .collect())
+}
+
+// Where a new node is placed relative to its target, see the SC server command reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddAction {
+    #[default]
+    Head = 0,
+    Tail = 1,
+    Before = 2,
+    After = 3,
+    Replace = 4
+}
+
+impl AddAction {
+    pub fn from_i32(value: i32) -> Result<AddAction, String> {
+        match value {
+            0 => Ok(AddAction::Head),
+            1 => Ok(AddAction::Tail),
+            2 => Ok(AddAction::Before),
+            3 => Ok(AddAction::After),
+            4 => Ok(AddAction::Replace),
+            _ => Err(format!("Unknown add action: {}", value))
+        }
+    }
+}
+
+// /s_new defName nodeID addAction targetID [name value]...
+#[derive(Debug, Clone, PartialEq)]
+pub struct SNew {
+    pub def_name: String,
+    pub node_id: i32,
+    pub add_action: AddAction,
+    pub target_id: i32,
+    pub controls: Vec<(String, f32)>
+}
+
+impl SNew {
+    // Defaults to adding to the head of the default group (1)
+    pub fn new(def_name: &str, node_id: i32) -> SNew {
+        SNew {
+            def_name: def_name.to_string(),
+            node_id,
+            add_action: AddAction::Head,
+            target_id: 1,
+            controls: vec![]
+        }
+    }
+
+    pub fn with_target(mut self, add_action: AddAction, target_id: i32) -> SNew {
+        self.add_action = add_action;
+        self.target_id = target_id;
+        self
+    }
+
+    pub fn with_control(mut self, name: &str, value: f32) -> SNew {
+        self.controls.push((name.to_string(), value));
+        self
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        let mut args = vec![
+            OscType::String(self.def_name.clone()),
+            OscType::Int(self.node_id),
+            OscType::Int(self.add_action as i32),
+            OscType::Int(self.target_id),
+        ];
+        args.extend(controls_to_args(&self.controls));
+
+        OscMessage { addr: "/s_new".to_string(), args }
+    }
+
+    pub fn from_message(msg: &OscMessage) -> Result<SNew, String> {
+        msg.expect_addr("/s_new")?;
+        msg.expect_args(4)?;
+
+        Ok(SNew {
+            def_name: msg.get_string_at(0, "def_name")?,
+            node_id: msg.get_int_at(1, "node_id")?,
+            add_action: AddAction::from_i32(msg.get_int_at(2, "add_action")?)?,
+            target_id: msg.get_int_at(3, "target_id")?,
+            controls: controls_from_args(msg, 4)?
+        })
+    }
+}
+
+// /n_set nodeID [name value]...
+#[derive(Debug, Clone, PartialEq)]
+pub struct NSet {
+    pub node_id: i32,
+    pub controls: Vec<(String, f32)>
+}
+
+impl NSet {
+    pub fn new(node_id: i32) -> NSet {
+        NSet { node_id, controls: vec![] }
+    }
+
+    pub fn with_control(mut self, name: &str, value: f32) -> NSet {
+        self.controls.push((name.to_string(), value));
+        self
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        let mut args = vec![OscType::Int(self.node_id)];
+        args.extend(controls_to_args(&self.controls));
+
+        OscMessage { addr: "/n_set".to_string(), args }
+    }
+
+    pub fn from_message(msg: &OscMessage) -> Result<NSet, String> {
+        msg.expect_addr("/n_set")?;
+
+        Ok(NSet {
+            node_id: msg.get_int_at(0, "node_id")?,
+            controls: controls_from_args(msg, 1)?
+        })
+    }
+}
+
+// /n_free nodeID...
+#[derive(Debug, Clone, PartialEq)]
+pub struct NFree {
+    pub node_ids: Vec<i32>
+}
+
+impl NFree {
+    pub fn new(node_ids: Vec<i32>) -> NFree {
+        NFree { node_ids }
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        OscMessage {
+            addr: "/n_free".to_string(),
+            args: self.node_ids.iter().map(|id| OscType::Int(*id)).collect()
+        }
+    }
+
+    pub fn from_message(msg: &OscMessage) -> Result<NFree, String> {
+        msg.expect_addr("/n_free")?;
+
+        let node_ids = (0..msg.args.len())
+            .map(|index| msg.get_int_at(index, "node_id"))
+            .collect::<Result<Vec<i32>, String>>()?;
+
+        Ok(NFree { node_ids })
+    }
+}
+
+// /b_allocRead bufnum path [startFrame numFrames]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BAllocRead {
+    pub buffer_number: i32,
+    pub path: String,
+    pub start_frame: i32,
+    // 0 or less reads the entire file
+    pub frame_count: i32
+}
+
+impl BAllocRead {
+    pub fn new(buffer_number: i32, path: &str) -> BAllocRead {
+        BAllocRead {
+            buffer_number,
+            path: path.to_string(),
+            start_frame: 0,
+            frame_count: 0
+        }
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        OscMessage {
+            addr: "/b_allocRead".to_string(),
+            args: vec![
+                OscType::Int(self.buffer_number),
+                OscType::String(self.path.clone()),
+                OscType::Int(self.start_frame),
+                OscType::Int(self.frame_count),
+            ]
+        }
+    }
+
+    pub fn from_message(msg: &OscMessage) -> Result<BAllocRead, String> {
+        msg.expect_addr("/b_allocRead")?;
+        msg.expect_args(2)?;
+
+        Ok(BAllocRead {
+            buffer_number: msg.get_int_at(0, "buffer_number")?,
+            path: msg.get_string_at(1, "path")?,
+            start_frame: msg.get_int_at(2, "start_frame").unwrap_or(0),
+            frame_count: msg.get_int_at(3, "frame_count").unwrap_or(0)
+        })
+    }
+}
+
+// /d_recv bytes - compiled synthdef file contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct DRecv {
+    pub synth_def: Vec<u8>
+}
+
+impl DRecv {
+    pub fn new(synth_def: Vec<u8>) -> DRecv {
+        DRecv { synth_def }
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        OscMessage {
+            addr: "/d_recv".to_string(),
+            args: vec![OscType::Blob(self.synth_def.clone())]
+        }
+    }
+
+    pub fn from_message(msg: &OscMessage) -> Result<DRecv, String> {
+        msg.expect_addr("/d_recv")?;
+
+        let synth_def = msg.args.first()
+            .and_then(|arg| arg.clone().blob())
+            .ok_or("synth_def blob not found as 0th arg".to_string())?;
+
+        Ok(DRecv { synth_def })
+    }
+}
+
+/*
+    Thread-safe node id source. SuperCollider leaves ids below 1000 to the
+        server and other clients by convention, so allocation starts there by default.
+ */
+pub struct NodeIdAllocator {
+    next: AtomicI32
+}
+
+impl Default for NodeIdAllocator {
+    fn default() -> Self {
+        NodeIdAllocator::starting_at(1000)
+    }
+}
+
+impl NodeIdAllocator {
+    pub fn new() -> NodeIdAllocator {
+        NodeIdAllocator::default()
+    }
+
+    pub fn starting_at(first_id: i32) -> NodeIdAllocator {
+        NodeIdAllocator { next: AtomicI32::new(first_id) }
+    }
+
+    pub fn next_id(&self) -> i32 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RampCurve {
+    #[default]
+    Linear,
+    // Equal ratios per step, natural for frequencies; start and end must share a sign and not be 0
+    Exponential
+}
+
+/*
+    Automation of a single node control from start to end over a duration, expanded into
+        /n_set messages wrapped as relative TimedOSCPacket:s, one every step (in the same
+        time unit as duration) and always ending on exactly the end value.
+    This is the two point case of an Envelope (see envelope.rs), which does the sampling.
+
+    let packets = Ramp::new(node_id, "cutoff", 200.0, 4000.0)
+        .over(BigDecimal::from(2), BigDecimal::from_str("0.05")?)
+        .curve(RampCurve::Exponential)
+        .to_timed_packets()?;
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ramp {
+    pub node_id: i32,
+    pub control: String,
+    pub start: f32,
+    pub end: f32,
+    pub duration: BigDecimal,
+    pub step: BigDecimal,
+    pub curve: RampCurve
+}
+
+impl Ramp {
+    // Defaults to an instant jump to the end value; see over()
+    pub fn new(node_id: i32, control: &str, start: f32, end: f32) -> Ramp {
+        Ramp {
+            node_id,
+            control: control.to_string(),
+            start,
+            end,
+            duration: BigDecimal::zero(),
+            step: BigDecimal::one(),
+            curve: RampCurve::Linear
+        }
+    }
+
+    pub fn over(mut self, duration: BigDecimal, step: BigDecimal) -> Ramp {
+        self.duration = duration;
+        self.step = step;
+        self
+    }
+
+    pub fn curve(mut self, curve: RampCurve) -> Ramp {
+        self.curve = curve;
+        self
+    }
+
+    pub fn to_envelope(&self) -> Envelope {
+        let shape = match self.curve {
+            RampCurve::Linear => CurveShape::Linear,
+            RampCurve::Exponential => CurveShape::Exponential
+        };
+        Envelope::new(self.start).then(self.duration.clone(), self.end, shape)
+    }
+
+    pub fn to_timed_packets(&self) -> Result<Vec<TimedOSCPacket>, String> {
+        if self.duration.is_negative() {
+            return Err(format!("Ramp duration must not be negative, got {}", self.duration));
+        }
+        if !self.step.is_positive() {
+            return Err(format!("Ramp step must be positive, got {}", self.step));
+        }
+        if self.curve == RampCurve::Exponential && (self.start == 0.0 || self.end == 0.0 || self.start.signum() != self.end.signum()) {
+            return Err(format!("Exponential ramp from {} to {} must not cross or touch 0", self.start, self.end));
+        }
+
+        self.to_envelope().sample(&self.step, |value| {
+            OscPacket::Message(NSet::new(self.node_id).with_control(&self.control, value).to_message())
+        })
+    }
+}
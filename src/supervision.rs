@@ -0,0 +1,97 @@
+use std::fmt;
+use std::time::Duration;
+
+/*
+    Recovery from a receive socket that keeps failing, e.g. because its network interface
+        went down. After failures_before_rebind consecutive read failures the socket is
+        rebound to the same address, retrying with exponential backoff up to max_backoff.
+    After rebind_attempts_before_report failed attempts in a row the stack reports itself
+        unhealthy with the rebind error (see OSCStack::on_health_change) and keeps retrying
+        at max_backoff until the socket is back.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupervisionPolicy {
+    pub failures_before_rebind: u32,
+    // Wait before the first rebind attempt, doubled after every failed attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub rebind_attempts_before_report: u32
+}
+
+impl Default for SupervisionPolicy {
+    fn default() -> Self {
+        SupervisionPolicy {
+            failures_before_rebind: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            rebind_attempts_before_report: 20
+        }
+    }
+}
+
+impl SupervisionPolicy {
+    // Wait before the given (0-based) rebind attempt
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackHealth {
+    Healthy,
+    // Receiving failed; holds the last socket error
+    Unhealthy(String)
+}
+
+impl fmt::Display for StackHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackHealth::Healthy => write!(f, "healthy"),
+            StackHealth::Unhealthy(e) => write!(f, "unhealthy: {}", e)
+        }
+    }
+}
+
+// Tracks consecutive receive failures against the policy
+pub(crate) struct Supervisor {
+    policy: SupervisionPolicy,
+    consecutive_failures: u32,
+    healthy: bool
+}
+
+impl Supervisor {
+    pub(crate) fn new(policy: SupervisionPolicy) -> Supervisor {
+        Supervisor { policy, consecutive_failures: 0, healthy: true }
+    }
+
+    pub(crate) fn policy(&self) -> &SupervisionPolicy {
+        &self.policy
+    }
+
+    // The socket works; returns the new health if this recovers from being unhealthy
+    pub(crate) fn succeeded(&mut self) -> Option<StackHealth> {
+        self.consecutive_failures = 0;
+        if self.healthy {
+            return None;
+        }
+        self.healthy = true;
+        Some(StackHealth::Healthy)
+    }
+
+    // The socket failed; returns the new health if this is the first failure since healthy
+    pub(crate) fn failed(&mut self, error: &str) -> Option<StackHealth> {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if !self.healthy {
+            return None;
+        }
+        self.healthy = false;
+        Some(StackHealth::Unhealthy(error.to_string()))
+    }
+
+    pub(crate) fn should_rebind(&self) -> bool {
+        self.consecutive_failures >= self.policy.failures_before_rebind
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use rosc::{OscMessage, OscType};
+
+use crate::model::OscArgWriter;
+
+/*
+    Base message with named slots, for stamping out variants in hot sequencer loops without
+        building each message from scratch.
+
+    let note = MessageTemplate::new(OscMessage {
+        addr: "/note_on".to_string(),
+        args: vec![OscType::String("lead".to_string()), OscType::Float(0.0), OscType::Float(0.5)]
+    }).slot("freq", 1).slot("amp", 2);
+
+    let msg = note.stamp(&[("freq", OscType::Float(440.0))])?;
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    base: OscMessage,
+    slots: HashMap<String, usize>
+}
+
+impl MessageTemplate {
+    pub fn new(base: OscMessage) -> MessageTemplate {
+        MessageTemplate { base, slots: HashMap::new() }
+    }
+
+    // Name the arg at the given index of the base message
+    pub fn slot(mut self, name: &str, index: usize) -> MessageTemplate {
+        self.slots.insert(name.to_string(), index);
+        self
+    }
+
+    pub fn base(&self) -> &OscMessage {
+        &self.base
+    }
+
+    // Copy of the base message with the named slots filled in; unnamed args keep their base value
+    pub fn stamp(&self, values: &[(&str, OscType)]) -> Result<OscMessage, String> {
+        let replacements = values.iter()
+            .map(|(name, value)| self.slots.get(*name)
+                .map(|index| (*index, value.clone()))
+                .ok_or(format!("Template for {} has no slot named {}", self.base.addr, name)))
+            .collect::<Result<Vec<_>, String>>()?;
+        self.stamp_indexed(&replacements)
+    }
+
+    // As stamp, but by arg index directly
+    pub fn stamp_indexed(&self, replacements: &[(usize, OscType)]) -> Result<OscMessage, String> {
+        self.base.with_args_replaced(replacements)
+    }
+}
@@ -0,0 +1,356 @@
+use std::io::BufRead;
+
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+use crate::osc_stack::OSCStack;
+
+/*
+    Human-writable text format for OSC packets, for shell scripts and REPLs.
+
+    Messages are an address followed by args, ended by ';' or a newline:
+        /note_on "kick" 440.0 0.5 ;
+    Bundles are bracketed and may span lines, with an optional @seconds:fraction time tag:
+        [ /bundle_info "timed_msg"; /timed_msg_info "0.0"; /note_on kick 440.0 ]
+
+    Arg syntax:
+        "quoted" or bare words  -> String (quotes support \" and \\ escapes)
+        12                      -> Int
+        12L                     -> Long
+        440.0 / 1e3             -> Float
+        440.0d                  -> Double
+        inf_f / -inf_f / nan_f  -> Float infinities and NaN
+        inf_d / -inf_d / nan_d  -> Double infinities and NaN
+        true / false            -> Bool
+        nil / inf               -> Nil / Inf (the OSC "infinitum" type, not a number)
+        #00ff10                 -> Blob (hex)
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Open(Option<OscTime>),
+    Close,
+    Separator
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' | '\n' => tokens.push(Token::Separator),
+            ']' => tokens.push(Token::Close),
+            '[' => {
+                let time = if chars.peek() == Some(&'@') {
+                    chars.next();
+                    let mut spec = String::new();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || next == ';' || next == ']' { break; }
+                        spec.push(next);
+                        chars.next();
+                    }
+                    Some(parse_time_tag(&spec)?)
+                } else {
+                    None
+                };
+                tokens.push(Token::Open(time));
+            },
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some(escaped) => value.push(escaped),
+                            None => return Err("Unterminated escape in quoted string".to_string())
+                        },
+                        Some(other) => value.push(other),
+                        None => return Err(format!("Unterminated quoted string: \"{}", value))
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            },
+            c if c.is_whitespace() => {},
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == ';' || next == ']' || next == '"' { break; }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_time_tag(spec: &str) -> Result<OscTime, String> {
+    let (seconds, fractional) = spec.split_once(':').unwrap_or((spec, "0"));
+    Ok(OscTime {
+        seconds: seconds.parse().map_err(|_| format!("Invalid time tag seconds: {}", spec))?,
+        fractional: fractional.parse().map_err(|_| format!("Invalid time tag fraction: {}", spec))?
+    })
+}
+
+fn parse_arg(word: &str) -> Result<OscType, String> {
+    let arg = match word {
+        "true" => OscType::Bool(true),
+        "false" => OscType::Bool(false),
+        "nil" => OscType::Nil,
+        "inf" => OscType::Inf,
+        "inf_f" => OscType::Float(f32::INFINITY),
+        "-inf_f" => OscType::Float(f32::NEG_INFINITY),
+        "nan_f" => OscType::Float(f32::NAN),
+        "inf_d" => OscType::Double(f64::INFINITY),
+        "-inf_d" => OscType::Double(f64::NEG_INFINITY),
+        "nan_d" => OscType::Double(f64::NAN),
+        _ if word.starts_with('#') => {
+            let hex = &word[1..];
+            if !hex.is_ascii() {
+                return Err(format!("Blob {} should only hold hex digits", word));
+            }
+            if !hex.len().is_multiple_of(2) {
+                return Err(format!("Blob {} has an odd number of hex digits", word));
+            }
+            let bytes = (0..hex.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|e| format!("Invalid blob {}: {}", word, e))?;
+            OscType::Blob(bytes)
+        },
+        _ => {
+            let numeric = word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.');
+            if !numeric {
+                OscType::String(word.to_string())
+            } else if let Some(long) = word.strip_suffix('L') {
+                OscType::Long(long.parse().map_err(|_| format!("Invalid long: {}", word))?)
+            } else if let Some(double) = word.strip_suffix('d') {
+                OscType::Double(double.parse().map_err(|_| format!("Invalid double: {}", word))?)
+            } else if word.contains(['.', 'e', 'E']) {
+                OscType::Float(word.parse().map_err(|_| format!("Invalid float: {}", word))?)
+            } else {
+                match word.parse() {
+                    Ok(int) => OscType::Int(int),
+                    // Things like "-x" or "1-2" are more likely strings than broken numbers
+                    Err(_) => OscType::String(word.to_string())
+                }
+            }
+        }
+    };
+
+    Ok(arg)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn skip_separators(&mut self) {
+        while self.peek() == Some(&Token::Separator) {
+            self.pos += 1;
+        }
+    }
+
+    fn packet(&mut self) -> Result<OscPacket, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Open(time)) => {
+                self.pos += 1;
+                let mut content = Vec::new();
+                loop {
+                    self.skip_separators();
+                    match self.peek() {
+                        Some(Token::Close) => { self.pos += 1; break; },
+                        Some(_) => content.push(self.packet()?),
+                        None => return Err("Unclosed bundle: missing ']'".to_string())
+                    }
+                }
+                Ok(OscPacket::Bundle(OscBundle {
+                    timetag: time.unwrap_or(OscTime { seconds: 0, fractional: 1 }),
+                    content
+                }))
+            },
+            Some(Token::Word(addr)) if addr.starts_with('/') => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                while let Some(token) = self.peek() {
+                    match token {
+                        Token::Word(word) => args.push(parse_arg(word)?),
+                        Token::Quoted(value) => args.push(OscType::String(value.clone())),
+                        _ => break
+                    }
+                    self.pos += 1;
+                }
+                Ok(OscPacket::Message(OscMessage { addr, args }))
+            },
+            Some(other) => Err(format!("Expected address or '[', got {:?}", other)),
+            None => Err("Expected packet, got end of input".to_string())
+        }
+    }
+}
+
+// All packets in the given text
+pub fn parse_packets(text: &str) -> Result<Vec<OscPacket>, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+    let mut packets = Vec::new();
+
+    loop {
+        parser.skip_separators();
+        if parser.peek().is_none() {
+            return Ok(packets);
+        }
+        packets.push(parser.packet()?);
+    }
+}
+
+// Exactly one packet
+pub fn parse_packet(text: &str) -> Result<OscPacket, String> {
+    let mut packets = parse_packets(text)?;
+    match packets.len() {
+        1 => Ok(packets.remove(0)),
+        n => Err(format!("Expected exactly one packet, found {}", n))
+    }
+}
+
+fn arg_to_text(arg: &OscType) -> String {
+    match arg {
+        OscType::Int(value) => value.to_string(),
+        OscType::Long(value) => format!("{}L", value),
+        OscType::Float(value) => match value.is_finite() {
+            true => format!("{:?}", value),
+            false => non_finite_to_text(value.is_nan(), value.is_sign_negative(), 'f')
+        },
+        OscType::Double(value) => match value.is_finite() {
+            true => format!("{:?}d", value),
+            false => non_finite_to_text(value.is_nan(), value.is_sign_negative(), 'd')
+        },
+        OscType::String(value) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")),
+        OscType::Bool(value) => value.to_string(),
+        OscType::Nil => "nil".to_string(),
+        OscType::Inf => "inf".to_string(),
+        OscType::Blob(bytes) => format!("#{}", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        // No text representation; written as a quoted description so the line still parses
+        other => format!("\"{}\"", format!("{:?}", other).replace(['"', '\\'], ""))
+    }
+}
+
+// Spellings of infinities and NaN, as "inf" is taken by OscType::Inf
+fn non_finite_to_text(nan: bool, negative: bool, suffix: char) -> String {
+    match (nan, negative) {
+        (true, _) => format!("nan_{}", suffix),
+        (false, true) => format!("-inf_{}", suffix),
+        (false, false) => format!("inf_{}", suffix)
+    }
+}
+
+// Single line text representation, parseable by parse_packet
+pub fn to_text(packet: &OscPacket) -> String {
+    match packet {
+        OscPacket::Message(msg) => {
+            let mut parts = vec![msg.addr.clone()];
+            parts.extend(msg.args.iter().map(arg_to_text));
+            format!("{} ;", parts.join(" "))
+        },
+        OscPacket::Bundle(bundle) => {
+            let time = match bundle.timetag {
+                OscTime { seconds: 0, fractional: 1 } => String::new(),
+                OscTime { seconds, fractional } => format!("@{}:{}", seconds, fractional)
+            };
+            let content: Vec<String> = bundle.content.iter().map(to_text).collect();
+            format!("[{} {} ]", time, content.join(" "))
+        }
+    }
+}
+
+/*
+    Streaming reader yielding packets from text lines, e.g. stdin.
+    Bundles may span several lines; blank lines and lines starting with // are ignored.
+ */
+pub struct TextPacketReader<R: BufRead> {
+    input: R,
+    pending: Vec<OscPacket>
+}
+
+impl<R: BufRead> TextPacketReader<R> {
+    pub fn new(input: R) -> TextPacketReader<R> {
+        TextPacketReader { input, pending: Vec::new() }
+    }
+
+    // Net bracket depth of the line, ignoring brackets inside quoted strings
+    fn depth_change(line: &str) -> i32 {
+        let mut depth = 0;
+        let mut quoted = false;
+        let mut escaped = false;
+        for c in line.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                '[' if !quoted => depth += 1,
+                ']' if !quoted => depth -= 1,
+                _ => {}
+            }
+        }
+        depth
+    }
+}
+
+impl<R: BufRead> Iterator for TextPacketReader<R> {
+    type Item = Result<OscPacket, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.pending.is_empty() {
+            return Some(Ok(self.pending.remove(0)));
+        }
+
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) if text.trim().is_empty() => return None,
+                Ok(0) => break,
+                Ok(_) => {},
+                Err(e) => return Some(Err(e.to_string()))
+            }
+
+            if line.trim_start().starts_with("//") {
+                continue;
+            }
+
+            depth += TextPacketReader::<R>::depth_change(&line);
+            text.push_str(&line);
+            if depth <= 0 && !text.trim().is_empty() {
+                break;
+            }
+        }
+
+        match parse_packets(&text) {
+            Ok(packets) => {
+                self.pending = packets;
+                self.next()
+            },
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+// Feed every packet read from the input through the stack handlers, stopping at the first parse error
+#[cfg(all(feature = "stack", not(target_arch = "wasm32")))]
+pub fn dispatch_lines<R: BufRead>(input: R, stack: &OSCStack) -> Result<(), String> {
+    for packet in TextPacketReader::new(input) {
+        stack.interpret(packet?);
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode, Signed, ToPrimitive, Zero};
+use rosc::OscType;
+
+/*
+    Times travel as f32 in some JDW messages and as BigDecimal strings in others (timed_msg).
+    TimeValue pins down how a time is rounded and written, so that every service produces
+        and accepts the same text for the same time:
+        - Values are rounded to at most max_scale decimals using the policy's rounding mode
+        - On the wire a time is a plain decimal string without exponent or trailing zeros,
+            e.g. "0.25", "12", "-1.5"
+        - Floats and doubles are converted via their shortest decimal representation, so
+            0.1f32 is "0.1"
+    Senders may also write times as OSC floats or doubles, which tools outside JDW handle
+        natively, see TimeEncoding.
+ */
+
+/*
+    How times are written to the wire. Builders write decimal strings; every encoding is
+        accepted when parsing, so a deployment can switch its senders over one service at
+        a time, either per bundle with TimedOSCPacket::to_bundle_with or per client with
+        OscClient::with_time_encoding.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeEncoding {
+    // Exact, but only understood by JDW services
+    #[default]
+    DecimalString,
+    // Native OSC types, for times they represent exactly (see TimeValue::to_osc_float)
+    FloatArg,
+    DoubleArg
+}
+
+impl TimeEncoding {
+    // How a received time was written, e.g. for finding services not yet migrated; None for args that are no time
+    pub fn of(arg: &OscType) -> Option<TimeEncoding> {
+        match arg {
+            OscType::String(_) => Some(TimeEncoding::DecimalString),
+            OscType::Float(_) => Some(TimeEncoding::FloatArg),
+            OscType::Double(_) => Some(TimeEncoding::DoubleArg),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimePolicy {
+    pub max_scale: i64,
+    pub rounding: RoundingMode
+}
+
+// Nanosecond precision, matching OSC time tag conversion (see nrt.rs)
+impl Default for TimePolicy {
+    fn default() -> Self {
+        TimePolicy { max_scale: 9, rounding: RoundingMode::HalfEven }
+    }
+}
+
+/*
+    Bounds on parsed times, checked before rescaling: an exponent such as "1e20000000" is
+        cheap to parse but expands into millions of digits when rounded to the policy scale.
+ */
+const MAX_INTEGER_DIGITS: i64 = 30;
+const MAX_PARSED_SCALE: i64 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeValue(BigDecimal);
+
+impl TimeValue {
+    pub fn new(value: &BigDecimal, policy: &TimePolicy) -> TimeValue {
+        TimeValue(value.with_scale_round(policy.max_scale, policy.rounding).normalized())
+    }
+
+    pub fn from_f32(value: f32, policy: &TimePolicy) -> Result<TimeValue, String> {
+        TimeValue::parse(&value.to_string(), policy)
+    }
+
+    // The exact binary value of e.g. 0.1 is 0.1000000000000000055..., which the sender never meant
+    pub fn from_f64(value: f64, policy: &TimePolicy) -> Result<TimeValue, String> {
+        if !value.is_finite() {
+            return Err(format!("Time {} is not a finite number", value));
+        }
+        TimeValue::parse(&value.to_string(), policy)
+    }
+
+    pub fn parse(value: &str, policy: &TimePolicy) -> Result<TimeValue, String> {
+        let decimal = BigDecimal::from_str(value.trim()).map_err(|e| format!("Invalid time {}: {}", value, e))?;
+        if decimal.is_zero() {
+            return Ok(TimeValue::new(&BigDecimal::zero(), policy));
+        }
+
+        let (_, scale) = decimal.as_bigint_and_exponent();
+        let integer_digits = decimal.digits() as i64 - scale;
+        if scale > MAX_PARSED_SCALE || integer_digits > MAX_INTEGER_DIGITS {
+            return Err(format!("Time {} is out of range", value));
+        }
+        Ok(TimeValue::new(&decimal, policy))
+    }
+
+    // Accepts the wire string format as well as numeric args from less strict senders
+    pub fn from_osc_arg(arg: &OscType, policy: &TimePolicy) -> Result<TimeValue, String> {
+        match arg {
+            OscType::String(value) => TimeValue::parse(value, policy),
+            OscType::Float(value) => TimeValue::from_f32(*value, policy),
+            OscType::Double(value) => TimeValue::from_f64(*value, policy),
+            OscType::Int(value) => Ok(TimeValue::new(&BigDecimal::from(*value), policy)),
+            OscType::Long(value) => Ok(TimeValue::new(&BigDecimal::from(*value), policy)),
+            other => Err(format!("Expected a time, got {:?}", other))
+        }
+    }
+
+    pub fn as_bigdecimal(&self) -> &BigDecimal {
+        &self.0
+    }
+
+    pub fn to_f32(&self) -> f32 {
+        self.0.to_f32().unwrap_or_default()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_f64().unwrap_or_default()
+    }
+
+    pub fn to_osc_arg(&self) -> OscType {
+        OscType::String(self.to_wire_string())
+    }
+
+    // Fails rather than send a time that would not parse back to the same value
+    pub fn to_osc_float(&self) -> Result<OscType, String> {
+        let float = self.to_f32();
+        match BigDecimal::from_str(&float.to_string()) {
+            Ok(decimal) if float.is_finite() && decimal == self.0 => Ok(OscType::Float(float)),
+            _ => Err(format!("Time {} cannot be sent as a float without losing precision", self))
+        }
+    }
+
+    // Fails rather than send a time that would not parse back to the same value
+    pub fn to_osc_double(&self) -> Result<OscType, String> {
+        let double = self.to_f64();
+        match BigDecimal::from_str(&double.to_string()) {
+            Ok(decimal) if double.is_finite() && decimal == self.0 => Ok(OscType::Double(double)),
+            _ => Err(format!("Time {} cannot be sent as a double without losing precision", self))
+        }
+    }
+
+    pub fn to_osc_arg_encoded(&self, encoding: TimeEncoding) -> Result<OscType, String> {
+        match encoding {
+            TimeEncoding::DecimalString => Ok(self.to_osc_arg()),
+            TimeEncoding::FloatArg => self.to_osc_float(),
+            TimeEncoding::DoubleArg => self.to_osc_double()
+        }
+    }
+
+    // In the given encoding, or as a decimal string where that would lose precision
+    pub fn to_osc_arg_preferring(&self, encoding: TimeEncoding) -> OscType {
+        self.to_osc_arg_encoded(encoding).unwrap_or_else(|_| self.to_osc_arg())
+    }
+
+    pub fn to_wire_string(&self) -> String {
+        let (digits, scale) = self.0.as_bigint_and_exponent();
+        let sign = if digits.is_negative() { "-" } else { "" };
+        let digits = digits.abs().to_string();
+
+        if scale <= 0 {
+            return format!("{}{}{}", sign, digits, "0".repeat(-scale as usize));
+        }
+
+        let scale = scale as usize;
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - scale);
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+impl fmt::Display for TimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_wire_string())
+    }
+}
+
+impl From<TimeValue> for BigDecimal {
+    fn from(value: TimeValue) -> Self {
+        value.0
+    }
+}
@@ -0,0 +1,222 @@
+use bigdecimal::BigDecimal;
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::core::args::named_arg_index;
+use crate::model::{FromTaggedBundle, OscArgHandler, TaggedBundle, TimedOSCPacket};
+use crate::time_value::{TimePolicy, TimeValue};
+
+/*
+    Multi-track material shared across JDW services: a Project holds named tracks, each an
+        independent list of timed packets (times relative to the project start) along with
+        where it is played and how:
+        - target: the service or synth group the track is meant for, empty for the default
+        - gain: multiplies the "amp" named arg of the track's messages
+        - offset: time added to every packet of the track
+    Tracks are merged into one stream in execution order before sending downstream.
+        Packets sharing time and order play in track order, and within a track in their
+        original sequence, so the merged stream is the same on every run.
+    Soloing any track silences every track that is not soloed; a muted track stays silent
+        even when soloed.
+
+    On the wire a project is a tagged bundle of track bundles:
+    [/bundle_info, "project"]
+    [/project_info, "demo"]
+    [
+        [/bundle_info, "track"]
+        [/track_info, "drums", "127.0.0.1:57110", 1.0, "0", 0, 0]
+        [... timed_msg bundles ...]
+    ]
+    ...
+ */
+
+pub const PROJECT_TAG: &str = "project";
+pub const PROJECT_INFO_ADDR: &str = "/project_info";
+pub const TRACK_TAG: &str = "track";
+pub const TRACK_INFO_ADDR: &str = "/track_info";
+// Named arg scaled by track gain
+pub const GAIN_ARG: &str = "amp";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub name: String,
+    pub target: String,
+    pub packets: Vec<TimedOSCPacket>,
+    pub gain: f32,
+    pub offset: BigDecimal,
+    pub muted: bool,
+    pub solo: bool
+}
+
+impl Track {
+    pub fn new(name: &str, packets: Vec<TimedOSCPacket>) -> Track {
+        Track {
+            name: name.to_string(),
+            target: String::new(),
+            packets,
+            gain: 1.0,
+            offset: BigDecimal::from(0),
+            muted: false,
+            solo: false
+        }
+    }
+
+    pub fn with_target(mut self, target: &str) -> Track {
+        self.target = target.to_string();
+        self
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Track {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_offset(mut self, offset: BigDecimal) -> Track {
+        self.offset = offset;
+        self
+    }
+
+    pub fn muted(mut self, muted: bool) -> Track {
+        self.muted = muted;
+        self
+    }
+
+    pub fn solo(mut self, solo: bool) -> Track {
+        self.solo = solo;
+        self
+    }
+
+    /*
+        The packets as played: offset applied to their times and gain to the float "amp"
+            args of their messages. Packets wrapping bundles are only shifted in time.
+     */
+    pub fn rendered(&self) -> Vec<TimedOSCPacket> {
+        self.packets.iter().map(|packet| {
+            let mut packet = packet.clone();
+            packet.time += &self.offset;
+            if let OscPacket::Message(msg) = &mut packet.packet {
+                if let Some(OscType::Float(amp)) = named_arg_index(&msg.args, GAIN_ARG).and_then(|index| msg.args.get_mut(index)) {
+                    *amp *= self.gain;
+                }
+            }
+            packet
+        }).collect()
+    }
+
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = OscMessage {
+            addr: TRACK_INFO_ADDR.to_string(),
+            args: vec![
+                OscType::String(self.name.clone()),
+                OscType::String(self.target.clone()),
+                OscType::Float(self.gain),
+                TimeValue::new(&self.offset, &TimePolicy::default()).to_osc_arg(),
+                OscType::Int(self.muted as i32),
+                OscType::Int(self.solo as i32)
+            ]
+        };
+
+        let packets = self.packets.iter().map(|packet| OscPacket::Bundle(packet.to_bundle()));
+        TaggedBundle::with_contents(TRACK_TAG, std::iter::once(OscPacket::Message(info)).chain(packets).collect()).to_bundle()
+    }
+}
+
+impl FromTaggedBundle for Track {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        if bundle.bundle_tag != TRACK_TAG {
+            return Err(format!("Attempted to parse {} as {} bundle", bundle.bundle_tag, TRACK_TAG));
+        }
+
+        let info = bundle.get_message(0)?;
+        info.expect_addr(TRACK_INFO_ADDR)?;
+        let offset_arg = info.args.get(3).ok_or("Track info has no offset")?;
+        let packets = bundle.iter_bundles()
+            .map(|packet| TimedOSCPacket::from_bundle(TaggedBundle::new(packet)?))
+            .collect::<Result<Vec<TimedOSCPacket>, String>>()?;
+
+        Ok(Track {
+            name: info.get_string_at(0, "name")?,
+            target: info.get_string_at(1, "target")?,
+            packets,
+            gain: info.get_float_at(2, "gain")?,
+            offset: TimeValue::from_osc_arg(offset_arg, &TimePolicy::default())?.into(),
+            muted: info.get_int_at(4, "muted")? != 0,
+            solo: info.get_int_at(5, "solo")? != 0
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub name: String,
+    pub tracks: Vec<Track>
+}
+
+impl Project {
+    pub fn new(name: &str) -> Project {
+        Project { name: name.to_string(), tracks: Vec::new() }
+    }
+
+    pub fn with_track(mut self, track: Track) -> Project {
+        self.tracks.push(track);
+        self
+    }
+
+    pub fn track(&self, name: &str) -> Option<&Track> {
+        self.tracks.iter().find(|track| track.name == name)
+    }
+
+    // For muting, soloing or editing a track in place
+    pub fn track_mut(&mut self, name: &str) -> Option<&mut Track> {
+        self.tracks.iter_mut().find(|track| track.name == name)
+    }
+
+    // All audible tracks merged, see merge_tracks
+    pub fn merged(&self) -> Vec<TimedOSCPacket> {
+        merge_tracks(&self.tracks)
+    }
+
+    pub fn to_bundle(&self) -> OscBundle {
+        let info = OscMessage { addr: PROJECT_INFO_ADDR.to_string(), args: vec![OscType::String(self.name.clone())] };
+        let tracks = self.tracks.iter().map(|track| OscPacket::Bundle(track.to_bundle()));
+        TaggedBundle::with_contents(PROJECT_TAG, std::iter::once(OscPacket::Message(info)).chain(tracks).collect()).to_bundle()
+    }
+}
+
+impl FromTaggedBundle for Project {
+    fn from_tagged_bundle(bundle: TaggedBundle) -> Result<Self, String> {
+        if bundle.bundle_tag != PROJECT_TAG {
+            return Err(format!("Attempted to parse {} as {} bundle", bundle.bundle_tag, PROJECT_TAG));
+        }
+
+        let info = bundle.get_message(0)?;
+        info.expect_addr(PROJECT_INFO_ADDR)?;
+        let tracks = bundle.iter_bundles()
+            .map(|track| Track::from_tagged_bundle(TaggedBundle::new(track)?))
+            .collect::<Result<Vec<Track>, String>>()?;
+
+        Ok(Project { name: info.get_string_at(0, "name")?, tracks })
+    }
+}
+
+// Whether the track plays, given all tracks of the mix
+pub fn is_audible(track: &Track, tracks: &[Track]) -> bool {
+    let soloing = tracks.iter().any(|track| track.solo);
+    !track.muted && (track.solo || !soloing)
+}
+
+pub fn merge_tracks(tracks: &[Track]) -> Vec<TimedOSCPacket> {
+    merge_tracks_labelled(tracks).into_iter().map(|(_, packet)| packet).collect()
+}
+
+// As merge_tracks, with the name of the track each packet came from
+pub fn merge_tracks_labelled(tracks: &[Track]) -> Vec<(&str, TimedOSCPacket)> {
+    let mut merged: Vec<(usize, TimedOSCPacket)> = tracks.iter()
+        .enumerate()
+        .filter(|(_, track)| is_audible(track, tracks))
+        .flat_map(|(index, track)| track.rendered().into_iter().map(move |packet| (index, packet)))
+        .collect();
+
+    // Stable, so packets of a track that compare equal keep their original sequence
+    merged.sort_by(|(a_track, a), (b_track, b)| a.schedule_cmp(b).then(a_track.cmp(b_track)));
+    merged.into_iter().map(|(index, packet)| (tracks[index].name.as_str(), packet)).collect()
+}
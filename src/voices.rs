@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::supercollider::NodeIdAllocator;
+
+/*
+    Maps the external string ids carried by JDW note messages to SuperCollider node ids.
+    Shared between threads as-is; a /note_on allocates (or retriggers) a voice, /note_modify
+        looks it up and /note_off releases it.
+
+    let voices = VoiceRegistry::new();
+    let node_id = voices.note_on("lead_17");
+    ...
+    if let Some(node_id) = voices.note_off("lead_17") { ... }
+ */
+
+struct Voice {
+    node_id: i32,
+    released_at: Option<Instant>
+}
+
+pub struct VoiceRegistry {
+    allocator: NodeIdAllocator,
+    voices: Mutex<HashMap<String, Voice>>,
+    // How long a released voice can still be looked up, for messages racing the note-off
+    release_grace: Duration
+}
+
+impl Default for VoiceRegistry {
+    fn default() -> Self {
+        VoiceRegistry::with_allocator(NodeIdAllocator::new())
+    }
+}
+
+impl VoiceRegistry {
+    pub fn new() -> VoiceRegistry {
+        VoiceRegistry::default()
+    }
+
+    pub fn with_allocator(allocator: NodeIdAllocator) -> VoiceRegistry {
+        VoiceRegistry {
+            allocator,
+            voices: Mutex::new(HashMap::new()),
+            release_grace: Duration::ZERO
+        }
+    }
+
+    pub fn release_grace(mut self, grace: Duration) -> VoiceRegistry {
+        self.release_grace = grace;
+        self
+    }
+
+    // A panicking consumer must not lock everyone else out, so poisoning is ignored
+    fn voices(&self) -> MutexGuard<'_, HashMap<String, Voice>> {
+        self.voices.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn is_live(&self, voice: &Voice, now: Instant) -> bool {
+        voice.released_at.is_none_or(|released| now.duration_since(released) < self.release_grace)
+    }
+
+    /*
+        Node id for a starting note. A still playing (or just released) voice with the same
+            external id is reused, so that a repeated note_on retriggers instead of leaking a node.
+     */
+    pub fn note_on(&self, external_id: &str) -> i32 {
+        let now = Instant::now();
+        let mut voices = self.voices();
+
+        if let Some(voice) = voices.get_mut(external_id).filter(|voice| self.is_live(voice, now)) {
+            voice.released_at = None;
+            return voice.node_id;
+        }
+
+        let node_id = self.allocator.next_id();
+        voices.insert(external_id.to_string(), Voice { node_id, released_at: None });
+        node_id
+    }
+
+    // Node id of a playing voice, or of a released one within the grace period
+    pub fn lookup(&self, external_id: &str) -> Option<i32> {
+        let now = Instant::now();
+        self.voices().get(external_id)
+            .filter(|voice| self.is_live(voice, now))
+            .map(|voice| voice.node_id)
+    }
+
+    // Releases the voice, returning its node id if it was playing
+    pub fn note_off(&self, external_id: &str) -> Option<i32> {
+        let now = Instant::now();
+        let mut voices = self.voices();
+
+        let voice = voices.get_mut(external_id).filter(|voice| voice.released_at.is_none())?;
+        let node_id = voice.node_id;
+
+        if self.release_grace.is_zero() {
+            voices.remove(external_id);
+        } else {
+            voice.released_at = Some(now);
+        }
+
+        // Released voices are otherwise only cleaned up when their id is reused
+        voices.retain(|_, voice| self.is_live(voice, now));
+        Some(node_id)
+    }
+
+    // Playing voices, not counting released ones
+    pub fn active_count(&self) -> usize {
+        self.voices().values().filter(|voice| voice.released_at.is_none()).count()
+    }
+}
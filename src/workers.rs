@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use log::error;
+
+use crate::memory::EvictionPolicy;
+
+/*
+    Fixed set of threads taking jobs from a shared queue, used by OSCStack to run handlers
+        registered as thread-safe next to the receive loop instead of on it.
+    A panicking job is logged and does not take its worker down with it.
+ */
+
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+pub(crate) struct WorkerPool {
+    sender: Sender<Job>
+}
+
+impl WorkerPool {
+    pub(crate) fn new(threads: usize) -> WorkerPool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("osc-worker-{}", index))
+                .spawn(move || loop {
+                    // Holding the lock only while waiting lets the other workers run their jobs
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return
+                    };
+                    match job {
+                        Ok(job) => if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            error!("OSC handler panicked on worker thread {}", index);
+                        },
+                        // Pool dropped
+                        Err(_) => return
+                    }
+                })
+                .expect("Failed to spawn OSC worker thread");
+        }
+
+        WorkerPool { sender }
+    }
+
+    // Sized to the available cores
+    pub(crate) fn with_default_size() -> WorkerPool {
+        WorkerPool::new(thread::available_parallelism().map(|n| n.get()).unwrap_or(4))
+    }
+
+    pub(crate) fn execute(&self, job: Job) {
+        // Workers only stop once the pool is dropped, so this cannot fail while self exists
+        let _ = self.sender.send(job);
+    }
+}
+
+/*
+    Approximate bytes held by the queues of a stack, checked against an optional limit.
+    Shared by all handler queues so that one stalled handler can't take all memory while
+        the others fill up as well.
+ */
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    used: AtomicUsize,
+    // usize::MAX when unlimited
+    limit: AtomicUsize
+}
+
+impl Default for MemoryBudget {
+    fn default() -> MemoryBudget {
+        MemoryBudget { used: AtomicUsize::new(0), limit: AtomicUsize::new(usize::MAX) }
+    }
+}
+
+impl MemoryBudget {
+    pub(crate) fn set_limit(&self, bytes: usize) {
+        self.limit.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.used() > self.limit.load(Ordering::Relaxed)
+    }
+
+    // For items that must be queued even over budget
+    pub(crate) fn force_reserve(&self, bytes: usize) {
+        self.used.fetch_add(bytes, Ordering::AcqRel);
+    }
+
+    // Takes the bytes from the budget if they fit
+    pub(crate) fn try_reserve(&self, bytes: usize) -> bool {
+        let limit = self.limit.load(Ordering::Relaxed);
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| used.checked_add(bytes).filter(|total| *total <= limit))
+            .is_ok()
+    }
+
+    // For memory that is held regardless, such as packets waiting for reordering
+    pub(crate) fn adjust(&self, previous: usize, current: usize) {
+        if current > previous {
+            self.force_reserve(current - previous);
+        } else {
+            self.release(previous - current);
+        }
+    }
+
+    pub(crate) fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+// Bytes taken from a budget, given back when dropped; also when the job holding it panics
+pub(crate) struct Reservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize
+}
+
+impl Reservation {
+    pub(crate) fn try_new(budget: Arc<MemoryBudget>, bytes: usize) -> Option<Reservation> {
+        budget.try_reserve(bytes).then_some(Reservation { budget, bytes })
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+pub(crate) enum QueueError {
+    // The receiving thread has stopped
+    Closed,
+    // The item does not fit the budget, even after evicting what the policy allows
+    OverBudget
+}
+
+struct QueueState<T> {
+    // Items with their size as charged to the budget
+    items: VecDeque<(T, usize)>,
+    // How many items at the front were queued with send_urgent, kept in the order they came
+    urgent: usize,
+    sender_dropped: bool,
+    receiver_dropped: bool
+}
+
+struct Queue<T> {
+    state: Mutex<QueueState<T>>,
+    available: Condvar,
+    budget: Arc<MemoryBudget>
+}
+
+impl<T> Queue<T> {
+    fn state(&self) -> MutexGuard<'_, QueueState<T>> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/*
+    Single consumer queue charging its items to a MemoryBudget, used instead of an mpsc
+        channel for dedicated and sharded handlers so that queued items can be evicted.
+ */
+pub(crate) fn budgeted_queue<T>(budget: Arc<MemoryBudget>) -> (QueueSender<T>, QueueReceiver<T>) {
+    let queue = Arc::new(Queue {
+        state: Mutex::new(QueueState { items: VecDeque::new(), urgent: 0, sender_dropped: false, receiver_dropped: false }),
+        available: Condvar::new(),
+        budget
+    });
+    (QueueSender { queue: queue.clone() }, QueueReceiver { queue })
+}
+
+pub(crate) struct QueueSender<T> {
+    queue: Arc<Queue<T>>
+}
+
+impl<T> QueueSender<T> {
+    // Queues the item, returning how many older items were evicted to make room for it
+    pub(crate) fn send(&self, item: T, size: usize, policy: EvictionPolicy) -> Result<usize, QueueError> {
+        let mut state = self.queue.state();
+        if state.receiver_dropped {
+            return Err(QueueError::Closed);
+        }
+
+        let mut evicted = 0;
+        while !self.queue.budget.try_reserve(size) {
+            if policy == EvictionPolicy::DropNewest {
+                return Err(QueueError::OverBudget);
+            }
+            // Urgent items are never evicted
+            let oldest = state.urgent;
+            match state.items.remove(oldest) {
+                Some((_, oldest_size)) => {
+                    self.queue.budget.release(oldest_size);
+                    evicted += 1;
+                },
+                None => return Err(QueueError::OverBudget)
+            }
+        }
+
+        state.items.push_back((item, size));
+        self.queue.available.notify_one();
+        Ok(evicted)
+    }
+
+    // Drops everything queued, returning how many items that was
+    pub(crate) fn clear(&self) -> usize {
+        let mut state = self.queue.state();
+        let cleared = state.items.len();
+        state.urgent = 0;
+        let bytes: usize = state.items.drain(..).map(|(_, size)| size).sum();
+        self.queue.budget.release(bytes);
+        cleared
+    }
+
+    // Queues the item ahead of everything but earlier urgent items, even over budget
+    pub(crate) fn send_urgent(&self, item: T, size: usize) -> Result<(), QueueError> {
+        let mut state = self.queue.state();
+        if state.receiver_dropped {
+            return Err(QueueError::Closed);
+        }
+
+        self.queue.budget.force_reserve(size);
+        let position = state.urgent;
+        state.items.insert(position, (item, size));
+        state.urgent += 1;
+        self.queue.available.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        self.queue.state().sender_dropped = true;
+        self.queue.available.notify_all();
+    }
+}
+
+pub(crate) struct QueueReceiver<T> {
+    queue: Arc<Queue<T>>
+}
+
+impl<T> QueueReceiver<T> {
+    // Blocks for the next item, None once the queue is empty and the sender dropped
+    pub(crate) fn recv(&self) -> Option<T> {
+        let mut state = self.queue.state();
+        loop {
+            if let Some((item, size)) = state.items.pop_front() {
+                state.urgent = state.urgent.saturating_sub(1);
+                self.queue.budget.release(size);
+                return Some(item);
+            }
+            if state.sender_dropped {
+                return None;
+            }
+            state = self.queue.available.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+impl<T> Drop for QueueReceiver<T> {
+    // Also runs when the handler thread panics, returning what it left queued to the budget
+    fn drop(&mut self) {
+        let mut state = self.queue.state();
+        state.receiver_dropped = true;
+        state.urgent = 0;
+        let queued: usize = state.items.drain(..).map(|(_, size)| size).sum();
+        self.queue.budget.release(queued);
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use js_sys::{ArrayBuffer, JsString, Uint8Array};
+use log::warn;
+use rosc::{OscMessage, OscPacket};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+use crate::codec::{default_codec, SharedCodec};
+use crate::model::TaggedBundle;
+use crate::text;
+
+/*
+    Browser counterpart to OSCStack, for tools running as wasm32 that talk to JDW services
+        through a WebSocket bridge. Every binary frame holds one encoded packet (plain OSC
+        unless given another codec); text frames are read in the text format, see text.rs.
+
+    let stack = WsStack::connect("ws://localhost:13339")?
+        .on_message("/note_on", |msg| draw_note(msg))
+        .on_tbundle("timed_msg", |bundle| schedule(bundle));
+    stack.send(&OscPacket::Message(msg))?;
+
+    There is no receive loop to begin: frames are dispatched from the browser's event loop
+        as they arrive, for as long as the stack is kept alive. Dropping it closes the socket.
+ */
+
+type MessageOperation = Box<dyn Fn(OscMessage)>;
+type TbundleOperation = Box<dyn Fn(TaggedBundle)>;
+
+#[derive(Default)]
+struct Handlers {
+    message_operations: HashMap<String, Vec<MessageOperation>>,
+    tbundle_operations: HashMap<String, Vec<TbundleOperation>>,
+    warning_operation: Option<Box<dyn Fn(String)>>
+}
+
+impl Handlers {
+    fn warn(&self, warning: String) {
+        warn!("{}", warning);
+        if let Some(op) = &self.warning_operation {
+            op(warning);
+        }
+    }
+
+    fn interpret(&self, packet: OscPacket) {
+        match packet {
+            OscPacket::Message(msg) => match self.message_operations.get(&msg.addr) {
+                Some(operations) => operations.iter().for_each(|op| op(msg.clone())),
+                None => self.warn(format!("No handler for message {}", msg.addr))
+            },
+            OscPacket::Bundle(bundle) => match TaggedBundle::new(&bundle) {
+                Ok(tagged) => match self.tbundle_operations.get(&tagged.bundle_tag) {
+                    Some(operations) => operations.iter().for_each(|op| op(tagged.clone())),
+                    None => self.warn(format!("No handler for bundle tag {}", tagged.bundle_tag))
+                },
+                Err(e) => self.warn(format!("Untagged bundle: {}", e))
+            }
+        }
+    }
+}
+
+pub struct WsStack {
+    socket: WebSocket,
+    codec: SharedCodec,
+    handlers: Rc<RefCell<Handlers>>,
+    // Kept alive for as long as the socket may call it
+    _on_frame: Closure<dyn FnMut(MessageEvent)>
+}
+
+impl WsStack {
+    pub fn connect(url: &str) -> Result<WsStack, String> {
+        WsStack::connect_with_codec(url, default_codec())
+    }
+
+    pub fn connect_with_codec(url: &str, codec: SharedCodec) -> Result<WsStack, String> {
+        let socket = WebSocket::new(url).map_err(|e| format!("Failed to open WebSocket to {}: {:?}", url, e))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let handlers = Rc::new(RefCell::new(Handlers::default()));
+        let frame_handlers = handlers.clone();
+        let frame_codec = codec.clone();
+        let on_frame = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let handlers = frame_handlers.borrow();
+            match decode_frame(&event, &frame_codec) {
+                Ok(packet) => handlers.interpret(packet),
+                Err(e) => handlers.warn(e)
+            }
+        });
+        socket.set_onmessage(Some(on_frame.as_ref().unchecked_ref()));
+
+        Ok(WsStack { socket, codec, handlers, _on_frame: on_frame })
+    }
+
+    pub fn on_message(self, addr: &str, operations: impl Fn(OscMessage) + 'static) -> WsStack {
+        self.handlers.borrow_mut().message_operations.entry(addr.to_string()).or_default().push(Box::new(operations));
+        self
+    }
+
+    pub fn on_tbundle(self, tag: &str, operations: impl Fn(TaggedBundle) + 'static) -> WsStack {
+        self.handlers.borrow_mut().tbundle_operations.entry(tag.to_string()).or_default().push(Box::new(operations));
+        self
+    }
+
+    // Frames that could not be read or had no handler, in addition to the regular log output
+    pub fn on_warning(self, operations: impl Fn(String) + 'static) -> WsStack {
+        self.handlers.borrow_mut().warning_operation = Some(Box::new(operations));
+        self
+    }
+
+    // Fails while the socket is still connecting, see is_open
+    pub fn send(&self, packet: &OscPacket) -> Result<(), String> {
+        let bytes = self.codec.encode(packet)?;
+        self.socket.send_with_u8_array(&bytes).map_err(|e| format!("Failed to send over WebSocket: {:?}", e))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.socket.ready_state() == WebSocket::OPEN
+    }
+
+    pub fn url(&self) -> String {
+        self.socket.url()
+    }
+}
+
+impl Drop for WsStack {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+    }
+}
+
+fn decode_frame(event: &MessageEvent, codec: &SharedCodec) -> Result<OscPacket, String> {
+    let data = event.data();
+    if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+        let bytes = Uint8Array::new(buffer).to_vec();
+        return codec.decode(&bytes).map(|(_, packet)| packet);
+    }
+    match data.dyn_ref::<JsString>().and_then(|text| text.as_string()) {
+        Some(text) => text::parse_packet(&text),
+        None => Err("WebSocket frame is neither binary nor text".to_string())
+    }
+}
@@ -0,0 +1,55 @@
+// The receiver is part of the stack layer
+#![cfg(feature = "stack")]
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use jdw_osc_lib::receiver::OscReceiver;
+use rosc::{OscMessage, OscPacket, OscType};
+
+fn message(addr: &str, value: i32) -> OscPacket {
+    OscPacket::Message(OscMessage { addr: addr.to_string(), args: vec![OscType::Int(value)] })
+}
+
+#[test]
+fn every_packet_in_a_datagram_is_received() {
+    let mut receiver = OscReceiver::bind("127.0.0.1:0").unwrap();
+    let target = receiver.local_addr().unwrap();
+
+    let packets = vec![message("/first", 1), message("/second", 2), message("/third", 3)];
+    let mut datagram = Vec::new();
+    for packet in &packets {
+        datagram.extend(rosc::encoder::encode(packet).unwrap());
+    }
+
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.send_to(&datagram, target).unwrap();
+
+    for (index, expected) in packets.iter().enumerate() {
+        let received = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(&received, expected);
+        assert_eq!(receiver.last_packet_index(), index);
+        assert_eq!(receiver.last_packet_bytes(), rosc::encoder::encode(expected).unwrap().as_slice());
+    }
+
+    let info = receiver.last_datagram();
+    assert_eq!(info.size, datagram.len());
+    assert_eq!(info.packets, 3);
+    assert_eq!(info.trailing_bytes, 0);
+}
+
+#[test]
+fn undecodable_remainder_is_reported_as_trailing_bytes() {
+    let mut receiver = OscReceiver::bind("127.0.0.1:0").unwrap();
+    let target = receiver.local_addr().unwrap();
+
+    let mut datagram = rosc::encoder::encode(&message("/only", 1)).unwrap();
+    datagram.extend([0xff, 0xff, 0xff, 0xff]);
+
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.send_to(&datagram, target).unwrap();
+
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(2)).unwrap(), message("/only", 1));
+    assert_eq!(receiver.last_datagram().packets, 1);
+    assert_eq!(receiver.last_datagram().trailing_bytes, 4);
+}
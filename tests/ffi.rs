@@ -0,0 +1,43 @@
+// C ABI, called the way a foreign caller would
+#![cfg(feature = "ffi")]
+
+use std::ffi::CStr;
+use std::ptr;
+
+use jdw_osc_lib::ffi::*;
+
+#[test]
+fn encoding_without_a_length_fails() {
+    unsafe {
+        let msg = jdw_message_new(c"/note_on".as_ptr());
+        assert!(jdw_message_encode(msg, ptr::null_mut()).is_null());
+        assert!(!jdw_last_error().is_null());
+
+        let mut len = 0;
+        let data = jdw_message_encode(msg, &mut len);
+        assert!(!data.is_null());
+        assert!(len > 0);
+        jdw_bytes_free(data, len);
+        jdw_message_free(msg);
+    }
+}
+
+#[test]
+fn timed_msg_times_are_parsed_as_jdw_times() {
+    unsafe {
+        let timed = jdw_timed_msg_new(c"1e20000000".as_ptr(), jdw_message_new(c"/note_on".as_ptr()));
+        assert!(timed.is_null());
+        let error = CStr::from_ptr(jdw_last_error()).to_str().unwrap();
+        assert!(error.contains("out of range"), "{}", error);
+
+        let timed = jdw_timed_msg_new(c"0.1234567891".as_ptr(), jdw_message_new(c"/note_on".as_ptr()));
+        assert!(!timed.is_null());
+        let mut len = 0;
+        let data = jdw_bundle_encode(timed, &mut len);
+        let bytes = std::slice::from_raw_parts(data, len);
+        assert!(bytes.windows(11).any(|window| window == b"0.123456789"));
+        assert!(!bytes.windows(12).any(|window| window == b"0.1234567891"));
+        jdw_bytes_free(data, len);
+        jdw_bundle_free(timed);
+    }
+}
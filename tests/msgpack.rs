@@ -0,0 +1,24 @@
+// MessagePack codec limits
+#![cfg(feature = "msgpack")]
+
+use jdw_osc_lib::codec::PacketCodec;
+use jdw_osc_lib::msgpack::MsgPackCodec;
+use rosc::{OscArray, OscMessage, OscPacket, OscType};
+
+fn nested_arrays(depth: usize) -> OscPacket {
+    let arg = (0..depth).fold(OscType::Int(1), |inner, _| OscType::Array(OscArray { content: vec![inner] }));
+    OscPacket::Message(OscMessage { addr: "/nested".to_string(), args: vec![arg] })
+}
+
+#[test]
+fn nesting_is_limited() {
+    let codec = MsgPackCodec;
+    let shallow = nested_arrays(32);
+    assert_eq!(codec.decode(&codec.encode(&shallow).unwrap()).unwrap().1, shallow);
+    assert!(codec.decode(&codec.encode(&nested_arrays(33)).unwrap()).is_err());
+
+    // A datagram of nothing but single element arrays
+    let mut hostile = vec![0x93, 0x00, 0xa2, b'/', b'a', 0x91];
+    hostile.extend(std::iter::repeat_n(0x91, 300_000));
+    assert!(codec.decode(&hostile).is_err());
+}
@@ -0,0 +1,20 @@
+// Scale and pitch helpers
+#![cfg(feature = "model")]
+
+use jdw_osc_lib::music::Scale;
+
+#[test]
+fn scale_steps() {
+    let scale = Scale::major(0);
+    assert_eq!(scale.step(60.0, 2), 64.0);
+    assert_eq!(scale.step(61.0, -1), 59.0);
+    assert_eq!(scale.step(60.0, 0), 60.0);
+}
+
+#[test]
+fn scale_steps_leave_non_finite_notes_alone() {
+    let scale = Scale::minor_chord(1);
+    assert!(scale.step(f32::NAN, 1).is_nan());
+    assert_eq!(scale.step(f32::INFINITY, 3), f32::INFINITY);
+    assert_eq!(scale.step(f32::NEG_INFINITY, -3), f32::NEG_INFINITY);
+}
@@ -0,0 +1,301 @@
+// Build/parse pairs are part of the model layer
+#![cfg(feature = "model")]
+
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use bigdecimal::BigDecimal;
+use jdw_osc_lib::core::diff::OscDiff;
+use jdw_osc_lib::{assert_osc_eq, deadline};
+use jdw_osc_lib::envelope::{CurveShape, Envelope};
+use jdw_osc_lib::model::{OscArgHandler, TaggedBundle, TimedOSCPacket};
+use jdw_osc_lib::progress::Progress;
+use jdw_osc_lib::queue_update::{Queue, QueueItem, QueueUpdate};
+use jdw_osc_lib::reply::Reply;
+use jdw_osc_lib::roundtrip::{roundtrip, Roundtrip};
+use jdw_osc_lib::supercollider::{AddAction, BAllocRead, DRecv, NFree, NSet, SNew};
+use jdw_osc_lib::text;
+use jdw_osc_lib::time_value::{TimeEncoding, TimePolicy, TimeValue};
+use jdw_osc_lib::tracks::{Project, Track};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+fn check<T: Roundtrip>(value: T) {
+    if let Err(e) = roundtrip(&value) {
+        panic!("{}", e);
+    }
+}
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+fn note_on(freq: f32) -> OscPacket {
+    OscPacket::Message(OscMessage {
+        addr: "/note_on".to_string(),
+        args: vec![OscType::String("blip".to_string()), OscType::Float(freq)]
+    })
+}
+
+#[test]
+fn supercollider_commands() {
+    check(SNew::new("blip", 1001));
+    check(SNew::new("blip", 1002).with_target(AddAction::Replace, 1001).with_control("freq", 440.0).with_control("amp", 0.25));
+    check(NSet::new(1001));
+    check(NSet::new(1001).with_control("gate", 0.0).with_control("cutoff", -1.5));
+    check(NFree::new(vec![]));
+    check(NFree::new(vec![1001, 1002, i32::MAX]));
+    check(BAllocRead::new(3, "/samples/kick.wav"));
+    check(DRecv::new(vec![0, 1, 2, 255, 7]));
+}
+
+#[test]
+fn replies() {
+    check(Reply::Ok { request: "/nrt_record".to_string() });
+    check(Reply::Error { request: "/nrt_record".to_string(), reason: "No synth named blip".to_string() });
+    check(Progress::new("render-42", 37.5, "Rendering bar 12 of 32"));
+    check(Progress::new("render-43", 100.0, ""));
+}
+
+#[test]
+fn timed_packets() {
+    check(TimedOSCPacket::new(decimal("0"), note_on(440.0)));
+    check(TimedOSCPacket::new(decimal("0.125"), note_on(220.0)).with_probability(0.5));
+    check(TimedOSCPacket::new(decimal("12.000001"), note_on(110.0))
+        .with_metadata("voice", 3.0)
+        .with_metadata("accent", 0.8)
+        .with_order(-2));
+    check(TimedOSCPacket::new(decimal("1"), OscPacket::Bundle(TaggedBundle::with_contents("chord", vec![note_on(440.0), note_on(550.0)]).to_bundle())));
+}
+
+#[test]
+fn timed_packet_limits() {
+    check(TimedOSCPacket::new(decimal("0.123456789"), note_on(440.0)));
+    let fine = TimedOSCPacket::new(decimal("0.1234567891"), note_on(440.0));
+    assert!(roundtrip(&fine).is_err());
+    let parsed = TimedOSCPacket::parse(&fine.build()).unwrap();
+    assert_eq!(parsed.time, decimal("0.123456789"));
+
+    check(TimedOSCPacket::new(decimal("1"), note_on(440.0)).with_order(1 << 24));
+    check(TimedOSCPacket::new(decimal("1"), note_on(440.0)).with_order(-(1 << 24)));
+    let large = TimedOSCPacket::new(decimal("1"), note_on(440.0)).with_order((1 << 24) + 1);
+    assert!(roundtrip(&large).is_err());
+    assert_eq!(TimedOSCPacket::parse(&large.build()).unwrap().order, 1 << 24);
+}
+
+#[test]
+fn timed_packets_with_unknown_info_args() {
+    let mut bundle = TimedOSCPacket::new(decimal("0.5"), note_on(440.0)).with_metadata("voice", 2.0).to_bundle();
+    let OscPacket::Message(info) = &mut bundle.content[1] else { panic!("Expected /timed_msg_info") };
+    info.args.extend([OscType::String("legacy".to_string()), OscType::Int(7)]);
+
+    let parsed = TimedOSCPacket::parse(&OscPacket::Bundle(bundle)).unwrap();
+    assert_eq!(parsed.metadata_value("voice"), Some(2.0));
+    assert_eq!(parsed.metadata_value("legacy"), None);
+}
+
+#[test]
+fn stray_timed_info_args_only_drop_themselves() {
+    let mut bundle = TimedOSCPacket::new(decimal("0.5"), note_on(440.0)).to_bundle();
+    let OscPacket::Message(info) = &mut bundle.content[1] else { panic!("Expected /timed_msg_info") };
+    info.args.extend([
+        OscType::String("voice".to_string()), OscType::Float(2.0),
+        OscType::Int(7),
+        OscType::String("gain".to_string()), OscType::Float(0.5),
+        OscType::String("nameless".to_string()),
+        OscType::String("pan".to_string()), OscType::Float(-1.0)
+    ]);
+
+    let parsed = TimedOSCPacket::parse(&OscPacket::Bundle(bundle)).unwrap();
+    assert_eq!(parsed.metadata_value("voice"), Some(2.0));
+    assert_eq!(parsed.metadata_value("gain"), Some(0.5));
+    assert_eq!(parsed.metadata_value("pan"), Some(-1.0));
+    assert_eq!(parsed.metadata_value("nameless"), None);
+}
+
+#[test]
+fn varargs_are_validated() {
+    let named = OscMessage { addr: "/s_new".to_string(), args: vec![OscType::Int(1), OscType::String("freq".to_string()), OscType::Float(440.0)] };
+    assert_eq!(named.get_varargs(1).unwrap().len(), 2);
+    let shifted = OscMessage { addr: "/s_new".to_string(), args: vec![OscType::Int(1), OscType::Float(440.0), OscType::String("freq".to_string())] };
+    assert!(shifted.get_varargs(1).is_err());
+}
+
+#[test]
+fn non_finite_floats_as_text() {
+    let args = vec![
+        OscType::Float(f32::INFINITY),
+        OscType::Float(f32::NEG_INFINITY),
+        OscType::Double(f64::INFINITY),
+        OscType::Double(f64::NEG_INFINITY),
+        OscType::Inf
+    ];
+    let msg = OscPacket::Message(OscMessage { addr: "/limits".to_string(), args });
+    assert_eq!(text::parse_packet(&text::to_text(&msg)).unwrap(), msg);
+
+    let nan = OscPacket::Message(OscMessage { addr: "/nan".to_string(), args: vec![OscType::Float(f32::NAN), OscType::Double(f64::NAN)] });
+    assert_osc_eq!(nan, text::parse_packet(&text::to_text(&nan)).unwrap());
+}
+
+#[test]
+fn blobs_as_text() {
+    let blob = OscPacket::Message(OscMessage { addr: "/blob".to_string(), args: vec![OscType::Blob(vec![0x00, 0xab, 0xff])] });
+    assert_eq!(text::parse_packet(&text::to_text(&blob)).unwrap(), blob);
+    // Even byte lengths that would be cut inside a character
+    assert!(text::parse_packet("/blob #aéa").is_err());
+    assert!(text::parse_packet("/blob #é").is_err());
+    assert!(text::parse_packet("/blob #abc").is_err());
+}
+
+#[test]
+fn osc_diffs_compare_floats_as_sent() {
+    let message = |args| OscMessage { addr: "/limits".to_string(), args };
+    assert_eq!(message(vec![OscType::Float(f32::NAN)]).osc_diff(&message(vec![OscType::Float(f32::NAN)])), None);
+    assert_eq!(message(vec![OscType::Double(f64::NAN)]).osc_diff(&message(vec![OscType::Double(f64::NAN)])), None);
+    assert_eq!(
+        message(vec![OscType::Float(0.0), OscType::Double(1.0)]).differences(&message(vec![OscType::Float(-0.0), OscType::Double(f64::NAN)])),
+        vec!["arg 0: expected Float(0.0), got Float(-0.0)".to_string(), "arg 1: expected Double(1.0), got Double(NaN)".to_string()]
+    );
+}
+
+#[test]
+fn double_times() {
+    for time in ["0", "0.1", "0.125", "12.000001", "-3.5"] {
+        let packet = TimedOSCPacket::new(decimal(time), note_on(440.0)).with_probability(0.5);
+        let bundle = packet.to_bundle_with(TimeEncoding::DoubleArg).unwrap();
+        assert!(matches!(&bundle.content[1], OscPacket::Message(info) if matches!(info.args[0], OscType::Double(_))));
+        let parsed = TimedOSCPacket::from_bundle(TaggedBundle::new(&bundle).unwrap()).unwrap();
+        assert_eq!(parsed, packet, "time {}", time);
+    }
+
+    // More significant digits than a double holds
+    let precise = TimedOSCPacket::new(decimal("123456789.123456789"), note_on(440.0));
+    assert!(precise.to_bundle_with(TimeEncoding::DoubleArg).is_err());
+    assert!(precise.to_bundle_with(TimeEncoding::FloatArg).is_err());
+    assert!(precise.to_bundle_with(TimeEncoding::DecimalString).is_ok());
+}
+
+#[test]
+fn huge_time_exponents() {
+    for time in ["1e20000000", "1e-20000000", "-7e999999999"] {
+        let mut bundle = TimedOSCPacket::new(decimal("0"), note_on(440.0)).to_bundle();
+        if let OscPacket::Message(info) = &mut bundle.content[1] {
+            info.args[0] = OscType::String(time.to_string());
+        }
+        let started = std::time::Instant::now();
+        assert!(TimedOSCPacket::from_bundle(TaggedBundle::new(&bundle).unwrap()).is_err(), "time {}", time);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1), "time {}", time);
+    }
+
+    // Zero is fine with any exponent
+    assert!(TimeValue::parse("0e20000000", &TimePolicy::default()).is_ok());
+}
+
+#[test]
+fn float_times() {
+    for time in ["0", "0.1", "0.25", "-3.5"] {
+        let packet = TimedOSCPacket::new(decimal(time), note_on(440.0));
+        let bundle = packet.to_bundle_with(TimeEncoding::FloatArg).unwrap();
+        assert!(matches!(&bundle.content[1], OscPacket::Message(info) if matches!(info.args[0], OscType::Float(_))));
+        let parsed = TimedOSCPacket::from_bundle(TaggedBundle::new(&bundle).unwrap()).unwrap();
+        assert_eq!(parsed, packet, "time {}", time);
+    }
+
+    // Fine as a double, but not as a float
+    assert!(TimedOSCPacket::new(decimal("123456.789"), note_on(440.0)).to_bundle_with(TimeEncoding::FloatArg).is_err());
+}
+
+#[test]
+fn tagged_bundles() {
+    check(TaggedBundle::with_contents("empty", vec![]));
+    check(TaggedBundle::with_contents("batch", vec![
+        note_on(440.0),
+        OscPacket::Bundle(TimedOSCPacket::new(decimal("0.5"), note_on(330.0)).to_bundle())
+    ]));
+    let mut sequenced = TaggedBundle::with_contents("note_on", vec![note_on(440.0)]);
+    sequenced.info.seq = Some(41);
+    sequenced.info.session = Some("live-a".to_string());
+    check(sequenced);
+}
+
+#[test]
+fn legacy_bundle_info_args_are_not_deadlines() {
+    let legacy = OscBundle {
+        timetag: OscTime { seconds: 0, fractional: 1 },
+        content: vec![
+            OscPacket::Message(OscMessage { addr: "/bundle_info".to_string(), args: vec![OscType::String("note_on".to_string()), OscType::String("1".to_string())] }),
+            note_on(440.0)
+        ]
+    };
+    assert_eq!(deadline::valid_until(&legacy), None);
+
+    let valid_until = UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+    let keyed = OscBundle { content: vec![OscPacket::Message(deadline::bundle_info_with_deadline("note_on", valid_until)), note_on(440.0)], ..legacy };
+    assert_eq!(deadline::valid_until(&keyed), Some(valid_until));
+}
+
+#[test]
+fn projects() {
+    let drums = Track::new("drums", vec![
+        TimedOSCPacket::new(decimal("0"), note_on(60.0)),
+        TimedOSCPacket::new(decimal("0.5"), note_on(62.0)).with_order(1)
+    ]);
+    let bass = Track::new("bass", vec![TimedOSCPacket::new(decimal("0.25"), note_on(40.0))])
+        .with_target("127.0.0.1:57110")
+        .with_gain(0.5)
+        .with_offset(decimal("4.125"))
+        .muted(true)
+        .solo(true);
+
+    check(drums.clone());
+    check(Track::new("empty", vec![]));
+    check(Project::new("empty"));
+    check(Project::new("demo").with_track(drums).with_track(bass));
+}
+
+#[test]
+fn queue_updates() {
+    let kick = QueueItem::new("kick-1", TimedOSCPacket::new(decimal("0.5"), note_on(60.0)));
+    let snare = QueueItem::new("snare-2", TimedOSCPacket::new(decimal("0"), note_on(62.0)).with_order(1));
+    let louder_kick = QueueItem::new("kick-1", TimedOSCPacket::new(decimal("0.5"), note_on(61.0)));
+
+    let add = QueueUpdate::Add { queue: "loop-a".to_string(), items: vec![kick.clone(), snare.clone()] };
+    let replace = QueueUpdate::Replace { queue: "loop-a".to_string(), items: vec![louder_kick.clone()] };
+    let remove = QueueUpdate::Remove { queue: "loop-a".to_string(), ids: vec!["snare-2".to_string(), "gone".to_string()] };
+    check(kick.clone());
+    check(add.clone());
+    check(replace.clone());
+    check(remove.clone());
+    check(QueueUpdate::Remove { queue: "loop-a".to_string(), ids: vec![] });
+
+    let mut queue = Queue::new("loop-a");
+    queue.apply(add.clone()).unwrap();
+    assert_eq!(queue.packets(), vec![snare.packet.clone(), kick.packet.clone()]);
+    // All or nothing
+    assert!(queue.apply(add).is_err());
+    assert!(queue.apply(QueueUpdate::Replace { queue: "loop-a".to_string(), items: vec![louder_kick.clone(), QueueItem::new("new", kick.packet.clone())] }).is_err());
+    assert_eq!(queue.get("kick-1"), Some(&kick));
+    assert!(queue.apply(QueueUpdate::Remove { queue: "loop-b".to_string(), ids: vec!["kick-1".to_string()] }).is_err());
+    assert!(queue.contains("kick-1"));
+
+    queue.apply(replace).unwrap();
+    queue.apply(remove).unwrap();
+    assert_eq!(queue.items(), &[louder_kick]);
+}
+
+#[test]
+fn envelopes() {
+    check(Envelope::new(0.0));
+    check(Envelope::new(0.0)
+        .then(decimal("0.1"), 1.0, CurveShape::Linear)
+        .then(decimal("0.5"), 0.5, CurveShape::Exponential)
+        .then(decimal("1.5"), 0.5, CurveShape::Step)
+        .then(decimal("2"), 0.0, CurveShape::Curve(-4.0)));
+}
+
+#[test]
+#[allow(deprecated)]
+fn schemas_keep_their_old_path() {
+    let schema: jdw_osc_lib::schema::MessageSchema = jdw_osc_lib::core::schema::MessageSchema::new("/note_on")
+        .arg("freq", jdw_osc_lib::schema::ArgType::Float);
+    assert!(schema.validate_args(&OscMessage { addr: "/note_on".to_string(), args: vec![OscType::Float(440.0)] }).is_ok());
+}
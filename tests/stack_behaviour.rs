@@ -0,0 +1,693 @@
+// Behaviour of the stack, driven through interpret() and the local transport
+#![cfg(feature = "stack")]
+
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bigdecimal::BigDecimal;
+use jdw_osc_lib::client::Pacing;
+use jdw_osc_lib::config::StackConfig;
+use jdw_osc_lib::congestion::{AdaptiveConfig, AdaptiveRate};
+use jdw_osc_lib::core::schema::{ArgType, BundleSchema, MessageSchema};
+use jdw_osc_lib::handler_context::HandlerContext;
+use jdw_osc_lib::hello::{self, Capabilities};
+use jdw_osc_lib::reply::{self, Reply};
+use jdw_osc_lib::{cancel, echo, immediate, session};
+use jdw_osc_lib::local::LOCAL_SENDER;
+use jdw_osc_lib::osc_stack::{dispatch_context, shard_by_arg, ReceiveTime};
+use jdw_osc_lib::peers::PeerTable;
+use jdw_osc_lib::progress::Progress;
+use jdw_osc_lib::queue_update::{QueueItem, QueueUpdate};
+use jdw_osc_lib::receiver::OscReceiver;
+use jdw_osc_lib::sequence::{self, SequenceEvent, SequenceTracker};
+use jdw_osc_lib::stack_controller::{PausePolicy, StackController};
+use jdw_osc_lib::supervision::SupervisionPolicy;
+use jdw_osc_lib::time_value::TimeEncoding;
+use jdw_osc_lib::prelude::*;
+
+fn message(addr: &str, args: Vec<OscType>) -> OscMessage {
+    OscMessage { addr: addr.to_string(), args }
+}
+
+// Polls until the condition holds, failing the test after two seconds
+fn eventually(what: &str, condition: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !condition() {
+        assert!(started.elapsed() < Duration::from_secs(2), "timed out waiting for {}", what);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn panicking_parallel_handlers_release_their_budget() {
+    let stack = OSCStack::init("local:panicking-parallel".to_string())
+        .on_message_parallel("/boom", |_| panic!("handler failure"));
+    let controller = stack.controller();
+
+    for index in 0..10 {
+        stack.interpret(OscPacket::Message(message("/boom", vec![OscType::Int(index)])));
+    }
+    eventually("the budget to be released", || controller.queued_bytes() == 0);
+}
+
+#[test]
+fn cancel_messages_trip_running_operations() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let seen = cancelled.clone();
+    let stack = OSCStack::init("local:cancel-running".to_string())
+        .on_message_cancellable("/render", shard_by_arg(0), move |_, token| {
+            while !token.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            seen.store(true, Ordering::SeqCst);
+        });
+    let registry = stack.controller().cancellations();
+
+    stack.interpret(OscPacket::Message(message("/render", vec![OscType::String("render-42".to_string())])));
+    assert!(registry.is_registered("render-42"));
+
+    stack.interpret(OscPacket::Message(cancel::cancel_message("render-42")));
+    eventually("the operation to see its cancellation", || cancelled.load(Ordering::SeqCst));
+    eventually("the operation to finish", || !registry.is_registered("render-42"));
+}
+
+#[test]
+fn panicking_cancellable_operations_are_finished() {
+    let stack = OSCStack::init("local:cancel-panicking".to_string())
+        .on_message_cancellable("/render", shard_by_arg(0), |_, _| panic!("render failure"));
+    let registry = stack.controller().cancellations();
+
+    stack.interpret(OscPacket::Message(message("/render", vec![OscType::String("render-7".to_string())])));
+    eventually("the operation to be finished", || registry.registered_ids().is_empty());
+}
+
+#[test]
+fn immediate_bundles_keep_their_order_ahead_of_queued_messages() {
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocked = Mutex::new(blocked);
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    let stack = OSCStack::init("local:immediate-order".to_string())
+        .on_message_dedicated("/note", move |msg| {
+            let Some(OscType::Int(index)) = msg.args.first() else { return };
+            // The first message holds the handler until everything else is queued
+            if *index == 0 {
+                blocked.lock().unwrap().recv().unwrap();
+            }
+            log.lock().unwrap().push(*index);
+        });
+
+    let note = |index: i32| message("/note", vec![OscType::Int(index)]);
+    stack.interpret(OscPacket::Message(note(0)));
+    eventually("the handler to take the first message", || stack.controller().queued_bytes() == 0);
+    stack.interpret(OscPacket::Message(note(1)));
+    stack.interpret(OscPacket::Message(note(2)));
+    stack.interpret(OscPacket::Bundle(immediate::immediate_messages(vec![note(10), note(11), note(12)])));
+    stack.interpret(OscPacket::Bundle(immediate::immediate_messages(vec![note(20), note(21)])));
+    release.send(()).unwrap();
+
+    eventually("all messages to be handled", || received.lock().unwrap().len() == 8);
+    assert_eq!(vec![0, 10, 11, 12, 20, 21, 1, 2], *received.lock().unwrap());
+}
+
+// Runs a stack on a free UDP port for the rest of the test process, returning its address
+fn spawn_udp_stack(configure: fn(OSCStack<'static>) -> OSCStack<'static>) -> SocketAddr {
+    let addr = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    std::thread::spawn(move || configure(OSCStack::init(addr.to_string())).begin());
+    addr
+}
+
+// Sends the packet from the socket until the stack answers, since it may still be starting
+fn request(socket: &UdpSocket, target: SocketAddr, packet: &OscPacket) -> (OscPacket, SocketAddr) {
+    socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    let bytes = rosc::encoder::encode(packet).unwrap();
+    let mut buffer = [0u8; 2048];
+    let started = Instant::now();
+    loop {
+        socket.send_to(&bytes, target).unwrap();
+        if let Ok((size, from)) = socket.recv_from(&mut buffer) {
+            return (rosc::decoder::decode_udp(&buffer[..size]).unwrap().1, from);
+        }
+        assert!(started.elapsed() < Duration::from_secs(2), "no answer from {}", target);
+    }
+}
+
+#[test]
+fn echoes_are_answered_from_the_stack_address() {
+    let stack_addr = spawn_udp_stack(|stack| stack.echo(true));
+
+    for _ in 0..20 {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (reply, from) = request(&peer, stack_addr, &OscPacket::Message(echo::ping_message()));
+        assert_eq!(from, stack_addr);
+        let OscPacket::Message(reply) = reply else { panic!("Expected an echo reply, got {:?}", reply) };
+        assert_eq!(reply.addr, echo::ECHO_REPLY_ADDR);
+    }
+}
+
+fn acknowledge(msg: OscMessage, ctx: &HandlerContext) {
+    ctx.reply_message(reply::ok_reply(&msg)).unwrap();
+}
+
+#[test]
+fn handler_replies_are_sent_from_the_stack_address() {
+    let stack_addr = spawn_udp_stack(|stack| stack.on_message_with_context("/render", &acknowledge));
+
+    for _ in 0..20 {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (reply, from) = request(&peer, stack_addr, &OscPacket::Message(message("/render", vec![])));
+        assert_eq!(from, stack_addr);
+        assert_eq!(reply, OscPacket::Message(reply::ok_reply(&message("/render", vec![]))));
+    }
+}
+
+#[test]
+fn sequence_tracking_follows_restarted_senders() {
+    let source: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+    let mut tracker = SequenceTracker::new();
+    for seq in 0..5000 {
+        tracker.observe(source, seq);
+    }
+
+    // Far behind: restarted right away
+    assert_eq!(tracker.observe(source, 0), SequenceEvent::Restarted(5000, 0));
+    assert_eq!(tracker.observe(source, 1), SequenceEvent::InOrder);
+
+    // Slightly behind: late, until it keeps happening
+    for seq in 100..110 {
+        tracker.observe(source, seq);
+    }
+    let events: Vec<SequenceEvent> = (10..18).map(|seq| tracker.observe(source, seq)).collect();
+    assert!(events[..7].iter().all(|event| matches!(event, SequenceEvent::Reordered(110, _))));
+    assert_eq!(events[7], SequenceEvent::Restarted(110, 17));
+    assert_eq!(tracker.observe(source, 18), SequenceEvent::InOrder);
+}
+
+#[test]
+fn gaps_count_on_across_the_sequence_wrap() {
+    assert_eq!(sequence::missing_between(42, 45), 3);
+    assert_eq!(sequence::missing_between(i32::MAX, i32::MIN + 2), 3);
+    assert_eq!(sequence::missing_between(-5, i32::MAX), i32::MAX as u64 + 5);
+    assert_eq!(sequence::reported_missing(&sequence::gap_report(-5, i32::MAX)), Some(i32::MAX as u64 + 5));
+
+    // The gap the tracker sees is counted the same way as the report sent for it
+    let source = "127.0.0.1:9000".parse().unwrap();
+    let mut tracker = SequenceTracker::new();
+    tracker.observe(source, -6);
+    assert_eq!(tracker.observe(source, i32::MAX), SequenceEvent::Gap(-5, i32::MAX));
+}
+
+fn ignore_timed(_: BigDecimal, _: OscMessage) {}
+
+fn ignore_reply(_: Reply) {}
+
+#[test]
+fn hellos_are_answered_with_the_stack_capabilities() {
+    let stack_addr = spawn_udp_stack(|stack| stack.hello("sampler").on_timed("/note", &ignore_timed).on_reply(&ignore_reply));
+
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let greeting = OscPacket::Message(Capabilities::new("sequencer").hello_message());
+    let (answer, from) = request(&peer, stack_addr, &greeting);
+    assert_eq!(from, stack_addr);
+    let OscPacket::Message(answer) = answer else { panic!("Expected a hello reply, got {:?}", answer) };
+    assert_eq!(answer.addr, hello::HELLO_REPLY_ADDR);
+
+    let capabilities = Capabilities::from_message(&answer).unwrap();
+    assert_eq!(capabilities.name, "sampler");
+    assert!(capabilities.supports_tag("timed_msg"));
+    assert!(capabilities.supports_tag(immediate::IMMEDIATE_TAG));
+    assert!(!capabilities.supports_address("/note"));
+    assert!(capabilities.supports_address(hello::HELLO_ADDR));
+    assert!(capabilities.supports_address(reply::OK_REPLY_ADDR));
+    assert!(capabilities.supports_address(reply::ERROR_REPLY_ADDR));
+}
+
+static PROGRESS_SEEN: Mutex<Vec<f32>> = Mutex::new(Vec::new());
+static PROGRESS_REPLIES: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn progress_replies_reach_progress_handlers() {
+    let stack = OSCStack::init("local:progress".to_string())
+        .on_progress("render-42", &|progress| PROGRESS_SEEN.lock().unwrap().push(progress.pct))
+        .on_reply(&|_| { PROGRESS_REPLIES.fetch_add(1, Ordering::SeqCst); });
+
+    stack.interpret(OscPacket::Bundle(reply::progress_reply("render-42", 37.5)));
+    stack.interpret(OscPacket::Bundle(reply::progress_reply("render-43", 50.0)));
+    stack.interpret(OscPacket::Bundle(Progress::new("render-42", 150.0, "Done").to_bundle()));
+    assert_eq!(vec![37.5, 100.0], *PROGRESS_SEEN.lock().unwrap());
+    assert_eq!(PROGRESS_REPLIES.load(Ordering::SeqCst), 0);
+}
+
+// Runs a stack on a local endpoint for the rest of the test process, returning its controller
+fn spawn_local_stack(name: &str, configure: fn(OSCStack<'static>) -> OSCStack<'static>) -> StackController {
+    let (sender, receiver) = mpsc::channel();
+    let url = format!("local:{}", name);
+    std::thread::spawn(move || {
+        let stack = configure(OSCStack::init(url));
+        sender.send(stack.controller()).unwrap();
+        stack.begin();
+    });
+    receiver.recv().unwrap()
+}
+
+#[test]
+fn captures_do_not_overlap() {
+    let controller = spawn_local_stack("capture", |stack| stack);
+    assert_eq!(controller.capture_next(0, Duration::from_secs(10)), Ok(vec![]));
+
+    let capturing = controller.clone();
+    // Retried, as the probe below may briefly hold the capture itself
+    let first = std::thread::spawn(move || loop {
+        if let Ok(captured) = capturing.capture_next(2, Duration::from_secs(2)) {
+            return captured;
+        }
+    });
+    eventually("the first capture to start", || controller.capture_next(1, Duration::ZERO).is_err());
+
+    let client = OscClient::new("local:capture").unwrap();
+    client.send_message(message("/knob", vec![OscType::Int(1)])).unwrap();
+    client.send_message(message("/knob", vec![OscType::Int(2)])).unwrap();
+    assert_eq!(first.join().unwrap().len(), 2);
+}
+
+static RELOAD_PROBES: AtomicUsize = AtomicUsize::new(0);
+static RELOAD_IGNORED: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn reloads_apply_to_the_packet_the_stack_was_waiting_for() {
+    let controller = spawn_local_stack("reload", |stack| stack
+        .on_message("/probe", &|_| { RELOAD_PROBES.fetch_add(1, Ordering::SeqCst); })
+        .on_message("/ignored", &|_| { RELOAD_IGNORED.fetch_add(1, Ordering::SeqCst); }));
+
+    // Until the stack is up and waiting
+    let client = OscClient::new("local:reload").unwrap();
+    eventually("the stack to handle a probe", || {
+        let _ = client.send_message(message("/probe", vec![]));
+        RELOAD_PROBES.load(Ordering::SeqCst) > 0
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    let probes = RELOAD_PROBES.load(Ordering::SeqCst);
+
+    controller.reload(StackConfig { ignored_addresses: vec!["/ignored".to_string()], ..StackConfig::default() });
+    client.send_message(message("/ignored", vec![])).unwrap();
+    client.send_message(message("/probe", vec![])).unwrap();
+    eventually("the stack to handle the last probe", || RELOAD_PROBES.load(Ordering::SeqCst) > probes);
+    assert_eq!(RELOAD_IGNORED.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn clients_send_times_in_their_encoding() {
+    let mut receiver = OscReceiver::bind("local:float-times").unwrap();
+    let client = OscClient::new("local:float-times").unwrap().with_time_encoding(TimeEncoding::FloatArg);
+    let note = OscPacket::Message(message("/note", vec![OscType::Int(1)]));
+
+    for (time, expected) in [("0.25", TimeEncoding::FloatArg), ("123456.789", TimeEncoding::DecimalString)] {
+        let timed = TimedOSCPacket::new(time.parse().unwrap(), note.clone());
+        client.send(&OscPacket::Bundle(timed.to_bundle())).unwrap();
+
+        let received = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        let OscPacket::Bundle(bundle) = &received else { panic!("Expected a timed_msg bundle, got {:?}", received) };
+        let OscPacket::Message(info) = &bundle.content[1] else { panic!("Expected /timed_msg_info") };
+        assert_eq!(TimeEncoding::of(&info.args[0]), Some(expected), "time {}", time);
+        assert_eq!(TimedOSCPacket::from_bundle(TaggedBundle::new(bundle).unwrap()).unwrap(), timed);
+    }
+}
+
+#[test]
+fn rebind_backoff_is_bounded() {
+    let policy = SupervisionPolicy::default();
+    assert_eq!(policy.backoff(0), policy.initial_backoff);
+    assert_eq!(policy.backoff(1), policy.initial_backoff * 2);
+    assert_eq!(policy.backoff(policy.rebind_attempts_before_report), policy.max_backoff);
+    assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+}
+
+#[test]
+fn silent_peers_expire() {
+    let expired = Arc::new(Mutex::new(Vec::new()));
+    let table = PeerTable::new(Duration::from_millis(100));
+    let log = expired.clone();
+    table.on_expired(move |peer| log.lock().unwrap().push(peer.addr));
+    let stack_table = table.clone();
+    std::thread::spawn(move || OSCStack::init("local:peer-expiry".to_string()).track_peers(stack_table).begin());
+
+    // Until the stack is up and receiving
+    let client = OscClient::new("local:peer-expiry").unwrap();
+    eventually("the client to be seen", || {
+        let _ = client.send_message(message("/knob", vec![]));
+        table.get(LOCAL_SENDER).is_some()
+    });
+    eventually("the client to expire", || table.is_empty());
+    assert_eq!(vec![LOCAL_SENDER], *expired.lock().unwrap());
+}
+
+fn ignore_message(_: OscMessage) {}
+
+fn ignore_tbundle(_: TaggedBundle) {}
+
+#[test]
+fn schemas_can_be_shared_by_handlers() {
+    let note_on = || MessageSchema::new("/note_on").arg("freq", ArgType::Float);
+    let chord = || BundleSchema::new("chord").message("/note_on");
+    let stack = OSCStack::init("local:shared-schemas".to_string())
+        .on_message_with_schema(note_on(), &ignore_message)
+        .on_message_with_schema(note_on(), &ignore_message)
+        .on_tbundle_with_schema(chord(), &ignore_tbundle)
+        .on_tbundle_with_schema(chord(), &ignore_tbundle);
+    assert!(stack.describe_schemas().contains("/note_on"));
+}
+
+#[test]
+#[should_panic(expected = "Conflicting schemas registered for /note_on")]
+fn conflicting_message_schemas_are_rejected() {
+    let _ = OSCStack::init("local:conflicting-message-schemas".to_string())
+        .on_message_with_schema(MessageSchema::new("/note_on").arg("freq", ArgType::Float), &ignore_message)
+        .on_message_with_schema(MessageSchema::new("/note_on").arg("freq", ArgType::Int), &ignore_message);
+}
+
+#[test]
+#[should_panic(expected = "Conflicting schemas registered for chord")]
+fn conflicting_bundle_schemas_are_rejected() {
+    let _ = OSCStack::init("local:conflicting-bundle-schemas".to_string())
+        .on_tbundle_with_schema(BundleSchema::new("chord").message("/note_on"), &ignore_tbundle)
+        .on_tbundle_with_schema(BundleSchema::new("chord").message("/note_off"), &ignore_tbundle);
+}
+
+static ONCE_CALLS: AtomicUsize = AtomicUsize::new(0);
+static TEMPORARY_UNMATCHED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[test]
+fn temporary_handlers_stop_applying() {
+    let stack = OSCStack::init("local:temporary-handlers".to_string())
+        .on_message_once("/ack", &|_| { ONCE_CALLS.fetch_add(1, Ordering::SeqCst); })
+        .on_message_for("/knob", Duration::from_millis(50), &ignore_message)
+        .on_unmatched(&|packet| if let OscPacket::Message(msg) = packet {
+            TEMPORARY_UNMATCHED.lock().unwrap().push(msg.addr);
+        });
+
+    stack.interpret(OscPacket::Message(message("/ack", vec![])));
+    stack.interpret(OscPacket::Message(message("/ack", vec![])));
+    stack.interpret(OscPacket::Message(message("/knob", vec![])));
+    std::thread::sleep(Duration::from_millis(60));
+    stack.interpret(OscPacket::Message(message("/knob", vec![])));
+
+    assert_eq!(ONCE_CALLS.load(Ordering::SeqCst), 1);
+    assert_eq!(vec!["/ack".to_string(), "/knob".to_string()], *TEMPORARY_UNMATCHED.lock().unwrap());
+}
+
+#[test]
+fn funneled_bundles_can_be_awaited() {
+    let stack = OSCStack::init("local:await-funneled".to_string())
+        .funnel_tbundle("timed_msg")
+        .on_message("/s_new", &ignore_message);
+    let controller = stack.controller();
+    let waiter = std::thread::spawn(move || controller.await_tbundle("timed_msg", Duration::from_secs(2)));
+
+    let timed = TimedOSCPacket::new(BigDecimal::from(1), OscPacket::Message(message("/s_new", vec![])));
+    std::thread::sleep(Duration::from_millis(50));
+    stack.interpret(OscPacket::Bundle(timed.to_bundle()));
+
+    let received = waiter.join().unwrap().unwrap();
+    assert_eq!(TimedOSCPacket::from_bundle(received).unwrap(), timed);
+    assert!(stack.controller().await_tbundle("timed_msg", Duration::from_millis(10)).is_err());
+}
+
+static PANICS: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn panics_clear_queues_and_cancel_operations() {
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocked = Mutex::new(blocked);
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    let stack = OSCStack::init("local:panic-clears".to_string())
+        .on_message_dedicated("/note", move |msg| {
+            let Some(OscType::Int(index)) = msg.args.first() else { return };
+            if *index == 0 {
+                blocked.lock().unwrap().recv().unwrap();
+            }
+            log.lock().unwrap().push(*index);
+        })
+        .on_message_cancellable("/render", shard_by_arg(0), |_, token| {
+            while !token.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        })
+        .on_panic(&|| { PANICS.fetch_add(1, Ordering::SeqCst); });
+    let controller = stack.controller();
+    let registry = controller.cancellations();
+
+    let note = |index: i32| OscPacket::Message(message("/note", vec![OscType::Int(index)]));
+    stack.interpret(note(0));
+    eventually("the handler to take the first message", || controller.queued_bytes() == 0);
+    stack.interpret(note(1));
+    stack.interpret(note(2));
+    stack.interpret(OscPacket::Message(message("/render", vec![OscType::String("render-1".to_string())])));
+    assert!(controller.queued_bytes() > 0);
+
+    stack.interpret(OscPacket::Bundle(immediate::panic_bundle()));
+    assert_eq!(PANICS.load(Ordering::SeqCst), 1);
+    assert_eq!(controller.queued_bytes(), 0);
+    eventually("the operation to be cancelled", || registry.registered_ids().is_empty());
+
+    release.send(()).unwrap();
+    stack.interpret(note(3));
+    eventually("the next message to be handled", || received.lock().unwrap().len() == 2);
+    assert_eq!(vec![0, 3], *received.lock().unwrap());
+}
+
+static SESSION_LOG: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+static FILTERED_LOG: Mutex<Vec<Option<String>>> = Mutex::new(Vec::new());
+
+fn note_on_in(session_id: Option<&str>) -> OscPacket {
+    let bundle = TaggedBundle::with_contents("note_on", vec![]).to_bundle();
+    OscPacket::Bundle(match session_id {
+        Some(id) => session::in_session(bundle, id),
+        None => bundle
+    })
+}
+
+#[test]
+fn session_handlers_only_see_their_session() {
+    let stack = OSCStack::init("local:session-handlers".to_string())
+        .session("live-a", |s| s.on_tbundle("note_on", &|_| SESSION_LOG.lock().unwrap().push("a")))
+        .session("live-b", |s| s.on_tbundle("note_on", &|_| SESSION_LOG.lock().unwrap().push("b")))
+        .on_tbundle("note_on", &|_| SESSION_LOG.lock().unwrap().push("any"));
+
+    stack.interpret(note_on_in(Some("live-a")));
+    stack.interpret(note_on_in(Some("live-b")));
+    stack.interpret(note_on_in(None));
+    assert_eq!(vec!["a", "any", "b", "any", "a", "b", "any"], *SESSION_LOG.lock().unwrap());
+}
+
+#[test]
+fn other_sessions_can_be_filtered_out() {
+    let stack = OSCStack::init("local:session-filter".to_string())
+        .only_session("live-a")
+        .on_tbundle("note_on", &|_| FILTERED_LOG.lock().unwrap().push(dispatch_context().session));
+
+    stack.interpret(note_on_in(Some("live-a")));
+    stack.interpret(note_on_in(Some("live-b")));
+    stack.interpret(note_on_in(None));
+    assert_eq!(vec![Some("live-a".to_string()), None], *FILTERED_LOG.lock().unwrap());
+}
+
+static INFO_LOG: Mutex<Vec<(Option<String>, Option<i32>)>> = Mutex::new(Vec::new());
+
+#[test]
+fn handlers_see_the_bundle_info() {
+    let stack = OSCStack::init("local:bundle-info".to_string())
+        .on_tbundle("note_on", &|bundle| INFO_LOG.lock().unwrap().push((bundle.info.session, bundle.info.seq)));
+
+    let header = message("/bundle_info", vec![OscType::String("note_on".to_string())]);
+    let mut bundle = TaggedBundle::with_contents("note_on", vec![]).to_bundle();
+    bundle.content[0] = OscPacket::Message(sequence::with_sequence(session::with_session(header, "live-a"), 41));
+    stack.interpret(OscPacket::Bundle(bundle));
+    stack.interpret(note_on_in(None));
+    assert_eq!(vec![(Some("live-a".to_string()), Some(41)), (None, None)], *INFO_LOG.lock().unwrap());
+}
+
+static PAUSE_LOG: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+
+fn log_knob(msg: OscMessage) {
+    if let Some(OscType::Int(value)) = msg.args.first() {
+        PAUSE_LOG.lock().unwrap().push(*value);
+    }
+}
+
+#[test]
+fn paused_stacks_buffer_or_drop_packets() {
+    let controller = spawn_local_stack("pause", |stack| stack.on_message("/knob", &log_knob));
+    let client = OscClient::new("local:pause").unwrap();
+    let knob = |value: i32| message("/knob", vec![OscType::Int(value)]);
+
+    controller.pause();
+    // Retried, as the stack may still be starting
+    eventually("the stack to listen", || client.send_message(knob(1)).is_ok());
+    eventually("the first packet to be buffered", || controller.queued_bytes() > 0);
+    let first_size = controller.queued_bytes();
+    client.send_message(knob(2)).unwrap();
+    eventually("the second packet to be buffered", || controller.queued_bytes() > first_size);
+    assert!(PAUSE_LOG.lock().unwrap().is_empty());
+
+    controller.resume();
+    eventually("the buffered packets to be dispatched", || PAUSE_LOG.lock().unwrap().len() == 2);
+    assert_eq!(controller.queued_bytes(), 0);
+
+    controller.set_pause_policy(PausePolicy::Drop);
+    controller.pause();
+    client.send_message(knob(3)).unwrap();
+    eventually("the packet to be dropped", || controller.metrics().paused_drops == 1);
+    controller.resume();
+    client.send_message(knob(4)).unwrap();
+    eventually("the next packet to be dispatched", || PAUSE_LOG.lock().unwrap().len() == 3);
+    assert_eq!(vec![1, 2, 4], *PAUSE_LOG.lock().unwrap());
+}
+
+#[test]
+fn paced_sends_keep_their_order_and_spacing() {
+    let mut receiver = OscReceiver::bind("local:pacing").unwrap();
+    let gap = Duration::from_millis(10);
+    let client = Arc::new(OscClient::new("local:pacing").unwrap().with_pacing(Pacing::Gap(gap)));
+    let note = |thread: i32, index: i32| OscPacket::Message(message("/note", vec![OscType::Int(thread), OscType::Int(index)]));
+
+    let started = Instant::now();
+    let senders: Vec<_> = (0..2).map(|thread| {
+        let client = client.clone();
+        std::thread::spawn(move || client.send_all(&(0..3).map(|index| note(thread, index)).collect::<Vec<_>>()).unwrap())
+    }).collect();
+    senders.into_iter().for_each(|sender| sender.join().unwrap());
+    // Six sends, each after the gap of the one before
+    assert!(started.elapsed() >= gap * 5);
+
+    let received: Vec<OscPacket> = (0..6).map(|_| receiver.recv_timeout(Duration::from_secs(1)).unwrap()).collect();
+    for thread in 0..2 {
+        let sent_by_thread: Vec<OscPacket> = received.iter()
+            .filter(|packet| matches!(packet, OscPacket::Message(msg) if msg.args[0] == OscType::Int(thread)))
+            .cloned()
+            .collect();
+        assert_eq!(sent_by_thread, (0..3).map(|index| note(thread, index)).collect::<Vec<_>>());
+    }
+
+    assert_eq!(Pacing::Bandwidth(1000).delay_after(250), Duration::from_millis(250));
+    assert_eq!(Pacing::Bandwidth(0).delay_after(250), Duration::ZERO);
+}
+
+fn small_rate(hold: Duration) -> AdaptiveRate {
+    AdaptiveRate::new(AdaptiveConfig {
+        min_rate: 10_000,
+        max_rate: 120_000,
+        initial_rate: 100_000,
+        increase: 10_000,
+        decrease: 0.5,
+        min_batch: 1,
+        max_batch: 5,
+        initial_batch: 4,
+        slow_factor: 3.0,
+        hold
+    })
+}
+
+#[test]
+fn received_feedback_adjusts_the_rate() {
+    let rate = small_rate(Duration::from_secs(3600));
+    let stack = OSCStack::init("local:rate-feedback".to_string()).adapt_rate(rate.clone());
+
+    // Additive increase up to the bounds
+    stack.interpret(OscPacket::Message(reply::ok_reply(&message("/note", vec![]))));
+    assert_eq!((rate.rate(), rate.batch_size()), (110_000, 5));
+    stack.interpret(OscPacket::Message(reply::ok_reply(&message("/note", vec![]))));
+    assert_eq!((rate.rate(), rate.batch_size()), (120_000, 5));
+
+    // Multiplicative decrease, once per hold
+    stack.interpret(OscPacket::Message(sequence::gap_report(10, 14)));
+    assert_eq!((rate.rate(), rate.batch_size()), (60_000, 2));
+    stack.interpret(OscPacket::Message(sequence::gap_report(20, 22)));
+    assert_eq!((rate.rate(), rate.batch_size()), (60_000, 2));
+
+    // Clients sharing the rate split queue updates into batches
+    let mut receiver = OscReceiver::bind("local:rate-batches").unwrap();
+    let client = OscClient::new("local:rate-batches").unwrap().with_adaptive_rate(rate.clone());
+    let items = (0..5).map(|index| QueueItem::new(&index.to_string(), TimedOSCPacket::new(BigDecimal::from(index), OscPacket::Message(message("/note", vec![]))))).collect();
+    client.send_queue_update(&QueueUpdate::Add { queue: "loop-a".to_string(), items }).unwrap();
+    let sizes: Vec<usize> = (0..3).map(|_| match receiver.recv_timeout(Duration::from_secs(1)).unwrap() {
+        OscPacket::Bundle(bundle) => match QueueUpdate::from_tagged_bundle(TaggedBundle::new(&bundle).unwrap()).unwrap() {
+            QueueUpdate::Add { items, .. } => items.len(),
+            other => panic!("expected an add, got {:?}", other)
+        },
+        other => panic!("expected a bundle, got {:?}", other)
+    }).collect();
+    assert_eq!(sizes, vec![2, 2, 1]);
+}
+
+#[test]
+fn slow_round_trips_count_as_congestion() {
+    let rate = small_rate(Duration::ZERO);
+    rate.on_round_trip(Duration::from_millis(10));
+    assert_eq!(rate.rate(), 110_000);
+    rate.on_round_trip(Duration::from_millis(25));
+    assert_eq!(rate.rate(), 120_000);
+    rate.on_round_trip(Duration::from_millis(40));
+    assert_eq!((rate.rate(), rate.batch_size()), (60_000, 2));
+
+    // Never below the bounds
+    for _ in 0..10 {
+        rate.on_loss(1);
+    }
+    assert_eq!((rate.rate(), rate.batch_size()), (10_000, 1));
+    rate.on_loss(0);
+    rate.on_ack();
+    assert_eq!((rate.rate(), rate.batch_size()), (20_000, 2));
+}
+
+static RECEIVE_TIMES: Mutex<Vec<(Option<ReceiveTime>, Instant)>> = Mutex::new(Vec::new());
+
+fn log_receive_time(_: OscMessage, ctx: &HandlerContext) {
+    RECEIVE_TIMES.lock().unwrap().push((ctx.received(), Instant::now()));
+}
+
+#[test]
+fn packets_keep_the_time_they_arrived() {
+    let stack = OSCStack::init("local:receive-times".to_string()).on_message_with_context("/knob", &log_receive_time);
+    stack.interpret(OscPacket::Message(message("/knob", vec![])));
+    assert_eq!(RECEIVE_TIMES.lock().unwrap().pop().unwrap().0, None);
+
+    let controller = spawn_local_stack("receive-times-paused", |stack| stack.on_message_with_context("/knob", &log_receive_time));
+    let client = OscClient::new("local:receive-times-paused").unwrap();
+    controller.pause();
+    let sent = Instant::now();
+    eventually("the stack to listen", || client.send_message(message("/knob", vec![])).is_ok());
+    eventually("the packet to be buffered", || controller.queued_bytes() > 0);
+    std::thread::sleep(Duration::from_millis(50));
+    controller.resume();
+    eventually("the packet to be dispatched", || !RECEIVE_TIMES.lock().unwrap().is_empty());
+
+    let (received, dispatched) = RECEIVE_TIMES.lock().unwrap().pop().unwrap();
+    let received = received.expect("packets from the wire have a receive time");
+    assert!(received.instant >= sent);
+    assert!(dispatched.duration_since(received.instant) >= Duration::from_millis(50));
+}
+
+static EDGE_HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn packets_at_the_capture_timeout_are_not_lost() {
+    let controller = spawn_local_stack("capture-edge", |stack| {
+        stack.on_message("/knob", &|_| { EDGE_HANDLED.fetch_add(1, Ordering::SeqCst); })
+    });
+    let client = OscClient::new("local:capture-edge").unwrap();
+    eventually("the stack to listen", || client.send_message(message("/knob", vec![])).is_ok());
+
+    // Each packet is sent close to the timeout of a capture, so some arrive right at the edge
+    let mut captured = 0;
+    for _ in 0..500 {
+        let capturing = controller.clone();
+        let capture = std::thread::spawn(move || capturing.capture_next(1, Duration::from_millis(1)).unwrap());
+        std::thread::sleep(Duration::from_micros(900));
+        client.send_message(message("/knob", vec![])).unwrap();
+        captured += capture.join().unwrap().len();
+    }
+    eventually("every packet to be captured or handled", || captured + EDGE_HANDLED.load(Ordering::SeqCst) == 501);
+}
@@ -0,0 +1,20 @@
+// Fixtures cover the model layer formats
+#![cfg(feature = "model")]
+
+use jdw_osc_lib::compat;
+
+#[test]
+fn frozen_fixtures_still_build_and_parse() {
+    if let Err(e) = compat::verify_wire_compat() {
+        panic!("{}", e);
+    }
+}
+
+#[test]
+fn fixture_names_are_unique() {
+    let mut names = compat::fixture_names();
+    let count = names.len();
+    names.sort();
+    names.dedup();
+    assert_eq!(count, names.len());
+}